@@ -0,0 +1,156 @@
+// ****************************************************************************
+// The command line interface of the `tempesta` binary: reads the global
+// options, then runs the command with its arguments and exits.
+// ****************************************************************************
+
+use crate::methods;
+use crate::methods::common::CONFIG_FILE_PATH;
+use std::env;
+use std::path::PathBuf;
+
+/// Runs the command given in the arguments of the process and exits.
+pub fn main() {
+  let args: Vec<String> = env::args().collect();
+  let mut config_path: Option<PathBuf> = None;
+  let mut processed_args: Vec<String> = Vec::new();
+
+  // Manually parse for --config or -c flag
+  let mut i = 0;
+  while i < args.len() {
+    if args[i] == "--config" || args[i] == "-c" {
+      if i + 1 < args.len() {
+        config_path = Some(PathBuf::from(&args[i + 1]));
+        i += 2; // Consume flag and value
+        continue;
+      } else {
+        eprintln!("Error: --config requires a path argument.");
+        std::process::exit(1);
+      }
+    }
+    processed_args.push(args[i].clone());
+    i += 1;
+  }
+
+  // If --config was not provided, check TEMPESTA_CONFIG environment variable
+  if config_path.is_none() {
+    if let Ok(env_config) = env::var("TEMPESTA_CONFIG") {
+      config_path = Some(PathBuf::from(env_config));
+    }
+  }
+
+  // Initialize the global CONFIG_FILE_PATH
+  if let Some(path) = config_path {
+    CONFIG_FILE_PATH
+      .set(path)
+      .expect("Failed to set config file path");
+  }
+
+  // Now use processed_args for command dispatch
+  if processed_args.len() < 2 {
+    eprintln!("Usage: tempesta <command> [options]");
+    std::process::exit(1);
+  }
+  let command = &processed_args[1];
+  if !matches!(
+    command.as_str(),
+    "completion"
+      | "init"
+      | "i"
+      | "merge-driver"
+      | "--help"
+      | "-h"
+      | "--version"
+      | "-v"
+  ) {
+    methods::journal::prepare();
+  }
+  match command.as_str() {
+    "add" | "a" => methods::add::run(processed_args),
+    "batch" => methods::batch::run(processed_args),
+    "check" => methods::check::run(processed_args),
+    "completion" => methods::completion::run(processed_args),
+    "config" | "c" => methods::config::run(),
+    "dedupe" => methods::dedupe::run(processed_args),
+    "edit" | "e" => methods::edit::run(processed_args),
+    "get" | "g" => methods::get::run(processed_args),
+    "init" | "i" => methods::init::run(),
+    "list" | "l" | "ls" => methods::list::run(processed_args),
+    "log" => methods::log::run(processed_args),
+    "merge-driver" => methods::merge_driver::run(processed_args),
+    "migrate-store" => methods::migrate_store::run(processed_args),
+    "move" | "m" | "mv" => methods::r#move::run(processed_args),
+    "open" | "o" => methods::open::run(processed_args),
+    "queue" | "q" => methods::queue::run(processed_args),
+    "remove" | "r" | "rm" => methods::remove::run(processed_args),
+    "restore" => methods::restore::run(processed_args),
+    "review" => methods::review::run(processed_args),
+    "serve" => methods::serve::run(processed_args),
+    "status" => methods::status::run(processed_args),
+    "sync" | "s" => methods::sync::run(),
+    "trash" | "t" => methods::trash::run(processed_args),
+    "undo" => methods::undo::run(),
+    "update" | "u" => methods::update::run(processed_args),
+    "verify" => methods::verify::run(processed_args),
+    "--help" | "-h" => print_help(),
+    "--version" | "-v" => print_version(),
+    _ => {
+      eprintln!("Unknown command: {}", command);
+      eprintln!(
+                "Available commands: [a]dd, batch, check, dedupe, [e]dit, [i]nit, [l]ist, log, migrate-store, [m]ove, [o]pen, [q]ueue, [r]emove, restore, review, serve, status, [s]ync, [t]rash, undo, [u]pdate, verify"
+            );
+      std::process::exit(1);
+    }
+  }
+  methods::common::wait_for_background_pushes();
+  std::process::exit(0);
+}
+
+// ****************************************************************************
+// Print the version of the package
+// ****************************************************************************
+fn print_version() {
+  println!("Tempesta version: {}", env!("CARGO_PKG_VERSION"));
+}
+
+// ****************************************************************************
+// Print help
+// ****************************************************************************
+fn print_help() {
+  println!("Tempesta - The lightest and fastest CLI for managing bookmarks");
+  println!();
+  println!("USAGE:");
+  println!("    tempesta <COMMAND> [OPTIONS]");
+  println!();
+  println!("COMMANDS:");
+  println!("    add, a          Add a new bookmark");
+  println!("    batch           Apply many changes with a single commit");
+  println!("    check           Check that bookmarked pages are reachable");
+  println!("    completion      Generate shell completion");
+  println!("    config, c       Show configuration");
+  println!("    dedupe          Merge bookmarks pointing to the same URL");
+  println!("    edit, e         Edit a bookmark");
+  println!("    get, g          Get bookmark URL");
+  println!("    init, i         Initialize configuration");
+  println!("    list, l, ls     List all bookmarks");
+  println!("    log             Show the history of a bookmark or the store");
+  println!("    merge-driver    Git merge driver for bookmark files");
+  println!("    migrate-store   Move the bookmarks to another storage backend");
+  println!("    move, m, mv     Move/rename a bookmark");
+  println!("    open, o         Open a bookmark in browser");
+  println!("    queue, q        Manage the reading queue");
+  println!("    remove, r, rm   Remove a bookmark");
+  println!("    restore         Restore a bookmark from the history");
+  println!("    review          Review expired and stale bookmarks");
+  println!("    serve           Serve the bookmarks on localhost");
+  println!("    status          Show the state of the store");
+  println!("    sync, s         Pull and push the changes of the store");
+  println!("    trash, t        Manage removed bookmarks");
+  println!("    undo            Undo the last change");
+  println!("    update, u       Update a bookmark");
+  println!("    verify          Verify the signatures of the commits");
+  println!();
+  println!("OPTIONS:");
+  println!("    --config, -c <PATH>    Use custom config file path");
+  println!("    --help, -h             Show this help message");
+  println!("    --version, -v          Show version information");
+}
@@ -3,13 +3,23 @@ _tempesta() {
 
     # When only the command is entered, complete the subcommands.
     if [ $COMP_CWORD -eq 1 ]; then
-        local subcommands="add edit list move open remove update a e l m o r u"
+        local subcommands="add batch check dedupe edit list log merge-driver migrate-store move open queue remove restore review serve status sync trash undo update verify a e l m o q r s t u"
         COMPREPLY=( $( compgen -W "$subcommands" -- "${COMP_WORDS[1]}" ) )
         return 0
     fi
 
     subcmd="${COMP_WORDS[1]}"
 
+    if [[ $COMP_CWORD -eq 2 && ( $subcmd == 'queue' || $subcmd == 'q' ) ]]; then
+        COMPREPLY=( $( compgen -W "add next list" -- "${COMP_WORDS[2]}" ) )
+        return 0
+    fi
+
+    if [[ $COMP_CWORD -eq 2 && ( $subcmd == 'trash' || $subcmd == 't' ) ]]; then
+        COMPREPLY=( $( compgen -W "list restore empty" -- "${COMP_WORDS[2]}" ) )
+        return 0
+    fi
+
     # Handle second argument for all commands
     if [[ $COMP_CWORD -eq 2 ]]; then
         cur="${COMP_WORDS[2]}"
@@ -24,22 +34,10 @@ _tempesta() {
     fi
 }
 
-# Function to get the bookmark directory from the `tempesta config` command
-_get_bookmark_directory() {
-  tempesta config | awk -F': ' '/Bookmark store directory:/ {print $2}' | xargs
-}
-
 _tempesta_complete_entries_helper() {
-    # local prefix="${BOOKMARK_STORE_DIR:-$HOME/.bookmark-store}"
-    # Set the prefix dynamically
-    local prefix="$(_get_bookmark_directory)"
-    local prefix=$(eval echo $prefix)
-
-    # Find all *.toml files (ignoring .git and .gpg-id), remove the store prefix and the .toml extension,
-    # escape backslashes and colons, and sort the list.
-    find -L "$prefix" \( -name .git -o -name .gpg-id \) -prune -o -type f -name "*.toml" -print 2>/dev/null \
-      | sed -e "s#${prefix}/\{0,1\}##" -e 's#\.toml$##' -e 's#\\#\\\\#g' -e 's#:#\\:#g' \
-      | sort
+    # Ask tempesta for the bookmarks, so that every store backend is completed,
+    # and keep the paths before the " :: " divisor.
+    tempesta list 2>/dev/null | sed -e 's# :: .*$##'
 }
 
 complete -F _tempesta tempesta
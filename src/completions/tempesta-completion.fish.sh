@@ -1,18 +1,7 @@
-function _get_bookmark_directory
-    tempesta config | string match -r 'Bookmark store directory: *(.+)' | string replace -r 'Bookmark store directory: *' ''
-end
-
 function _tempesta_complete_entries_helper
-    # Dynamically infer the prefix from the tempesta config command
-    set -l prefix (_get_bookmark_directory)
-    # Expand tilde if present
-    if string match -q "~*" "$prefix"
-      set prefix (eval echo $prefix)
-    end
-
-    find -L "$prefix" \( -name .git -o -name .gpg-id \) -prune -o -type f -name "*.toml" -print 2>/dev/null \
-    | sed -e "s#${prefix}/\{0,1\}##" -e 's#\.toml$##' -e 's#\\#\\\\#g' -e 's#:#\\:#g' \
-    | sort
+    # Ask tempesta for the bookmarks, so that every store backend is completed,
+    # and keep the paths before the " :: " divisor
+    tempesta list 2>/dev/null | string replace -r ' :: .*$' ''
 end
 
 function _tempesta_complete_entries
@@ -21,22 +10,43 @@ end
 
 # Subcommands
 complete -c tempesta -n '__fish_use_subcommand' -a "add" -d "Add a new bookmark"
+complete -c tempesta -n '__fish_use_subcommand' -a "batch" -d "Apply many changes with a single commit"
+complete -c tempesta -n '__fish_use_subcommand' -a "check" -d "Check that bookmarked pages are reachable"
+complete -c tempesta -n '__fish_use_subcommand' -a "dedupe" -d "Merge bookmarks pointing to the same URL"
 complete -c tempesta -n '__fish_use_subcommand' -a "edit" -d "Edit a bookmark"
 complete -c tempesta -n '__fish_use_subcommand' -a "list" -d "List bookmarks"
+complete -c tempesta -n '__fish_use_subcommand' -a "log" -d "Show the history of a bookmark or the store"
+complete -c tempesta -n '__fish_use_subcommand' -a "merge-driver" -d "Git merge driver for bookmark files"
+complete -c tempesta -n '__fish_use_subcommand' -a "migrate-store" -d "Move the bookmarks to another storage backend"
 complete -c tempesta -n '__fish_use_subcommand' -a "move" -d "Move a bookmark"
 complete -c tempesta -n '__fish_use_subcommand' -a "open" -d "Open a bookmark"
+complete -c tempesta -n '__fish_use_subcommand' -a "queue" -d "Manage the reading queue"
 complete -c tempesta -n '__fish_use_subcommand' -a "remove" -d "Remove a bookmark"
+complete -c tempesta -n '__fish_use_subcommand' -a "restore" -d "Restore a bookmark from the history"
+complete -c tempesta -n '__fish_use_subcommand' -a "review" -d "Review expired and stale bookmarks"
+complete -c tempesta -n '__fish_use_subcommand' -a "serve" -d "Serve the bookmarks on localhost"
+complete -c tempesta -n '__fish_use_subcommand' -a "status" -d "Show the state of the store"
+complete -c tempesta -n '__fish_use_subcommand' -a "sync" -d "Pull and push the changes of the store"
+complete -c tempesta -n '__fish_use_subcommand' -a "trash" -d "Manage removed bookmarks"
+complete -c tempesta -n '__fish_use_subcommand' -a "undo" -d "Undo the last change"
 complete -c tempesta -n '__fish_use_subcommand' -a "update" -d "Update a bookmark"
+complete -c tempesta -n '__fish_use_subcommand' -a "verify" -d "Verify the signatures of the commits"
 complete -c tempesta -n '__fish_use_subcommand' -a "a" -d "Shortcut for add"
 complete -c tempesta -n '__fish_use_subcommand' -a "e" -d "Shortcut for edit"
 complete -c tempesta -n '__fish_use_subcommand' -a "l" -d "Shortcut for list"
 complete -c tempesta -n '__fish_use_subcommand' -a "m" -d "Shortcut for move"
 complete -c tempesta -n '__fish_use_subcommand' -a "o" -d "Shortcut for open"
+complete -c tempesta -n '__fish_use_subcommand' -a "q" -d "Shortcut for queue"
 complete -c tempesta -n '__fish_use_subcommand' -a "r" -d "Shortcut for remove"
+complete -c tempesta -n '__fish_use_subcommand' -a "s" -d "Shortcut for sync"
+complete -c tempesta -n '__fish_use_subcommand' -a "t" -d "Shortcut for trash"
 complete -c tempesta -n '__fish_use_subcommand' -a "u" -d "Shortcut for update"
 
+complete -c tempesta -n "__fish_seen_subcommand_from queue q" -a "add next list"
+complete -c tempesta -n "__fish_seen_subcommand_from trash t" -a "list restore empty"
+
 # Completion for entries for all subcommands
-for cmd in add edit list move open remove update a e l m o r u
+for cmd in add check dedupe edit list log move open remove restore review update a e l m o r u
     complete -c tempesta -n "__fish_seen_subcommand_from $cmd" -a "(_tempesta_complete_entries)"
 end
 
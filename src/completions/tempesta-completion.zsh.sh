@@ -10,9 +10,15 @@ _tempesta () {
     shift words
 
     case "${cmd}" in
-      add|edit|list|open|remove|update|a|e|l|o|r|u)
+      add|check|dedupe|edit|list|log|open|remove|restore|review|update|a|e|l|o|r|u)
         _arguments : "1:bookmark:_tempesta_complete_entries_helper"
         ;;
+      queue|q)
+        _arguments : "1:action:(add next list)"
+        ;;
+      trash|t)
+        _arguments : "1:action:(list restore empty)"
+        ;;
       move|m)
         _arguments \
           "1:source:_tempesta_complete_entries_helper" \
@@ -25,32 +31,39 @@ _tempesta () {
     local -a subcommands
     subcommands=(
       "add:[a] Add a new bookmark"
+      "batch:Apply many changes with a single commit"
+      "check:Check that bookmarked pages are reachable"
+      "dedupe:Merge bookmarks pointing to the same URL"
       "edit:[e] Edit an existing bookmark"
       "list:[l] List bookmarks"
+      "log:Show the history of a bookmark or the store"
+      "merge-driver:Git merge driver for bookmark files"
+      "migrate-store:Move the bookmarks to another storage backend"
       "move:[m] Move an existing bookmark"
       "open:[o] Open a bookmark"
+      "queue:[q] Manage the reading queue"
       "remove:[r] Remove a bookmark"
+      "restore:Restore a bookmark from the history"
+      "review:Review expired and stale bookmarks"
+      "serve:Serve the bookmarks on localhost"
+      "status:Show the state of the store"
+      "sync:[s] Pull and push the changes of the store"
+      "trash:[t] Manage removed bookmarks"
+      "undo:Undo the last change"
       "update:[u] Update an existing bookmark"
+      "verify:Verify the signatures of the commits"
       "" # without this is printing \[
     )
     _describe -t commands 'tempesta' subcommands
   fi
 }
 
-# Function to get the bookmark directory from the `tempesta config` command
-_get_bookmark_directory() {
-  tempesta config | awk -F': ' '/Bookmark store directory:/ {print $2}' | xargs
-}
-
 _tempesta_complete_entries_helper () {
   local IFS=$'\n'
 
-  # local prefix="${BOOKMARK_STORE_DIR:-$HOME/.bookmark-store}"
-  # Set the prefix dynamically
-  local prefix="$(_get_bookmark_directory)"
-  local prefix=$(eval echo $prefix)
-
-  _values -C 'bookmarks' ${$(find -L "$prefix" \( -name .git -o -name .gpg-id \) -prune -o -type f -name "*.toml" -print 2>/dev/null | sed -e "s#${prefix}/\{0,1\}##" -e 's#\.toml$##' -e 's#\\#\\\\#g' -e 's#:#\\:#g' | sort):-""}
+  # Ask tempesta for the bookmarks, so that every store backend is completed,
+  # and keep the paths before the " :: " divisor
+  _values -C 'bookmarks' ${$(tempesta list 2>/dev/null | sed -e 's# :: .*$##'):-""}
 }
 
 compdef _tempesta tempesta
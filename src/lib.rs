@@ -0,0 +1,18 @@
+// ****************************************************************************
+// Tempesta as a library. `Store` adds, reads, changes and searches the
+// bookmarks of a store, committing the changes like the CLI does, and returns
+// errors instead of printing them and exiting. The `tempesta` binary is the
+// command line interface built on the same modules.
+// ****************************************************************************
+
+#[doc(hidden)]
+pub mod cli;
+pub(crate) mod methods;
+mod store;
+
+#[cfg(test)]
+mod store_tests;
+
+pub use methods::common::Bookmark;
+pub use methods::config::{Config, PushMode, StoreBackend};
+pub use store::{Error, Result, Store};
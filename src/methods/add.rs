@@ -1,21 +1,86 @@
 use super::common::{self, PanicOnError};
+use super::config;
+use super::metadata;
+use crate::store::{self, Store};
 use std::io::{self, Write};
+use std::time::Duration;
+
+const DEFAULT_FETCH_TIMEOUT: u64 = 5;
+const DEFAULT_PATH_TEMPLATE: &str = "{host}/{first-path-segment}";
 
 // ****************************************************************************
 // Add a bookmark
 // ****************************************************************************
-pub fn run(args: Vec<String>) {
-  if args.len() < 4 {
-    eprintln!("Usage: tempesta add <path> <url> [tags...]");
+pub fn run(mut args: Vec<String>) {
+  let config = config::load_config();
+  let raw = common::take_flag(&mut args, &["--raw"]);
+  let keyword = common::take_option(&mut args, "--keyword");
+  let fetch = if common::take_flag(&mut args, &["--no-fetch"]) {
+    common::take_flag(&mut args, &["--fetch"]);
+    false
+  } else {
+    common::take_flag(&mut args, &["--fetch"])
+      || config.fetch_metadata.unwrap_or(false)
+  };
+  // The path can be omitted: `tempesta add <url> [tags...]`
+  let auto_path = args.len() > 2
+    && common::looks_like_url(&args[2])
+    && !args.get(3).is_some_and(|arg| common::looks_like_url(arg));
+  if args.len() < 4 && !auto_path {
+    eprintln!(
+      "Usage: tempesta add [<path>] <url> [tags...] [--fetch|--no-fetch] [--raw] [--keyword <keyword>]"
+    );
     std::process::exit(1);
   }
-  let relative_path = &args[2];
-  common::validate_path(relative_path);
-  let toml_file_path = common::get_bookmark_file_path(relative_path);
-  if toml_file_path.exists() {
+  let (relative_path, url, tags) = if auto_path {
+    (None, &args[2], args[3..].to_vec())
+  } else {
+    (Some(args[2].clone()), &args[3], args[4..].to_vec())
+  };
+  if let Some(relative_path) = &relative_path {
+    common::validate_bookmark_path(relative_path);
+  }
+  common::validate_url(url);
+  if let Some(keyword) = &keyword {
+    common::check_keyword(keyword).unwrap_or_else(|e| panic!("{}", e));
+  }
+  let url = &common::clean_url_unless_raw(url, raw);
+  let mut bookmark = common::Bookmark {
+    url: url.to_owned(),
+    tags,
+    keyword,
+    created_at: Some(common::now_timestamp()),
+    ..Default::default()
+  };
+  if fetch && common::is_web_url(url) {
+    let timeout = config.fetch_timeout.unwrap_or(DEFAULT_FETCH_TIMEOUT);
+    match metadata::fetch(url, Duration::from_secs(timeout)) {
+      Ok(metadata) => {
+        if let Some(title) = &metadata.title {
+          println!("Title: {}", title);
+        }
+        let suggested_tags = metadata::suggest_tags(&metadata, &bookmark.tags);
+        if !suggested_tags.is_empty() {
+          println!("Suggested tags: {}", suggested_tags.join(" "));
+        }
+        bookmark.title = metadata.title;
+        bookmark.description = metadata.description;
+      }
+      Err(e) => eprintln!("Cannot fetch metadata for {}: {}", url, e),
+    }
+  }
+  let relative_path = relative_path.unwrap_or_else(|| {
+    let template = config
+      .path_template
+      .as_deref()
+      .unwrap_or(DEFAULT_PATH_TEMPLATE);
+    common::generate_bookmark_path(template, url, bookmark.title.as_deref())
+  });
+  common::validate_bookmark_path(&relative_path);
+  if common::bookmark_exists(&relative_path) {
     print!(
       "Bookmark already exists at {}. Overwrite? (y/N): ",
-      toml_file_path.display()
+      common::open_store().borrow().location(&relative_path)
     );
     io::stdout()
       .flush()
@@ -37,11 +102,11 @@ pub fn run(args: Vec<String>) {
     }
     println!("Overwriting file...");
   }
-  let url = &args[3];
-  common::validate_url(url);
-  let tags = &args[4..].to_vec();
-  common::store_bookmark(&toml_file_path, url, tags);
-  let comment = format!("Add bookmark {}", &relative_path);
-  common::git_commit(&comment);
+  for duplicate in common::find_duplicates(url) {
+    if duplicate != relative_path {
+      eprintln!("Warning: {} already points to the same URL", duplicate);
+    }
+  }
+  store::exit_on_error(Store::shared().put(&relative_path, bookmark));
   println!("Bookmark added successfully as {}", &relative_path);
 }
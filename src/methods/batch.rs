@@ -0,0 +1,256 @@
+// ****************************************************************************
+// Apply many changes with a single commit and push. The commands are read
+// from stdin or a file, one per line, either as command line arguments
+// (`add <path> <url> [tags...]`) or as JSON objects
+// (`{"command": "add", "path": "...", "url": "...", "tags": [...]}`).
+// Every line is validated first: if any of them fails nothing is written.
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::trash;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+enum Operation {
+  Add {
+    path: String,
+    url: String,
+    #[serde(default)]
+    tags: Vec<String>,
+  },
+  Update {
+    path: String,
+    url: String,
+    tags: Option<Vec<String>>,
+  },
+  Move {
+    from: String,
+    to: String,
+  },
+  Remove {
+    path: String,
+  },
+}
+
+pub fn run(mut args: Vec<String>) {
+  let file = common::take_option(&mut args, "--file");
+  let input = match &file {
+    Some(file) if file != "-" => fs::read_to_string(file).unwrap_or_else(|e| {
+      eprintln!("Cannot read {}: {}", file, e);
+      std::process::exit(1);
+    }),
+    _ => {
+      let mut input = String::new();
+      io::stdin()
+        .read_to_string(&mut input)
+        .panic_on_error("Failed to read input");
+      input
+    }
+  };
+  let mut operations = Vec::new();
+  let mut errors = Vec::new();
+  for (index, line) in input.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    match parse_line(line) {
+      Ok(operation) => operations.push((index + 1, operation)),
+      Err(e) => errors.push((index + 1, e)),
+    }
+  }
+  errors.extend(validate(&operations));
+  if !errors.is_empty() {
+    errors.sort_by_key(|(line, _)| *line);
+    for (line, error) in &errors {
+      eprintln!("line {}: {}", line, error);
+    }
+    eprintln!("Batch aborted: nothing was written.");
+    std::process::exit(1);
+  }
+  if operations.is_empty() {
+    println!("Nothing to do.");
+    return;
+  }
+  for (_, operation) in &operations {
+    apply(operation);
+  }
+  let comment = format!("Batch: {} changes", operations.len());
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "batch",
+      ..Default::default()
+    },
+  );
+  println!("Batch applied: {} changes", operations.len());
+}
+
+fn parse_line(line: &str) -> Result<Operation, String> {
+  if line.starts_with('{') {
+    return serde_json::from_str(line).map_err(|e| e.to_string());
+  }
+  let words = split_words(line)?;
+  let usage = |usage: &str| Err(format!("usage: {}", usage));
+  match words[0].as_str() {
+    "add" | "a" if words.len() >= 3 => Ok(Operation::Add {
+      path: words[1].clone(),
+      url: words[2].clone(),
+      tags: words[3..].to_vec(),
+    }),
+    "add" | "a" => usage("add <path> <url> [tags...]"),
+    "update" | "u" if words.len() >= 3 => Ok(Operation::Update {
+      path: words[1].clone(),
+      url: words[2].clone(),
+      tags: Some(words[3..].to_vec()),
+    }),
+    "update" | "u" => usage("update <path> <url> [tags...]"),
+    "move" | "m" | "mv" if words.len() == 3 => Ok(Operation::Move {
+      from: words[1].clone(),
+      to: words[2].clone(),
+    }),
+    "move" | "m" | "mv" => usage("move <path-from> <path-to>"),
+    "remove" | "r" | "rm" if words.len() == 2 => Ok(Operation::Remove {
+      path: words[1].clone(),
+    }),
+    "remove" | "r" | "rm" => usage("remove <path>"),
+    command => Err(format!("unknown command: {}", command)),
+  }
+}
+
+/// Splits a line into words, honouring single and double quotes and
+/// backslash escapes.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+  let mut words = Vec::new();
+  let mut word: Option<String> = None;
+  let mut quote = None;
+  let mut chars = line.chars();
+  while let Some(c) = chars.next() {
+    match (quote, c) {
+      (None, c) if c.is_whitespace() => words.extend(word.take()),
+      (None, '"' | '\'') => {
+        quote = Some(c);
+        word.get_or_insert_with(String::new);
+      }
+      (Some(q), c) if c == q => quote = None,
+      (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+      (_, '\\') => {
+        let escaped = chars.next().ok_or("trailing backslash")?;
+        word.get_or_insert_with(String::new).push(escaped);
+      }
+      (_, c) => word.get_or_insert_with(String::new).push(c),
+    }
+  }
+  if quote.is_some() {
+    return Err("unterminated quote".to_string());
+  }
+  words.extend(word);
+  Ok(words)
+}
+
+/// Checks every operation against the store as it will be after the
+/// previous ones, returning the errors with their line. The bookmarks that
+/// are updated or moved must load, so that applying cannot fail midway.
+fn validate(operations: &[(usize, Operation)]) -> Vec<(usize, String)> {
+  // Path in the batch and the path it is stored at, `None` once added
+  let mut paths: BTreeMap<String, Option<String>> =
+    common::find_toml_bookmark_files(None)
+      .into_iter()
+      .map(|path| (path.clone(), Some(path)))
+      .collect();
+  let check_loads = |stored: &Option<String>| match stored {
+    Some(stored) => common::open_store()
+      .borrow()
+      .load(stored)
+      .map(|_| ())
+      .map_err(|e| format!("bookmark {} cannot be loaded: {}", stored, e)),
+    None => Ok(()),
+  };
+  let mut errors = Vec::new();
+  for (line, operation) in operations {
+    let result = match operation {
+      Operation::Add { path, url, .. } => common::check_bookmark_path(path)
+        .and(common::check_url(url))
+        .and_then(|_| {
+          if paths.contains_key(path) {
+            Err(format!("bookmark {} already exists", path))
+          } else {
+            paths.insert(path.clone(), None);
+            Ok(())
+          }
+        }),
+      Operation::Update { path, url, .. } => common::check_bookmark_path(path)
+        .and(common::check_url(url))
+        .and_then(|_| match paths.get(path) {
+          Some(stored) => check_loads(stored),
+          None => Err(format!("bookmark {} does not exist", path)),
+        }),
+      Operation::Move { from, to } => common::check_bookmark_path(from)
+        .and(common::check_path(to))
+        .and_then(|_| {
+          let to = common::move_destination(from, to);
+          match paths.get(from) {
+            None => Err(format!("bookmark {} does not exist", from)),
+            Some(_) if paths.contains_key(&to) => {
+              Err(format!("bookmark {} already exists", to))
+            }
+            Some(stored) => check_loads(stored).map(|_| {
+              let stored = paths.remove(from).flatten();
+              paths.insert(to, stored);
+            }),
+          }
+        }),
+      Operation::Remove { path } => {
+        if paths.remove(path).is_some() {
+          Ok(())
+        } else {
+          Err(format!("bookmark {} does not exist", path))
+        }
+      }
+    };
+    if let Err(e) = result {
+      errors.push((*line, e));
+    }
+  }
+  errors
+}
+
+fn apply(operation: &Operation) {
+  match operation {
+    Operation::Add { path, url, tags } => {
+      let bookmark = Bookmark {
+        url: common::clean_url_unless_raw(url, false),
+        tags: tags.clone(),
+        created_at: Some(common::now_timestamp()),
+        ..Default::default()
+      };
+      common::store_bookmark(path, &bookmark);
+    }
+    Operation::Update { path, url, tags } => {
+      let mut bookmark = common::load_bookmark(path);
+      bookmark.url = common::clean_url_unless_raw(url, false);
+      if let Some(tags) = tags {
+        bookmark.tags = tags.clone();
+      }
+      bookmark.updated_at = Some(common::now_timestamp());
+      common::store_bookmark(path, &bookmark);
+    }
+    Operation::Move { from, to } => {
+      let to = common::move_destination(from, to);
+      common::rename_bookmark(from, &to);
+      println!("Bookmark moved from {} to {}", from, to);
+    }
+    Operation::Remove { path } => {
+      if trash::is_enabled() {
+        trash::trash_bookmark(path);
+      } else {
+        common::remove_bookmark(path);
+      }
+      println!("Bookmark removed: {}", path);
+    }
+  }
+}
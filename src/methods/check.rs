@@ -0,0 +1,244 @@
+// ****************************************************************************
+// Check that bookmarked web pages are still reachable. It reports the HTTP
+// status, the redirect chain and DNS/TLS/connection failures, optionally
+// writes the result into the bookmarks and updates permanently moved URLs.
+// ****************************************************************************
+
+use super::common::{self, PanicOnError};
+use super::config;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use url::Url;
+
+const DEFAULT_CHECK_CONCURRENCY: usize = 8;
+const DEFAULT_CHECK_TIMEOUT: u64 = 10;
+const MAX_REDIRECTS: usize = 10;
+
+enum Outcome {
+  Reached {
+    status: u16,
+    redirects: Vec<(u16, String)>,
+  },
+  Failed {
+    kind: &'static str,
+    detail: String,
+  },
+}
+
+impl Outcome {
+  fn is_broken(&self) -> bool {
+    match self {
+      Outcome::Reached { status, .. } => *status >= 400,
+      Outcome::Failed { .. } => true,
+    }
+  }
+
+  /// The URL the bookmark should point to when every redirect is permanent
+  /// and the target is reachable.
+  fn permanent_target(&self) -> Option<&String> {
+    match self {
+      Outcome::Reached { status, redirects }
+        if (200..300).contains(status)
+          && !redirects.is_empty()
+          && redirects.iter().all(|(code, _)| [301, 308].contains(code)) =>
+      {
+        redirects.last().map(|(_, url)| url)
+      }
+      _ => None,
+    }
+  }
+
+  fn status(&self) -> String {
+    match self {
+      Outcome::Reached { status, .. } => status.to_string(),
+      Outcome::Failed { kind, .. } => kind.to_string(),
+    }
+  }
+
+  fn describe(&self) -> String {
+    match self {
+      Outcome::Reached { status, redirects } => {
+        let mut description = String::new();
+        for (code, url) in redirects {
+          description.push_str(&format!("{} -> {} :: ", code, url));
+        }
+        description.push_str(&status.to_string());
+        description
+      }
+      Outcome::Failed { kind, detail } => format!("{}: {}", kind, detail),
+    }
+  }
+}
+
+pub fn run(mut args: Vec<String>) {
+  let config = config::load_config();
+  let tags = common::take_options(&mut args, "--tag");
+  let concurrency = match common::take_option(&mut args, "--concurrency") {
+    Some(value) => value.parse::<usize>().unwrap_or_else(|_| {
+      eprintln!("Invalid concurrency: {}", value);
+      std::process::exit(1);
+    }),
+    None => config
+      .check_concurrency
+      .unwrap_or(DEFAULT_CHECK_CONCURRENCY),
+  }
+  .max(1);
+  let timeout = match common::take_option(&mut args, "--timeout") {
+    Some(value) => common::parse_duration(&value).unwrap_or_else(|| {
+      eprintln!("Invalid timeout: {}", value);
+      std::process::exit(1);
+    }),
+    None => {
+      Duration::from_secs(config.check_timeout.unwrap_or(DEFAULT_CHECK_TIMEOUT))
+    }
+  };
+  let write = common::take_flag(&mut args, &["--write"]);
+  let fix_redirects = common::take_flag(&mut args, &["--fix-redirects"]);
+  let relative_paths = common::select_bookmarks(&args[2..], &tags);
+  let bookmarks: Vec<(String, String)> = relative_paths
+    .into_iter()
+    .map(|path| {
+      let url = common::load_bookmark(&path).url;
+      (path, url)
+    })
+    .filter(|(_, url)| common::is_web_url(url))
+    .collect();
+  if bookmarks.is_empty() {
+    println!("No web bookmarks to check.");
+    return;
+  }
+  let outcomes = check_all(&bookmarks, concurrency, timeout);
+  let mut broken = 0;
+  let mut changed = 0;
+  for ((relative_path, url), outcome) in bookmarks.iter().zip(&outcomes) {
+    println!("{} :: {} :: {}", relative_path, url, outcome.describe());
+    if outcome.is_broken() {
+      broken += 1;
+    }
+    let target = outcome.permanent_target().filter(|_| fix_redirects);
+    if !write && target.is_none() {
+      continue;
+    }
+    let mut bookmark = common::load_bookmark(relative_path);
+    if write {
+      bookmark.last_checked = Some(common::now_timestamp());
+      bookmark.status = Some(outcome.status());
+    }
+    if let Some(target) = target {
+      println!("Updating {} to {}", relative_path, target);
+      bookmark.url = target.clone();
+      bookmark.updated_at = Some(common::now_timestamp());
+    }
+    common::store_bookmark(relative_path, &bookmark);
+    changed += 1;
+  }
+  if changed > 0 {
+    common::git_commit(
+      &format!("Check {} bookmarks", changed),
+      common::CommitInfo {
+        action: "check",
+        ..Default::default()
+      },
+    );
+  }
+  println!("Checked {} bookmarks: {} broken", bookmarks.len(), broken);
+  if broken > 0 {
+    std::process::exit(1);
+  }
+}
+
+/// Checks the URLs with a pool of threads and returns the outcomes in the
+/// same order.
+fn check_all(
+  bookmarks: &[(String, String)],
+  concurrency: usize,
+  timeout: Duration,
+) -> Vec<Outcome> {
+  // Redirects are followed by hand to report the chain
+  let agent = common::http_agent(timeout, 0);
+  let queue = Arc::new(Mutex::new((0..bookmarks.len()).collect::<Vec<_>>()));
+  let (sender, receiver) = mpsc::channel();
+  thread::scope(|scope| {
+    for _ in 0..concurrency.min(bookmarks.len()) {
+      let queue = Arc::clone(&queue);
+      let sender = sender.clone();
+      let agent = agent.clone();
+      scope.spawn(move || loop {
+        let next = queue.lock().panic_on_error("Check queue poisoned").pop();
+        let Some(index) = next else {
+          break;
+        };
+        let outcome = check_url(&agent, &bookmarks[index].1);
+        sender
+          .send((index, outcome))
+          .panic_on_error("Failed to send check result");
+      });
+    }
+  });
+  drop(sender);
+  let mut outcomes: Vec<(usize, Outcome)> = receiver.into_iter().collect();
+  outcomes.sort_by_key(|(index, _)| *index);
+  outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+fn check_url(agent: &ureq::Agent, url: &str) -> Outcome {
+  let mut current = url.to_string();
+  let mut redirects = Vec::new();
+  for _ in 0..=MAX_REDIRECTS {
+    let (status, location) = match request(agent, "HEAD", &current) {
+      // Some servers do not implement HEAD
+      Ok((405 | 501, _)) => match request(agent, "GET", &current) {
+        Ok(response) => response,
+        Err(outcome) => return outcome,
+      },
+      Ok(response) => response,
+      Err(outcome) => return outcome,
+    };
+    let Some(location) = location.filter(|_| (300..400).contains(&status))
+    else {
+      return Outcome::Reached { status, redirects };
+    };
+    let next = Url::parse(&current)
+      .and_then(|base| base.join(&location))
+      .map(|next| next.to_string())
+      .unwrap_or(location);
+    redirects.push((status, next.clone()));
+    current = next;
+  }
+  Outcome::Failed {
+    kind: "too many redirects",
+    detail: current,
+  }
+}
+
+fn request(
+  agent: &ureq::Agent,
+  method: &str,
+  url: &str,
+) -> Result<(u16, Option<String>), Outcome> {
+  let response = match agent.request(method, url).call() {
+    Ok(response) => response,
+    Err(ureq::Error::Status(_, response)) => response,
+    Err(ureq::Error::Transport(transport)) => {
+      let detail = transport.to_string();
+      let lowercase = detail.to_lowercase();
+      let kind = match transport.kind() {
+        ureq::ErrorKind::Dns => "DNS error",
+        _ if lowercase.contains("tls") || lowercase.contains("certificate") => {
+          "TLS error"
+        }
+        _ if lowercase.contains("timed out") => "timeout",
+        ureq::ErrorKind::InvalidUrl | ureq::ErrorKind::UnknownScheme => {
+          "invalid URL"
+        }
+        _ => "connection failed",
+      };
+      return Err(Outcome::Failed { kind, detail });
+    }
+  };
+  Ok((
+    response.status(),
+    response.header("location").map(String::from),
+  ))
+}
@@ -1,18 +1,56 @@
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
 use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
 
-use super::config;
+#[cfg(feature = "native-git")]
+use super::config::GitBackend;
+use super::config::{self, PushMode, StoreBackend};
+use super::journal;
+#[cfg(feature = "native-git")]
+use super::native_git;
+use super::store::{self, BookmarkStore};
 
 pub static CONFIG_FILE_PATH: OnceLock<PathBuf> = OnceLock::new();
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Bookmark {
   pub url: String,
   pub tags: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  /// Short name resolving the bookmark in go-links, e.g. `go/<keyword>`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub keyword: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub browser: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub unread: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub created_at: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub updated_at: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub expires_at: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub review_after: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub last_checked: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub status: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub deleted_at: Option<String>,
 }
 
 pub trait PanicOnError<T> {
@@ -34,43 +72,70 @@ impl<T> PanicOnError<T> for Option<T> {
 }
 
 pub fn get_toml_bookmark_files(sub_path: Option<String>) -> Vec<String> {
-  let root_dir = get_bookmark_store_dir_path();
-  let search_dir = match &sub_path {
-    Some(sub) => {
-      let mut d = root_dir.clone();
-      d.push(sub);
-      d
-    }
-    None => root_dir.clone(),
-  };
-  let mut bookmarks = Vec::new();
-  fn visit_dir(dir: &PathBuf, root_dir: &PathBuf, bookmarks: &mut Vec<String>) {
-    if let Ok(entries) = fs::read_dir(dir) {
-      for entry in entries.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_dir() {
-          visit_dir(&path, root_dir, bookmarks); // recurse
-        } else if path.is_file()
-          && path.extension().is_some_and(|ext| ext == "toml")
-        {
-          if let Ok(relative_path) = path.strip_prefix(root_dir) {
-            if let Some(relative_str) = relative_path.to_str() {
-              let without_extension = relative_str.trim_end_matches(".toml");
-              bookmarks.push(without_extension.to_string());
-            }
-          }
-        }
-      }
-    }
-  }
-  visit_dir(&search_dir, &root_dir, &mut bookmarks);
+  let bookmarks = find_toml_bookmark_files(sub_path.clone());
   if bookmarks.is_empty() {
-    eprintln!("No .toml files found in {:?}", search_dir);
+    let mut search_dir = get_bookmark_store_dir_path();
+    search_dir.extend(sub_path);
+    eprintln!("No bookmarks found in {:?}", search_dir);
   }
-  bookmarks.sort();
   bookmarks
 }
 
+/// Same as `get_toml_bookmark_files` without complaining about an empty store.
+pub fn find_toml_bookmark_files(sub_path: Option<String>) -> Vec<String> {
+  open_store().borrow().list(sub_path.as_deref())
+}
+
+/// A store shared by every helper of a command.
+pub type SharedStore = Rc<RefCell<Box<dyn BookmarkStore>>>;
+
+thread_local! {
+  static OPENED_STORE: RefCell<Option<(StoreBackend, PathBuf, SharedStore)>> =
+    const { RefCell::new(None) };
+}
+
+/// The store of the bookmarks selected by `store_backend`. It is opened once
+/// per command, the single file backends parse the whole file when opened.
+pub fn open_store() -> SharedStore {
+  let backend = config::load_config().store_backend.unwrap_or_default();
+  let dir = get_bookmark_store_dir_path();
+  OPENED_STORE.with_borrow_mut(|opened| match opened {
+    Some((opened_backend, opened_dir, store))
+      if *opened_backend == backend && *opened_dir == dir =>
+    {
+      store.clone()
+    }
+    _ => {
+      let store = store::open(backend, &dir).unwrap_or_else(|e| {
+        eprintln!("Cannot open the bookmark store: {}", e);
+        std::process::exit(1);
+      });
+      let store = Rc::new(RefCell::new(store));
+      *opened = Some((backend, dir, store.clone()));
+      store
+    }
+  })
+}
+
+/// Opens the store again on the next `open_store`, after git changed its
+/// files.
+pub fn forget_store() {
+  OPENED_STORE.with_borrow_mut(|opened| *opened = None);
+}
+
+/// Exits unless every bookmark has its own file, which the commands reading
+/// the git history of a bookmark need.
+pub fn require_directory_store(feature: &str) {
+  let backend = config::load_config().store_backend.unwrap_or_default();
+  if backend != StoreBackend::Directory {
+    eprintln!(
+      "The {} store backend has no {}: migrate to the directory backend.",
+      backend, feature
+    );
+    std::process::exit(1);
+  }
+}
+
 pub fn get_bookmark_store_dir_path() -> PathBuf {
   let config = config::load_config();
   let expanded_dir = expand_tilde(&config.dir);
@@ -91,21 +156,260 @@ pub fn expand_tilde(path: &str) -> PathBuf {
 }
 
 pub fn validate_path(relative_path: &str) {
+  check_path(relative_path).unwrap_or_else(|e| panic!("{}", e));
+}
+
+pub fn validate_bookmark_path(relative_path: &str) {
+  check_bookmark_path(relative_path).unwrap_or_else(|e| panic!("{}", e));
+}
+
+/// A bookmark path, which cannot end with `/` like a directory.
+pub fn check_bookmark_path(relative_path: &str) -> Result<(), String> {
+  if relative_path.ends_with('/') {
+    return Err(format!(
+      "Invalid path {}: a bookmark path cannot end with '/'",
+      relative_path
+    ));
+  }
+  check_path(relative_path)
+}
+
+pub fn check_path(relative_path: &str) -> Result<(), String> {
   let re =
     Regex::new(r"^[a-zåäöA-ZÅÄÖ0-9_/.-]+$").panic_on_error("Invalid path");
   if !re.is_match(relative_path) {
-    panic!("Invalid path. Please avoid spaces and special characters.");
+    return Err(
+      "Invalid path. Please avoid spaces and special characters.".to_string(),
+    );
+  }
+  // `..` would leave the store and the hidden directories are its own state.
+  // A trailing `/` names a directory.
+  let segments = relative_path.strip_suffix('/').unwrap_or(relative_path);
+  if segments
+    .split('/')
+    .any(|segment| segment.is_empty() || segment.starts_with('.'))
+  {
+    return Err(
+      "Invalid path. Please avoid empty segments and segments starting with '.'."
+        .to_string(),
+    );
   }
+  Ok(())
 }
 
+/// A keyword is a single path segment, so that `go/<keyword>/<extra>` is not
+/// mistaken for a bookmark path.
+pub fn check_keyword(keyword: &str) -> Result<(), String> {
+  if keyword.contains('/') || check_path(keyword).is_err() {
+    return Err(format!(
+      "Invalid keyword {}. Please use a single word without '/'.",
+      keyword
+    ));
+  }
+  Ok(())
+}
+
+/// The bookmark other than `relative_path` with the keyword, ignoring the
+/// case like the go-links do. A keyword resolves a single bookmark.
+pub fn find_keyword_owner(
+  store: &dyn BookmarkStore,
+  keyword: &str,
+  relative_path: &str,
+) -> Option<String> {
+  store
+    .load_all(None)
+    .into_iter()
+    .find(|(path, bookmark)| {
+      path != relative_path
+        && bookmark.as_ref().is_ok_and(|bookmark| {
+          bookmark
+            .keyword
+            .as_ref()
+            .is_some_and(|other| other.to_lowercase() == keyword.to_lowercase())
+        })
+    })
+    .map(|(path, _)| path)
+}
+
+pub const DEFAULT_ALLOWED_SCHEMES: &[&str] = &[
+  "http", "https", "ftp", "sftp", "file", "mailto", "ssh", "tel", "vscode",
+  "obsidian", "zotero", "slack", "magnet",
+];
+
+const WEB_SCHEMES: &[&str] = &["http", "https"];
+
 pub fn validate_url(url: &str) {
-  let re = Regex::new(r"^(https?|ftp)://[^\s/$.?#].[^\s]*$")
-    .panic_on_error("Invalid url format");
+  check_url(url).unwrap_or_else(|e| panic!("{}", e));
+}
+
+pub fn check_url(url: &str) -> Result<(), String> {
+  check_url_with(url, config::load_config().allowed_schemes.as_deref())
+}
+
+/// Checks the URL against the allowed schemes, the default ones when `None`.
+pub fn check_url_with(
+  url: &str,
+  allowed_schemes: Option<&[String]>,
+) -> Result<(), String> {
+  const INVALID_URL: &str =
+    "Invalid URL. Please use a proper format (e.g., https://example.com).";
+  if is_local_path(url) {
+    return Ok(());
+  }
+  let scheme = get_url_scheme(url).ok_or(INVALID_URL)?;
+  let allowed = match allowed_schemes {
+    Some(schemes) => schemes.iter().any(|s| s.eq_ignore_ascii_case(&scheme)),
+    None => DEFAULT_ALLOWED_SCHEMES.contains(&scheme.as_str()),
+  };
+  if !allowed {
+    return Err(format!(
+      "URL scheme '{}' is not allowed. Add it to allowed_schemes in the config.",
+      scheme
+    ));
+  }
+  let re = if ["http", "https", "ftp", "sftp"].contains(&scheme.as_str()) {
+    Regex::new(r"^[a-zA-Z]+://[^\s/$.?#].[^\s]*$")
+  } else {
+    Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:[^\s]+$")
+  }
+  .panic_on_error("Invalid url format");
   if !re.is_match(url) {
-    panic!(
-      "Invalid URL. Please use a proper format (e.g., https://example.com)."
-    );
+    return Err(INVALID_URL.to_string());
+  }
+  Ok(())
+}
+
+pub fn is_local_path(url: &str) -> bool {
+  url.starts_with('/') || url.starts_with("~/")
+}
+
+pub fn is_web_url(url: &str) -> bool {
+  get_url_scheme(url)
+    .is_some_and(|scheme| WEB_SCHEMES.contains(&scheme.as_str()))
+}
+
+/// HTTP agent used to reach bookmarked pages.
+pub fn http_agent(timeout: Duration, redirects: u32) -> ureq::Agent {
+  ureq::AgentBuilder::new()
+    .timeout(timeout)
+    .redirects(redirects)
+    .user_agent(concat!("tempesta/", env!("CARGO_PKG_VERSION")))
+    .build()
+}
+
+/// Returns the lowercased scheme of the URL, if any.
+pub fn get_url_scheme(url: &str) -> Option<String> {
+  let re = Regex::new(r"^([a-zA-Z][a-zA-Z0-9+.-]*):").ok()?;
+  re.captures(url).map(|captures| captures[1].to_lowercase())
+}
+
+/// Opens the URL with the opener configured for its scheme. Web URLs go to
+/// the given browser (a profile name or a command template), then to the
+/// configured browser and finally to the system default browser. Everything
+/// else goes to the configured system opener.
+pub fn open_url(url: &str, browser: Option<&str>) {
+  let config = config::load_config();
+  let (scheme, target) = if is_local_path(url) {
+    ("file".to_string(), expand_tilde(url).display().to_string())
+  } else {
+    (get_url_scheme(url).unwrap_or_default(), url.to_string())
+  };
+  let is_web = WEB_SCHEMES.contains(&scheme.as_str());
+  if let Some(browser) = browser.filter(|_| is_web) {
+    spawn_command_template(&resolve_browser(&config, browser), &target);
+    return;
+  }
+  if let Some(template) = config
+    .openers
+    .as_ref()
+    .and_then(|openers| openers.get(&scheme))
+  {
+    spawn_command_template(template, &target);
+    return;
+  }
+  if is_web {
+    match &config.browser {
+      Some(browser) => {
+        spawn_command_template(&resolve_browser(&config, browser), &target)
+      }
+      None => {
+        webbrowser::open(&target).panic_on_error("Failed to open browser")
+      }
+    }
+    return;
+  }
+  let opener = config
+    .opener
+    .clone()
+    .unwrap_or_else(|| default_system_opener().to_string());
+  spawn_command_template(&opener, &target);
+}
+
+/// A browser is either the name of a profile in `browsers` or a command
+/// template.
+fn resolve_browser(config: &config::Config, browser: &str) -> String {
+  config
+    .browsers
+    .as_ref()
+    .and_then(|browsers| browsers.get(browser))
+    .cloned()
+    .unwrap_or_else(|| browser.to_string())
+}
+
+pub fn has_browser_profile(name: &str) -> bool {
+  let config = config::load_config();
+  config
+    .browsers
+    .is_some_and(|browsers| browsers.contains_key(name))
+}
+
+/// Returns the browser set for the closest directory of the bookmark in
+/// `directory_browsers`.
+pub fn get_directory_browser(relative_path: &str) -> Option<String> {
+  let config = config::load_config();
+  let directory_browsers = config.directory_browsers?;
+  directory_browsers
+    .iter()
+    .map(|(dir, browser)| (dir.trim_end_matches('/'), browser))
+    .filter(|(dir, _)| {
+      relative_path
+        .strip_prefix(dir)
+        .is_some_and(|rest| rest.starts_with('/'))
+    })
+    .max_by_key(|(dir, _)| dir.len())
+    .map(|(_, browser)| browser.clone())
+}
+
+fn default_system_opener() -> &'static str {
+  if cfg!(target_os = "macos") {
+    "open"
+  } else if cfg!(target_os = "windows") {
+    "explorer"
+  } else {
+    "xdg-open"
+  }
+}
+
+/// Spawns a command template such as `firefox --new-window {url}`. When the
+/// template has no `{url}` placeholder the URL is appended as last argument.
+/// The template is split like a shell would, so quoted program paths and
+/// arguments may contain spaces. The process is not waited for, so a browser
+/// that stays in the foreground does not block the terminal.
+#[allow(clippy::zombie_processes)]
+pub fn spawn_command_template(template: &str, url: &str) {
+  let parts =
+    shell_words::split(template).panic_on_error("Invalid opener command");
+  let mut parts = parts.into_iter();
+  let program = parts.next().panic_on_error("Empty opener command");
+  let mut args: Vec<String> = parts.map(|p| p.replace("{url}", url)).collect();
+  if !template.contains("{url}") {
+    args.push(url.to_string());
   }
+  Command::new(program)
+    .args(&args)
+    .stdin(Stdio::null())
+    .spawn()
+    .panic_on_error("Failed to run opener");
 }
 
 pub fn get_bookmark_file_path(relative_path: &String) -> PathBuf {
@@ -128,41 +432,453 @@ pub fn get_bookmark_file_path(relative_path: &String) -> PathBuf {
   bookmark_store_dir_path
 }
 
-pub fn store_bookmark(toml_file_path: &PathBuf, url: &str, tags: &[String]) {
-  let bookmark = Bookmark {
-    url: url.to_owned(),
-    tags: tags.to_owned(),
-  };
-  let toml_content =
-    toml::to_string(&bookmark).panic_on_error("Failed to serialize bookmark");
-  fs::write(toml_file_path, toml_content)
-    .panic_on_error("Failed to write bookmark file");
-  println!("Bookmark file stored at {}", toml_file_path.display())
+pub fn store_bookmark(relative_path: &str, bookmark: &Bookmark) {
+  let store = open_store();
+  let mut store = store.borrow_mut();
+  store
+    .save(relative_path, bookmark)
+    .panic_on_error("Failed to write bookmark");
+  println!("Bookmark file stored at {}", store.location(relative_path))
 }
 
-fn push_to_origin() {
-  let config = config::load_config();
+pub fn bookmark_exists(relative_path: &str) -> bool {
+  open_store().borrow().contains(relative_path)
+}
+
+pub fn remove_bookmark(relative_path: &str) {
+  open_store()
+    .borrow_mut()
+    .remove(relative_path)
+    .panic_on_error("Failed to remove bookmark");
+}
+
+/// Where `move` puts a bookmark: a destination ending with `/` is a directory
+/// that keeps the name of the bookmark.
+pub fn move_destination(from: &str, to: &str) -> String {
+  if to.ends_with('/') {
+    let file_name = Path::new(from).file_name().unwrap_or_default();
+    Path::new(to).join(file_name).display().to_string()
+  } else {
+    to.to_string()
+  }
+}
+
+pub fn rename_bookmark(from: &str, to: &str) {
+  open_store()
+    .borrow_mut()
+    .rename(from, to)
+    .panic_on_error("Failed to move bookmark");
+}
+
+/// Recursively removes parent directories if they are empty.
+/// Stops once a non-empty directory is found or the root is reached.
+pub fn cleanup_empty_parents(starting_dir: &Path) -> std::io::Result<()> {
+  let mut current = starting_dir.to_path_buf();
+  loop {
+    // If directory does not exist (already removed), we just break.
+    if !current.exists() {
+      break;
+    }
+    // Check if the directory is empty.
+    let is_empty = fs::read_dir(&current)?.next().is_none();
+    if is_empty {
+      // Try to remove the directory.
+      fs::remove_dir(&current)?;
+      // Move to parent directory for the next check.
+      if !current.pop() {
+        break; // Reached the root.
+      }
+    } else {
+      break; // Stop if we hit a non-empty directory.
+    }
+  }
+  Ok(())
+}
+
+/// Directory inside the store for local state that is never committed.
+pub const LOCAL_STATE_DIR: &str = ".tempesta";
+
+pub fn get_local_state_dir_path() -> PathBuf {
+  let dir = get_bookmark_store_dir_path();
+  create_local_state_dir(&dir)
+    .panic_on_error("Failed to create local state directory");
+  dir.join(LOCAL_STATE_DIR)
+}
+
+/// Creates the local state directory of the store in `dir` with the
+/// `.gitignore` keeping it out of git, when missing.
+pub fn create_local_state_dir(dir: &Path) -> Result<(), String> {
+  let state_dir_path = dir.join(LOCAL_STATE_DIR);
+  if !state_dir_path.exists() {
+    fs::create_dir_all(&state_dir_path).map_err(|e| e.to_string())?;
+    fs::write(state_dir_path.join(".gitignore"), "*\n")
+      .map_err(|e| e.to_string())?;
+  }
+  Ok(())
+}
+
+fn get_visits_file_path() -> PathBuf {
+  get_local_state_dir_path().join("visits.toml")
+}
+
+/// Last time each bookmark was opened on this machine.
+pub fn load_visits() -> BTreeMap<String, String> {
+  fs::read_to_string(get_visits_file_path())
+    .ok()
+    .and_then(|content| toml::from_str(&content).ok())
+    .unwrap_or_default()
+}
+
+pub fn record_visit(relative_path: &str) {
+  let mut visits = load_visits();
+  visits.insert(relative_path.to_string(), now_timestamp());
+  let content =
+    toml::to_string(&visits).panic_on_error("Failed to serialize visits");
+  fs::write(get_visits_file_path(), content)
+    .panic_on_error("Failed to write visits file");
+}
+
+/// Pushes the commits of the store in `dir` following `push_mode`, pulling
+/// first with `pull_before_push`. A pull that cannot fast-forward, or rebase
+/// with `rebase_on_pull`, is undone rather than leaving the store
+/// half-merged: `sync` merges it.
+fn push_changes(config: &config::Config, dir: &Path) -> Result<(), String> {
   if config.remote.is_none() {
-    return;
+    return Ok(());
+  }
+  match config.push_mode.unwrap_or_default() {
+    PushMode::Immediate => {}
+    PushMode::Deferred => return push_in_background(config, dir),
+    PushMode::Manual => return Ok(()),
   }
-  if config.pull_before_push.is_some().eq(&true) {
-    println!("Pulling most recent changes & pushing changes to remote...");
-    if config.rebase_on_pull.is_some().eq(&true) {
-      git_command(&["pull", "--rebase"], "Cannot pull & rebase from remote");
+  if config.pull_before_push == Some(true) {
+    forget_store();
+  }
+  #[cfg(feature = "native-git")]
+  if use_native_git(config) {
+    if config.pull_before_push == Some(true) {
+      native_git::fetch(dir)
+        .and_then(|_| match native_git::upstream(dir) {
+          Some(upstream) => native_git::fast_forward(dir, &upstream),
+          None => Ok(()),
+        })
+        .map_err(|e| {
+          format!(
+            "Cannot pull from remote: {}, run `tempesta sync`",
+            e.message()
+          )
+        })?;
+    }
+    return native_git::push(dir)
+      .map_err(|e| format!("Cannot push to remote: {}", e.message()));
+  }
+  if config.pull_before_push == Some(true) {
+    if config.rebase_on_pull == Some(true) {
+      if run_git(config, dir, &["pull", "--rebase"]).is_err() {
+        let _ = run_git(config, dir, &["rebase", "--abort"]);
+        return Err(
+          "Cannot pull & rebase from remote, run `tempesta sync`".to_string(),
+        );
+      }
+    } else if run_git(config, dir, &["pull", "--ff"]).is_err() {
+      let _ = run_git(config, dir, &["merge", "--abort"]);
+      return Err(
+        "Cannot pull & fast-forward from remote, run `tempesta sync`"
+          .to_string(),
+      );
+    }
+  }
+  run_git(config, dir, &["push", "-u", "--all"])
+    .map(|_| ())
+    .map_err(|e| format!("Cannot push to remote: {}", e))
+}
+
+/// Whether the store is committed and pushed in process rather than with the
+/// `git` binary. libgit2 cannot sign commits, signed stores use the binary.
+#[cfg(feature = "native-git")]
+pub fn use_native_git(config: &config::Config) -> bool {
+  config.git_backend.unwrap_or_default() == GitBackend::Native
+    && config.sign_commits != Some(true)
+}
+
+/// Fetches the changes of origin, returns `false` when it fails.
+pub fn git_fetch() -> bool {
+  let config = config::load_config();
+  #[cfg(feature = "native-git")]
+  if config.git && use_native_git(&config) {
+    return native_git::fetch(&get_bookmark_store_dir_path())
+      .map_err(|e| eprintln!("{}", e.message()))
+      .is_ok();
+  }
+  config.git && git_output(&["fetch", "origin"]).is_some()
+}
+
+/// Moves the current branch to the upstream when it only adds commits,
+/// returns `false` when the branches diverged.
+pub fn git_fast_forward(upstream: &str) -> bool {
+  let config = config::load_config();
+  #[cfg(feature = "native-git")]
+  if config.git && use_native_git(&config) {
+    forget_store();
+    return native_git::fast_forward(&get_bookmark_store_dir_path(), upstream)
+      .is_ok();
+  }
+  config.git && git_output(&["merge", "--ff-only", upstream]).is_some()
+}
+
+/// Pushes every branch to origin, returns `false` when it fails.
+pub fn git_push() -> bool {
+  let config = config::load_config();
+  #[cfg(feature = "native-git")]
+  if config.git && use_native_git(&config) {
+    return native_git::push(&get_bookmark_store_dir_path())
+      .map_err(|e| eprintln!("{}", e.message()))
+      .is_ok();
+  }
+  config.git && git_output(&["push", "-u", "--all"]).is_some()
+}
+
+/// Pushes every unpushed commit without waiting for it. When the push fails
+/// the commits stay unpushed until the next one or `sync`.
+fn push_in_background(
+  config: &config::Config,
+  dir: &Path,
+) -> Result<(), String> {
+  #[cfg(feature = "native-git")]
+  if use_native_git(config) {
+    let dir = dir.to_path_buf();
+    let push = std::thread::spawn(move || {
+      let _ = native_git::push(&dir);
+    });
+    let mut pushes = BACKGROUND_PUSHES
+      .lock()
+      .panic_on_error("Failed to record the push");
+    pushes.retain(|push| !push.is_finished());
+    pushes.push(push);
+    return Ok(());
+  }
+  let mut child = git_in_dir(config, dir, &["push", "--quiet", "-u", "--all"])
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .map_err(|_| "Cannot push to remote, run `tempesta sync`".to_string())?;
+  // Reaped by this thread in long running processes like `serve`, by init
+  // when the command exits first
+  std::thread::spawn(move || child.wait());
+  Ok(())
+}
+
+/// Pushes of the native git backend running in a thread of this process.
+#[cfg(feature = "native-git")]
+static BACKGROUND_PUSHES: std::sync::Mutex<Vec<std::thread::JoinHandle<()>>> =
+  std::sync::Mutex::new(Vec::new());
+
+/// Waits for the pushes running in this process, so that a command does not
+/// exit before them. Its output is printed by then.
+pub fn wait_for_background_pushes() {
+  #[cfg(feature = "native-git")]
+  {
+    let pushes = std::mem::take(
+      &mut *BACKGROUND_PUSHES
+        .lock()
+        .panic_on_error("Failed to read the pushes"),
+    );
+    for push in pushes {
+      let _ = push.join();
+    }
+  }
+}
+
+/// The remote branch tracked by the store: the upstream of the current branch
+/// or the branch with the same name on origin.
+pub fn get_upstream() -> Option<String> {
+  #[cfg(feature = "native-git")]
+  {
+    let config = config::load_config();
+    if config.git && use_native_git(&config) {
+      return native_git::upstream(&get_bookmark_store_dir_path());
+    }
+  }
+  if let Some(upstream) =
+    git_output(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{u}"])
+  {
+    return Some(upstream.trim().to_string());
+  }
+  let branch = git_output(&["symbolic-ref", "--short", "HEAD"])?;
+  let upstream = format!("origin/{}", branch.trim());
+  git_output(&["rev-parse", "--verify", "--quiet", &upstream]).map(|_| upstream)
+}
+
+/// Number of local commits missing from the remote and of remote commits
+/// missing locally, as of the last fetch. `None` without git or without a
+/// remote. Every commit is unpushed until the first push.
+pub fn count_ahead_behind() -> Option<(usize, usize)> {
+  git_output(&["remote", "get-url", "origin"])?;
+  let counts = match get_upstream() {
+    Some(upstream) => git_output(&[
+      "rev-list",
+      "--left-right",
+      "--count",
+      &format!("HEAD...{}", upstream),
+    ]),
+    None => git_output(&["rev-list", "--count", "HEAD"]),
+  }
+  .unwrap_or_default();
+  let mut counts = counts
+    .split_whitespace()
+    .map(|count| count.parse().unwrap_or(0));
+  Some((counts.next().unwrap_or(0), counts.next().unwrap_or(0)))
+}
+
+/// Trailer marking the commits created by tempesta, the ones `undo` reverts.
+pub const COMMIT_TRAILER: &str = "Created-by: tempesta";
+
+/// What a commit changes, the placeholders of `commit_template`.
+#[derive(Default)]
+pub struct CommitInfo<'a> {
+  pub action: &'a str,
+  pub path: &'a str,
+  pub url: &'a str,
+}
+
+/// Commits every change of the store and pushes it. With git disabled the
+/// changes are recorded in the local journal instead.
+pub fn git_commit(comment: &str, info: CommitInfo) {
+  let config = config::load_config();
+  announce_push(&config);
+  let store = open_store();
+  let committed = commit_changes(
+    &config,
+    &get_bookmark_store_dir_path(),
+    store.borrow().as_ref(),
+    comment,
+    &info,
+  );
+  if let Err(e) = committed {
+    eprintln!("{}", e);
+  }
+}
+
+/// Tells that the commit about to be made is pushed right away, as the
+/// command waits for the network.
+pub fn announce_push(config: &config::Config) {
+  if config.git
+    && config.remote.is_some()
+    && config.push_mode.unwrap_or_default() == PushMode::Immediate
+  {
+    if config.pull_before_push == Some(true) {
+      println!("Pulling most recent changes & pushing changes to remote...");
     } else {
-      git_command(&["pull", "--ff"], "Cannot pull & fast-forward from remote");
+      println!("Pushing changes to remote origin...");
     }
-    git_command(&["push", "-u", "--all"], "Cannot push to remote");
-  } else {
-    println!("Pushing changes to remote origin...");
-    git_command(&["push", "-u", "--all"], "Cannot push to remote");
   }
 }
 
-pub fn git_commit(comment: &str) {
-  git_command(&["add", "-A"], "Failed to add file to git stage");
-  git_command(&["commit", "-m", comment], "Failed to commit to git");
-  push_to_origin();
+/// Commits every change of the store in `dir` and pushes it following
+/// `push_mode`, or records them in the journal of the store with git
+/// disabled. Every change of the CLI and of the library goes through it.
+pub fn commit_changes(
+  config: &config::Config,
+  dir: &Path,
+  store: &dyn BookmarkStore,
+  comment: &str,
+  info: &CommitInfo,
+) -> Result<(), String> {
+  let comment = render_commit_message(config, dir, comment, info);
+  if !config.git {
+    return journal::record(dir, store, &comment)
+      .map_err(|e| format!("Failed to record the change: {}", e));
+  }
+  #[cfg(feature = "native-git")]
+  if use_native_git(config) {
+    let message = format!("{}\n\n{}\n", comment, COMMIT_TRAILER);
+    let committed = native_git::commit(dir, config, &message)
+      .map_err(|e| format!("Failed to commit to git: {}", e.message()))?;
+    return if committed {
+      push_changes(config, dir)
+    } else {
+      Ok(())
+    };
+  }
+  run_git(config, dir, &["add", "-A"])
+    .map_err(|e| format!("Failed to add file to git stage: {}", e))?;
+  // Nothing staged, e.g. a bookmark saved unchanged
+  if run_git(config, dir, &["diff", "--cached", "--quiet"]).is_ok() {
+    return Ok(());
+  }
+  run_git(
+    config,
+    dir,
+    &["commit", "-m", &comment, "-m", COMMIT_TRAILER],
+  )
+  .map_err(|e| format!("Failed to commit to git: {}", e))?;
+  push_changes(config, dir)
+}
+
+/// Renders the subject of the commit message with `commit_template`, where
+/// `{message}` is the default subject, and the Conventional Commits prefix.
+pub fn render_commit_message(
+  config: &config::Config,
+  dir: &Path,
+  comment: &str,
+  info: &CommitInfo,
+) -> String {
+  let (subject, body) = match comment.split_once("\n\n") {
+    Some((subject, body)) => (subject, Some(body)),
+    None => (comment, None),
+  };
+  let mut subject = match &config.commit_template {
+    Some(template) => {
+      let placeholder =
+        Regex::new(r"\{(\w+)\}").panic_on_error("Invalid placeholder regex");
+      placeholder
+        .replace_all(template, |captures: &regex::Captures| {
+          match &captures[1] {
+            "message" => subject.to_string(),
+            "action" => info.action.to_string(),
+            "path" => info.path.to_string(),
+            "url" => info.url.to_string(),
+            "host" => Url::parse(info.url)
+              .ok()
+              .and_then(|url| url.host_str().map(str::to_string))
+              .unwrap_or_default(),
+            "user" => get_user(config, dir),
+            _ => captures[0].to_string(),
+          }
+        })
+        .to_string()
+    }
+    None => subject.to_string(),
+  };
+  if config.conventional_commits == Some(true) {
+    let commit_type = match info.action {
+      "add" | "queue" | "restore" => "feat",
+      "undo" => "revert",
+      _ => "chore",
+    };
+    subject = format!("{}: {}", commit_type, subject);
+  }
+  match body {
+    Some(body) => format!("{}\n\n{}", subject, body),
+    None => subject,
+  }
+}
+
+/// The author of the commits: `git_author_name`, the git user or the login.
+fn get_user(config: &config::Config, dir: &Path) -> String {
+  config
+    .git_author_name
+    .clone()
+    .or_else(|| {
+      let output = git_in_dir(config, dir, &["config", "user.name"])
+        .output()
+        .ok()?;
+      let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+      Some(name).filter(|name| !name.is_empty())
+    })
+    .or_else(|| std::env::var("USER").ok())
+    .unwrap_or_default()
 }
 
 pub fn git_command(args: &[&str], error_message: &str) {
@@ -170,8 +886,76 @@ pub fn git_command(args: &[&str], error_message: &str) {
   if !config.git {
     return;
   }
-  let bookmark_store_dir_path = get_bookmark_store_dir_path();
-  run_command("git", args, &bookmark_store_dir_path, error_message);
+  git_in_store(&config, args)
+    .output()
+    .panic_on_error(error_message);
+}
+
+/// The store is opened again after the command, which may change its files.
+fn git_in_store(config: &config::Config, args: &[&str]) -> Command {
+  forget_store();
+  git_in_dir(config, &get_bookmark_store_dir_path(), args)
+}
+
+/// A git command run in the store with the author identity and the signing
+/// settings of the store, when configured.
+pub fn git_in_dir(
+  config: &config::Config,
+  dir: &Path,
+  args: &[&str],
+) -> Command {
+  let mut command = Command::new("git");
+  if config.sign_commits == Some(true) {
+    command.args(["-c", "commit.gpgSign=true"]);
+    if let Some(key) = &config.signing_key {
+      command.args(["-c", &format!("user.signingKey={}", key)]);
+    }
+    if let Some(format) = &config.signing_format {
+      command.args(["-c", &format!("gpg.format={}", format)]);
+    }
+  }
+  command.args(args).current_dir(dir);
+  if let Some(name) = &config.git_author_name {
+    command
+      .env("GIT_AUTHOR_NAME", name)
+      .env("GIT_COMMITTER_NAME", name);
+  }
+  if let Some(email) = &config.git_author_email {
+    command
+      .env("GIT_AUTHOR_EMAIL", email)
+      .env("GIT_COMMITTER_EMAIL", email);
+  }
+  command
+}
+
+/// Runs a git command in the store in `dir` and returns its stdout, or its
+/// error output when it fails.
+pub fn run_git(
+  config: &config::Config,
+  dir: &Path,
+  args: &[&str],
+) -> Result<String, String> {
+  let output = git_in_dir(config, dir, args)
+    .output()
+    .map_err(|e| e.to_string())?;
+  if !output.status.success() {
+    return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Runs a git command in the store and returns its stdout, or `None` when git
+/// is disabled or the command fails.
+pub fn git_output(args: &[&str]) -> Option<String> {
+  let config = config::load_config();
+  if !config.git {
+    return None;
+  }
+  let output = git_in_store(&config, args).output().ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  Some(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
 pub fn run_command(
@@ -187,11 +971,722 @@ pub fn run_command(
     .panic_on_error(error_message);
 }
 
-pub fn get_url(relative_path: &String) -> String {
-  let toml_file_path = get_bookmark_file_path(relative_path);
-  let toml_content =
-    fs::read_to_string(toml_file_path).panic_on_error("Failed to read TOML");
-  let bookmark: Bookmark = toml::from_str(&toml_content)
-    .panic_on_error("Failed to parse TOML content");
-  bookmark.url
+pub fn load_bookmark(relative_path: &str) -> Bookmark {
+  open_store()
+    .borrow()
+    .load(relative_path)
+    .panic_on_error("Failed to load bookmark")
+}
+
+/// The bookmark, or `None` when it is missing or not a valid bookmark.
+pub fn try_load_bookmark(relative_path: &str) -> Option<Bookmark> {
+  open_store().borrow().load(relative_path).ok()
+}
+
+/// Removes every `--name <value>` or `--name=<value>` from the arguments and
+/// returns their values.
+pub fn take_options(args: &mut Vec<String>, name: &str) -> Vec<String> {
+  let mut values = Vec::new();
+  while let Some(value) = take_option(args, name) {
+    values.push(value);
+  }
+  values
+}
+
+/// Prints the value as pretty JSON, the output of the `--json` options.
+pub fn print_json<T: Serialize>(value: &T) {
+  println!(
+    "{}",
+    serde_json::to_string_pretty(value)
+      .panic_on_error("Failed to serialize JSON")
+  );
+}
+
+/// Removes the flags from the arguments and returns whether any was present.
+pub fn take_flag(args: &mut Vec<String>, names: &[&str]) -> bool {
+  let before = args.len();
+  args.retain(|arg| !names.contains(&arg.as_str()));
+  args.len() != before
+}
+
+/// Parses durations such as `500ms`, `2s`, `10m`, `1h` or `30d`. A plain
+/// number is read as seconds.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+  let re = Regex::new(r"^(\d+(?:\.\d+)?)(ms|s|m|h|d)?$").ok()?;
+  let captures = re.captures(value.trim())?;
+  let amount: f64 = captures[1].parse().ok()?;
+  let seconds = match captures.get(2).map(|unit| unit.as_str()) {
+    Some("ms") => amount / 1000.0,
+    Some("m") => amount * 60.0,
+    Some("h") => amount * 3600.0,
+    Some("d") => amount * 86400.0,
+    _ => amount,
+  };
+  Some(Duration::from_secs_f64(seconds))
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+  pattern.contains(['*', '?', '['])
+}
+
+/// Translates a glob on bookmark paths into a regex. `*` and `?` do not cross
+/// directories while `**` does. `[a-z]` and `[!a-z]` match one character.
+pub fn glob_to_regex(pattern: &str) -> Regex {
+  let mut re = String::from("^");
+  let mut chars = pattern.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '*' if chars.peek() == Some(&'*') => {
+        chars.next();
+        re.push_str(".*");
+      }
+      '*' => re.push_str("[^/]*"),
+      '?' => re.push_str("[^/]"),
+      '[' => match glob_class(&mut chars.clone()) {
+        Some((class, length)) => {
+          re.push_str(&class);
+          for _ in 0..length {
+            chars.next();
+          }
+        }
+        None => re.push_str(r"\["),
+      },
+      _ => re.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+  re.push('$');
+  Regex::new(&re).panic_on_error("Invalid glob pattern")
+}
+
+/// The regex of the glob class following a `[`, and the number of characters
+/// it spans up to its `]`. `None` when the class is not closed.
+fn glob_class(
+  chars: &mut impl Iterator<Item = char>,
+) -> Option<(String, usize)> {
+  let mut class = String::from("[");
+  let mut length = 0;
+  let mut first = true;
+  loop {
+    let c = chars.next()?;
+    length += 1;
+    match c {
+      '!' | '^' if length == 1 => {
+        // A negated class still does not cross directories
+        class.push_str("^/");
+        continue;
+      }
+      // A `]` first in the class is a literal
+      ']' if !first => break,
+      // Characters taking a meaning inside regex classes
+      '\\' | '[' | ']' | '&' | '~' | '^' => {
+        class.push('\\');
+        class.push(c);
+      }
+      _ => class.push(c),
+    }
+    first = false;
+  }
+  class.push(']');
+  Some((class, length))
+}
+
+/// Removes `--name <value>` or `--name=<value>` from the arguments and returns
+/// its value.
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+  let prefix = format!("{}=", name);
+  let index = args
+    .iter()
+    .position(|arg| arg == name || arg.starts_with(&prefix))?;
+  let arg = args.remove(index);
+  if let Some(value) = arg.strip_prefix(&prefix) {
+    return Some(value.to_string());
+  }
+  if index < args.len() {
+    return Some(args.remove(index));
+  }
+  eprintln!("Error: {} requires a value.", name);
+  std::process::exit(1);
+}
+
+/// Resolves every argument into bookmark paths: a bookmark, a directory or a
+/// glob. With no arguments all bookmarks are considered. The result is then
+/// filtered by tags.
+pub fn select_bookmarks(patterns: &[String], tags: &[String]) -> Vec<String> {
+  let mut relative_paths: Vec<String> = Vec::new();
+  if patterns.is_empty() {
+    relative_paths = get_toml_bookmark_files(None);
+  }
+  for pattern in patterns {
+    let matches = if is_glob(pattern) {
+      let re = glob_to_regex(pattern.trim_end_matches('/'));
+      get_toml_bookmark_files(None)
+        .into_iter()
+        .filter(|path| re.is_match(path))
+        .collect()
+    } else {
+      validate_path(pattern);
+      let bookmark_paths = find_toml_bookmark_files(Some(pattern.clone()));
+      if !pattern.ends_with('/') && bookmark_exists(pattern) {
+        vec![pattern.clone()]
+      } else if !bookmark_paths.is_empty() {
+        bookmark_paths
+      } else {
+        eprintln!("Bookmark not found: {}", pattern);
+        std::process::exit(1);
+      }
+    };
+    for path in matches {
+      if !relative_paths.contains(&path) {
+        relative_paths.push(path);
+      }
+    }
+  }
+  if !tags.is_empty() {
+    relative_paths.retain(|path| {
+      let bookmark = load_bookmark(path);
+      tags.iter().all(|tag| bookmark.tags.contains(tag))
+    });
+  }
+  relative_paths
+}
+
+/// Loads every bookmark under the sub path together with its relative path.
+pub fn load_bookmarks(sub_path: Option<String>) -> Vec<(String, Bookmark)> {
+  let bookmarks = open_store().borrow().load_all(sub_path.as_deref());
+  if bookmarks.is_empty() {
+    get_toml_bookmark_files(sub_path);
+  }
+  bookmarks
+    .into_iter()
+    .map(|(relative_path, bookmark)| {
+      (
+        relative_path,
+        bookmark.panic_on_error("Failed to load bookmark"),
+      )
+    })
+    .collect()
+}
+
+/// Three-way merge of two versions of a bookmark at the field level. Tags
+/// added on either side are kept and tags removed on either side are dropped,
+/// the newest timestamps win and the other fields take the side that changed
+/// them, the most recently updated one when both did. Only a URL changed
+/// differently on both sides is a conflict, returned as `(ours, theirs)`.
+pub fn merge_bookmarks(
+  base: Option<&Bookmark>,
+  ours: &Bookmark,
+  theirs: &Bookmark,
+) -> Result<Bookmark, (String, String)> {
+  let base = base.cloned().unwrap_or_default();
+  let prefer_theirs = theirs.updated_at > ours.updated_at;
+  fn pick<T: PartialEq + Clone>(
+    base: &T,
+    ours: &T,
+    theirs: &T,
+    prefer_theirs: bool,
+  ) -> T {
+    if ours == theirs || theirs == base {
+      ours.clone()
+    } else if ours == base || prefer_theirs {
+      theirs.clone()
+    } else {
+      ours.clone()
+    }
+  }
+  let newest = |a: &Option<String>, b: &Option<String>| {
+    let seconds = |v: &Option<String>| v.as_deref().and_then(parse_timestamp);
+    if seconds(b) > seconds(a) {
+      b.clone()
+    } else {
+      a.clone().or_else(|| b.clone())
+    }
+  };
+  let url = if ours.url == theirs.url || theirs.url == base.url {
+    ours.url.clone()
+  } else if ours.url == base.url {
+    theirs.url.clone()
+  } else {
+    return Err((ours.url.clone(), theirs.url.clone()));
+  };
+  let mut tags: Vec<String> = ours
+    .tags
+    .iter()
+    .chain(&theirs.tags)
+    .filter(|tag| {
+      let removed_by =
+        |side: &Bookmark| base.tags.contains(tag) && !side.tags.contains(tag);
+      !removed_by(ours) && !removed_by(theirs)
+    })
+    .cloned()
+    .collect();
+  let mut seen = Vec::new();
+  tags.retain(|tag| {
+    let first = !seen.contains(tag);
+    seen.push(tag.clone());
+    first
+  });
+  Ok(Bookmark {
+    url,
+    tags,
+    title: pick(&base.title, &ours.title, &theirs.title, prefer_theirs),
+    description: pick(
+      &base.description,
+      &ours.description,
+      &theirs.description,
+      prefer_theirs,
+    ),
+    keyword: pick(&base.keyword, &ours.keyword, &theirs.keyword, prefer_theirs),
+    browser: pick(&base.browser, &ours.browser, &theirs.browser, prefer_theirs),
+    unread: pick(&base.unread, &ours.unread, &theirs.unread, prefer_theirs),
+    created_at: match (&ours.created_at, &theirs.created_at) {
+      (Some(a), Some(b)) => Some(a.min(b).clone()),
+      (a, b) => a.clone().or_else(|| b.clone()),
+    },
+    updated_at: newest(&ours.updated_at, &theirs.updated_at),
+    expires_at: newest(&ours.expires_at, &theirs.expires_at),
+    review_after: newest(&ours.review_after, &theirs.review_after),
+    last_checked: newest(&ours.last_checked, &theirs.last_checked),
+    status: if theirs.last_checked > ours.last_checked {
+      theirs.status.clone()
+    } else {
+      ours.status.clone().or_else(|| theirs.status.clone())
+    },
+    deleted_at: pick(
+      &base.deleted_at,
+      &ours.deleted_at,
+      &theirs.deleted_at,
+      prefer_theirs,
+    ),
+  })
+}
+
+/// Three-way merge of two versions of a single file store. A bookmark changed
+/// on one side only takes that side, the others are given to `resolve` with
+/// their ancestor as `(path, base, ours, theirs)`, `None` when missing.
+pub fn merge_bookmark_maps<E>(
+  base: &BTreeMap<String, Bookmark>,
+  ours: &BTreeMap<String, Bookmark>,
+  theirs: &BTreeMap<String, Bookmark>,
+  mut resolve: impl FnMut(
+    &str,
+    Option<&Bookmark>,
+    Option<Bookmark>,
+    Option<Bookmark>,
+  ) -> Result<Option<Bookmark>, E>,
+) -> Result<BTreeMap<String, Bookmark>, E> {
+  let mut paths: Vec<&String> = base
+    .keys()
+    .chain(ours.keys())
+    .chain(theirs.keys())
+    .collect();
+  paths.sort();
+  paths.dedup();
+  let mut merged = BTreeMap::new();
+  for path in paths {
+    let (base, our, their) = (base.get(path), ours.get(path), theirs.get(path));
+    let resolved = if our == their || their == base {
+      our.cloned()
+    } else if our == base {
+      their.cloned()
+    } else {
+      resolve(path, base, our.cloned(), their.cloned())?
+    };
+    if let Some(bookmark) = resolved {
+      merged.insert(path.clone(), bookmark);
+    }
+  }
+  Ok(merged)
+}
+
+/// Turns any text into a lowercase slug made of `a-z`, `0-9` and dashes.
+pub fn slugify(text: &str, max_len: usize) -> String {
+  let mut slug = String::new();
+  for c in text.to_lowercase().chars() {
+    if c.is_ascii_alphanumeric() {
+      slug.push(c);
+    } else if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+  slug.truncate(max_len);
+  slug.trim_end_matches('-').to_string()
+}
+
+/// Query parameters used only for tracking, `*` matches any suffix.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+  "utm_*", "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "mc_cid",
+  "mc_eid", "igshid", "yclid", "_ga", "_gl", "ref_src",
+];
+
+/// Whether the query parameter is a tracking parameter according to the
+/// config (`tracking_params`) or the built-in list.
+pub fn is_tracking_param(name: &str, tracking_params: &[String]) -> bool {
+  tracking_params
+    .iter()
+    .any(|pattern| match pattern.strip_suffix('*') {
+      Some(prefix) => name.starts_with(prefix),
+      None => name == pattern,
+    })
+}
+
+pub fn get_tracking_params() -> Vec<String> {
+  config::load_config().tracking_params.unwrap_or_else(|| {
+    DEFAULT_TRACKING_PARAMS
+      .iter()
+      .map(|p| p.to_string())
+      .collect()
+  })
+}
+
+/// Normalises a URL to compare bookmarks pointing to the same resource:
+/// lowercase scheme and host, no default port, no trailing slash, sorted
+/// query parameters without tracking parameters. The fragment is kept, as it
+/// can be a route of a single page app or an anchor, unless it is empty or a
+/// `:~:text=` highlight.
+pub fn normalize_url(url: &str, tracking_params: &[String]) -> String {
+  if is_local_path(url) {
+    return expand_tilde(url).display().to_string();
+  }
+  let Ok(mut parsed) = Url::parse(url) else {
+    return url.to_string();
+  };
+  let fragment = parsed
+    .fragment()
+    .map(|fragment| fragment.split(":~:").next().unwrap_or_default())
+    .filter(|fragment| !fragment.is_empty())
+    .map(str::to_string);
+  parsed.set_fragment(None);
+  let mut pairs: Vec<(String, String)> = parsed
+    .query_pairs()
+    .filter(|(name, _)| !is_tracking_param(name, tracking_params))
+    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+    .collect();
+  pairs.sort();
+  if pairs.is_empty() {
+    parsed.set_query(None);
+  } else {
+    parsed.query_pairs_mut().clear().extend_pairs(pairs);
+  }
+  if parsed.path().len() > 1 && parsed.path().ends_with('/') {
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+  }
+  let mut normalized = parsed.to_string();
+  if parsed.path() == "/" && parsed.query().is_none() {
+    normalized = normalized.trim_end_matches('/').to_string();
+  }
+  if let Some(fragment) = fragment {
+    normalized = format!("{}#{}", normalized, fragment);
+  }
+  normalized
+}
+
+/// Cleans a URL before it is stored: unwraps AMP cache URLs, applies the
+/// `host_rewrites`, drops the tracking parameters and applies the
+/// `url_rules`. Returns the URL unchanged when `clean_urls` is disabled.
+pub fn clean_url(url: &str) -> String {
+  clean_url_with(url, &config::load_config())
+}
+
+/// `clean_url` with the given configuration, for the processes that outlive
+/// changes of the configuration file.
+pub fn clean_url_with(url: &str, config: &config::Config) -> String {
+  if !config.clean_urls.unwrap_or(true) || is_local_path(url) {
+    return url.to_string();
+  }
+  let Ok(mut parsed) = Url::parse(&unwrap_amp_url(url)) else {
+    return url.to_string();
+  };
+  if let Some(host) = parsed.host_str().map(String::from) {
+    let mut rewritten = host.clone();
+    for rule in config.host_rewrites.iter().flatten() {
+      let re = Regex::new(&rule.pattern).panic_on_error("Invalid host rewrite");
+      rewritten = re.replace(&rewritten, rule.replace.as_str()).to_string();
+    }
+    if rewritten != host {
+      parsed
+        .set_host(Some(&rewritten))
+        .panic_on_error("Invalid rewritten host");
+    }
+  }
+  let tracking_params = config.tracking_params.clone().unwrap_or_else(|| {
+    DEFAULT_TRACKING_PARAMS
+      .iter()
+      .map(|p| p.to_string())
+      .collect()
+  });
+  let pairs: Vec<(String, String)> = parsed
+    .query_pairs()
+    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+    .collect();
+  let kept: Vec<&(String, String)> = pairs
+    .iter()
+    .filter(|(name, _)| !is_tracking_param(name, &tracking_params))
+    .collect();
+  if kept.is_empty() {
+    parsed.set_query(None);
+  } else if kept.len() != pairs.len() {
+    parsed.query_pairs_mut().clear().extend_pairs(kept);
+  }
+  let mut cleaned = parsed.to_string();
+  for rule in config.url_rules.iter().flatten() {
+    let re = Regex::new(&rule.pattern).panic_on_error("Invalid URL rule");
+    cleaned = re.replace(&cleaned, rule.replace.as_str()).to_string();
+  }
+  // Keep the URL as typed when only the serialisation changed
+  if cleaned == Url::parse(url).map(|u| u.to_string()).unwrap_or_default() {
+    return url.to_string();
+  }
+  cleaned
+}
+
+/// Cleans the URL unless `--raw` was given and tells the user when it
+/// changed.
+pub fn clean_url_unless_raw(url: &str, raw: bool) -> String {
+  if raw {
+    return url.to_string();
+  }
+  let cleaned = clean_url(url);
+  if cleaned != url {
+    println!("URL cleaned to {}", cleaned);
+  }
+  cleaned
+}
+
+/// Turns AMP cache URLs (`*.cdn.ampproject.org/c/s/host/path` and
+/// `www.google.com/amp/s/host/path`) into the canonical page URL.
+fn unwrap_amp_url(url: &str) -> String {
+  let re = Regex::new(concat!(
+    r"^https?://(?:[^/]+\.cdn\.ampproject\.org/[cv]|(?:www\.)?google\.[a-z.]+/amp)",
+    r"/(s/)?(.+)$"
+  ))
+  .panic_on_error("Invalid AMP regex");
+  match re.captures(url) {
+    Some(captures) => {
+      let scheme = if captures.get(1).is_some() {
+        "https"
+      } else {
+        "http"
+      };
+      format!("{}://{}", scheme, &captures[2])
+    }
+    None => url.to_string(),
+  }
+}
+
+/// Paths of the bookmarks pointing to the same resource as the URL.
+/// Bookmarks that fail to load are skipped, so a broken file does not prevent
+/// adding new bookmarks.
+pub fn find_duplicates(url: &str) -> Vec<String> {
+  let tracking_params = get_tracking_params();
+  let normalized = normalize_url(url, &tracking_params);
+  open_store()
+    .borrow()
+    .load_all(None)
+    .into_iter()
+    .filter(|(_, bookmark)| match bookmark {
+      Ok(bookmark) => {
+        normalize_url(&bookmark.url, &tracking_params) == normalized
+      }
+      Err(_) => false,
+    })
+    .map(|(relative_path, _)| relative_path)
+    .collect()
+}
+
+/// Whether a command line argument is a URL or a local path rather than a
+/// bookmark path. Bookmark paths cannot contain `:`.
+pub fn looks_like_url(arg: &str) -> bool {
+  get_url_scheme(arg).is_some() || arg.starts_with("~/")
+}
+
+/// Builds a bookmark path from a template. Placeholders:
+/// `{host}`, `{first-path-segment}`, `{last-path-segment}`, `{path}`,
+/// `{slug}`, `{title}` and `{date}`. Every segment is sanitised so that the
+/// result passes `validate_path`, and a free path is picked on collisions.
+pub fn generate_bookmark_path(
+  template: &str,
+  url: &str,
+  title: Option<&str>,
+) -> String {
+  let (host, segments) = match Url::parse(url) {
+    Ok(parsed) => {
+      let host = parsed
+        .host_str()
+        .map(|h| h.trim_start_matches("www.").to_string())
+        .unwrap_or_else(|| parsed.scheme().to_string());
+      let segments: Vec<String> = parsed
+        .path()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+      (host, segments)
+    }
+    Err(_) => (
+      "local".to_string(),
+      url
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect(),
+    ),
+  };
+  let without_scheme = url.split_once("://").map_or(url, |x| x.1);
+  let slug = slugify(without_scheme, 60);
+  let path = template
+    .replace("{host}", &host)
+    .replace(
+      "{first-path-segment}",
+      segments.first().map_or("index", |s| s.as_str()),
+    )
+    .replace(
+      "{last-path-segment}",
+      segments.last().map_or("index", |s| s.as_str()),
+    )
+    .replace("{path}", &segments.join("/"))
+    .replace("{slug}", &slug)
+    .replace("{title}", &title.map(|t| slugify(t, 60)).unwrap_or(slug))
+    .replace("{date}", &now_timestamp()[..10]);
+  let sanitized: Vec<String> = path
+    .split('/')
+    .map(sanitize_path_segment)
+    .filter(|s| !s.is_empty())
+    .collect();
+  if sanitized.is_empty() {
+    return dedupe_bookmark_path("bookmark");
+  }
+  dedupe_bookmark_path(&sanitized.join("/"))
+}
+
+fn sanitize_path_segment(segment: &str) -> String {
+  let mut sanitized = String::new();
+  for c in segment.chars() {
+    if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' {
+      sanitized.push(c.to_ascii_lowercase());
+    } else if !sanitized.ends_with('-') {
+      sanitized.push('-');
+    }
+  }
+  sanitized.trim_matches(['-', '.']).to_string()
+}
+
+/// Appends `-2`, `-3`, ... to the path until no bookmark exists there.
+pub fn dedupe_bookmark_path(relative_path: &str) -> String {
+  dedupe_path_in(open_store().borrow().as_ref(), relative_path)
+}
+
+/// The path, or the first free one with a `-2`, `-3`... suffix in the store.
+pub fn dedupe_path_in(
+  store: &dyn BookmarkStore,
+  relative_path: &str,
+) -> String {
+  if !store.contains(relative_path) {
+    return relative_path.to_string();
+  }
+  (2..)
+    .map(|n| format!("{}-{}", relative_path, n))
+    .find(|path| !store.contains(path))
+    .panic_on_error("Cannot find a free bookmark path")
+}
+
+/// Returns a random number in `0..len`.
+pub fn random_index(len: usize) -> usize {
+  let mut hasher = RandomState::new().build_hasher();
+  hasher.write_u128(
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_nanos())
+      .unwrap_or_default(),
+  );
+  (hasher.finish() % len as u64) as usize
+}
+
+pub fn now_seconds() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or_default()
+}
+
+pub fn now_timestamp() -> String {
+  format_timestamp(now_seconds())
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(seconds: i64) -> String {
+  let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+  let seconds_of_day = seconds.rem_euclid(86400);
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    year,
+    month,
+    day,
+    seconds_of_day / 3600,
+    seconds_of_day % 3600 / 60,
+    seconds_of_day % 60
+  )
+}
+
+/// Parses a `YYYY-MM-DD` date or an RFC 3339 timestamp into seconds since the
+/// Unix epoch.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+  let re = Regex::new(concat!(
+    r"^(\d{4})-(\d{2})-(\d{2})",
+    r"(?:[T ](\d{2}):(\d{2})(?::(\d{2}))?(?:\.\d+)?",
+    r"(Z|[+-]\d{2}:\d{2})?)?$"
+  ))
+  .ok()?;
+  let captures = re.captures(value.trim())?;
+  let number = |i: usize| -> i64 {
+    captures
+      .get(i)
+      .and_then(|m| m.as_str().parse().ok())
+      .unwrap_or(0)
+  };
+  let days = days_from_civil(number(1), number(2), number(3));
+  let mut seconds = days * 86400 + number(4) * 3600 + number(5) * 60;
+  seconds += number(6);
+  if let Some(offset) = captures.get(7).map(|m| m.as_str()) {
+    if offset != "Z" {
+      let sign = if offset.starts_with('-') { -1 } else { 1 };
+      let hours: i64 = offset[1..3].parse().ok()?;
+      let minutes: i64 = offset[4..6].parse().ok()?;
+      seconds -= sign * (hours * 3600 + minutes * 60);
+    }
+  }
+  Some(seconds)
+}
+
+// Days since 1970-01-01 from a proleptic Gregorian date, see
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let month_index = (month + 9) % 12;
+  let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+  let day_of_era =
+    year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719468;
+  let era = days.div_euclid(146097);
+  let day_of_era = days - era * 146097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+    - day_of_era / 146096)
+    / 365;
+  let day_of_year =
+    day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+  (year, month, day)
 }
@@ -4,16 +4,115 @@
 
 use super::common::PanicOnError;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
-#[derive(Serialize, Deserialize)]
+/// A regex and its replacement, `$1`... refer to the capture groups.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RewriteRule {
+  pub pattern: String,
+  pub replace: String,
+}
+
+/// When the commits are pushed to the remote.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushMode {
+  /// After every commit, waiting for the push to end.
+  #[default]
+  Immediate,
+  /// In the background after every commit, failed pushes are retried by the
+  /// next commit or by `sync`.
+  Deferred,
+  /// Only by `sync`.
+  Manual,
+}
+
+impl std::fmt::Display for PushMode {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(match self {
+      PushMode::Immediate => "immediate",
+      PushMode::Deferred => "deferred",
+      PushMode::Manual => "manual",
+    })
+  }
+}
+
+/// How the store is committed and pushed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+  /// In process with libgit2, when tempesta is built with the `native-git`
+  /// feature. It authenticates with the SSH agent only and skips the hooks
+  /// and `commit.gpgSign`, so it is opt-in.
+  Native,
+  /// With the `git` binary.
+  #[default]
+  Cli,
+}
+
+/// Where the bookmarks are kept, see `store.rs`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+  #[default]
+  Directory,
+  Toml,
+  Json,
+  Sqlite,
+}
+
+impl std::fmt::Display for StoreBackend {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(match self {
+      StoreBackend::Directory => "directory",
+      StoreBackend::Toml => "toml",
+      StoreBackend::Json => "json",
+      StoreBackend::Sqlite => "sqlite",
+    })
+  }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Config {
   pub git: bool,
   pub pull_before_push: Option<bool>,
   pub rebase_on_pull: Option<bool>,
+  pub push_mode: Option<PushMode>,
+  pub git_backend: Option<GitBackend>,
+  pub commit_template: Option<String>,
+  pub conventional_commits: Option<bool>,
+  pub git_author_name: Option<String>,
+  pub git_author_email: Option<String>,
+  pub sign_commits: Option<bool>,
+  pub signing_key: Option<String>,
+  pub signing_format: Option<String>,
+  pub allowed_signers: Option<String>,
   pub remote: Option<String>,
   pub dir: String,
+  pub store_backend: Option<StoreBackend>,
+  pub allowed_schemes: Option<Vec<String>>,
+  pub opener: Option<String>,
+  pub browser: Option<String>,
+  pub open_confirm_threshold: Option<usize>,
+  pub path_template: Option<String>,
+  pub queue_dir: Option<String>,
+  pub review_days: Option<i64>,
+  pub archive_dir: Option<String>,
+  pub check_concurrency: Option<usize>,
+  pub check_timeout: Option<u64>,
+  pub fetch_metadata: Option<bool>,
+  pub fetch_timeout: Option<u64>,
+  pub tracking_params: Option<Vec<String>>,
+  pub clean_urls: Option<bool>,
+  pub trash: Option<bool>,
+  pub openers: Option<HashMap<String, String>>,
+  pub browsers: Option<HashMap<String, String>>,
+  pub directory_browsers: Option<HashMap<String, String>>,
+  pub host_rewrites: Option<Vec<RewriteRule>>,
+  pub url_rules: Option<Vec<RewriteRule>>,
 }
 
 pub fn run() {
@@ -30,15 +129,128 @@ pub fn run() {
   if let Some(rebase_on_pull) = &config.rebase_on_pull {
     println!("Rebase on pull enabled:   {}", rebase_on_pull);
   }
+  if let Some(push_mode) = &config.push_mode {
+    println!("Push mode:                {}", push_mode);
+  }
+  if let Some(git_backend) = &config.git_backend {
+    println!(
+      "Git backend:              {}",
+      match git_backend {
+        GitBackend::Native => "native",
+        GitBackend::Cli => "cli",
+      }
+    );
+  }
+  if let Some(commit_template) = &config.commit_template {
+    println!("Commit message template:  {}", commit_template);
+  }
+  if let Some(conventional_commits) = &config.conventional_commits {
+    println!("Conventional commits:     {}", conventional_commits);
+  }
+  if let Some(name) = &config.git_author_name {
+    println!("Git author name:          {}", name);
+  }
+  if let Some(email) = &config.git_author_email {
+    println!("Git author email:         {}", email);
+  }
+  if let Some(sign_commits) = &config.sign_commits {
+    println!("Sign commits:             {}", sign_commits);
+  }
+  if let Some(signing_key) = &config.signing_key {
+    println!("Signing key:              {}", signing_key);
+  }
+  if let Some(signing_format) = &config.signing_format {
+    println!("Signing format:           {}", signing_format);
+  }
+  if let Some(allowed_signers) = &config.allowed_signers {
+    println!("Allowed signers:          {}", allowed_signers);
+  }
   println!("Bookmark store directory: {}", config.dir);
+  if let Some(store_backend) = &config.store_backend {
+    println!("Store backend:            {}", store_backend);
+  }
+  if let Some(allowed_schemes) = &config.allowed_schemes {
+    println!("Allowed URL schemes:      {}", allowed_schemes.join(", "));
+  }
+  if let Some(opener) = &config.opener {
+    println!("Opener:                   {}", opener);
+  }
+  if let Some(browser) = &config.browser {
+    println!("Browser:                  {}", browser);
+  }
+  if let Some(threshold) = &config.open_confirm_threshold {
+    println!("Confirm opening more than: {}", threshold);
+  }
+  if let Some(path_template) = &config.path_template {
+    println!("Bookmark path template:   {}", path_template);
+  }
+  if let Some(queue_dir) = &config.queue_dir {
+    println!("Reading queue directory:  {}", queue_dir);
+  }
+  if let Some(review_days) = &config.review_days {
+    println!("Review after days:        {}", review_days);
+  }
+  if let Some(archive_dir) = &config.archive_dir {
+    println!("Archive directory:        {}", archive_dir);
+  }
+  if let Some(check_concurrency) = &config.check_concurrency {
+    println!("Check concurrency:        {}", check_concurrency);
+  }
+  if let Some(check_timeout) = &config.check_timeout {
+    println!("Check timeout (seconds):  {}", check_timeout);
+  }
+  if let Some(fetch_metadata) = &config.fetch_metadata {
+    println!("Fetch metadata on add:    {}", fetch_metadata);
+  }
+  if let Some(fetch_timeout) = &config.fetch_timeout {
+    println!("Fetch timeout (seconds):  {}", fetch_timeout);
+  }
+  if let Some(tracking_params) = &config.tracking_params {
+    println!("Tracking parameters:      {}", tracking_params.join(", "));
+  }
+  if let Some(clean_urls) = &config.clean_urls {
+    println!("Clean URLs:               {}", clean_urls);
+  }
+  if let Some(trash) = &config.trash {
+    println!("Move removed to trash:    {}", trash);
+  }
+  print_table("Opener for", &config.openers);
+  print_table("Browser profile", &config.browsers);
+  print_table("Browser for directory", &config.directory_browsers);
+  print_rules("Host rewrite", &config.host_rewrites);
+  print_rules("URL rule", &config.url_rules);
+}
+
+fn print_rules(label: &str, rules: &Option<Vec<RewriteRule>>) {
+  for rule in rules.iter().flatten() {
+    println!("{}: {} -> {}", label, rule.pattern, rule.replace);
+  }
+}
+
+fn print_table(label: &str, table: &Option<HashMap<String, String>>) {
+  if let Some(table) = table {
+    let mut keys: Vec<&String> = table.keys().collect();
+    keys.sort();
+    for key in keys {
+      println!("{} {}: {}", label, key, table[key]);
+    }
+  }
 }
 
+/// The config file is read once per command.
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+
 pub fn load_config() -> Config {
+  let mut cached = CONFIG.lock().panic_on_error("Cannot read config cache");
+  if let Some(config) = &*cached {
+    return config.clone();
+  }
   let config_file_path = get_config_file_path();
   let content = fs::read_to_string(&config_file_path)
     .panic_on_error("Cannot read config file");
   let config: Config =
     toml::from_str(&content).panic_on_error("Cannot read toml config file");
+  *cached = Some(config.clone());
   config
 }
 
@@ -64,4 +276,6 @@ pub fn save_config(config: &Config) {
     toml::to_string(config).panic_on_error("Cannot write toml config file");
   fs::write(config_file_path, content)
     .panic_on_error("Cannot write config file");
+  *CONFIG.lock().panic_on_error("Cannot write config cache") =
+    Some(config.clone());
 }
@@ -0,0 +1,112 @@
+// ****************************************************************************
+// Find bookmarks pointing to the same resource once their URLs are normalised
+// and merge them interactively, combining their tags
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+pub fn run(mut args: Vec<String>) {
+  let list_only = common::take_flag(&mut args, &["--list"]);
+  let groups = find_duplicate_groups(args.get(2).cloned());
+  if groups.is_empty() {
+    println!("No duplicates found.");
+    return;
+  }
+  let mut merged = 0;
+  for (normalized, mut group) in groups {
+    println!("Duplicates of {}:", normalized);
+    for (index, (relative_path, bookmark)) in group.iter().enumerate() {
+      println!("  {}) {} :: {}", index + 1, relative_path, bookmark.url);
+    }
+    if list_only {
+      continue;
+    }
+    let Some(keep) = prompt_keep(group.len()) else {
+      break;
+    };
+    let Some(keep) = keep else {
+      continue;
+    };
+    let (kept_path, mut kept) = group.remove(keep);
+    for (relative_path, duplicate) in group {
+      merge_into(&mut kept, duplicate);
+      common::remove_bookmark(&relative_path);
+      println!("Merged {} into {}", relative_path, kept_path);
+      merged += 1;
+    }
+    kept.updated_at = Some(common::now_timestamp());
+    common::store_bookmark(&kept_path, &kept);
+  }
+  if merged > 0 {
+    let comment = format!("Dedupe bookmarks: merged {} duplicates", merged);
+    common::git_commit(
+      &comment,
+      common::CommitInfo {
+        action: "dedupe",
+        ..Default::default()
+      },
+    );
+    println!("Merged {} duplicates", merged);
+  }
+}
+
+/// Groups of bookmarks sharing the same normalised URL, keyed by it.
+fn find_duplicate_groups(
+  sub_path: Option<String>,
+) -> BTreeMap<String, Vec<(String, Bookmark)>> {
+  let tracking_params = common::get_tracking_params();
+  let mut groups: BTreeMap<String, Vec<(String, Bookmark)>> = BTreeMap::new();
+  for (relative_path, bookmark) in common::load_bookmarks(sub_path) {
+    let normalized = common::normalize_url(&bookmark.url, &tracking_params);
+    groups
+      .entry(normalized)
+      .or_default()
+      .push((relative_path, bookmark));
+  }
+  groups.retain(|_, group| group.len() > 1);
+  groups
+}
+
+/// Combines the tags and fills the missing metadata of the kept bookmark.
+fn merge_into(kept: &mut Bookmark, duplicate: Bookmark) {
+  for tag in duplicate.tags {
+    if !kept.tags.contains(&tag) {
+      kept.tags.push(tag);
+    }
+  }
+  kept.title = kept.title.take().or(duplicate.title);
+  kept.description = kept.description.take().or(duplicate.description);
+  kept.browser = kept.browser.take().or(duplicate.browser);
+  kept.created_at = match (kept.created_at.take(), duplicate.created_at) {
+    (Some(a), Some(b)) => Some(a.min(b)),
+    (a, b) => a.or(b),
+  };
+}
+
+/// Returns `None` to quit, `Some(None)` to skip the group or the index of the
+/// bookmark to keep.
+fn prompt_keep(count: usize) -> Option<Option<usize>> {
+  loop {
+    print!("Keep which one? [1-{}, s to skip, q to quit]: ", count);
+    io::stdout()
+      .flush()
+      .panic_on_error("Failed to flush stdout");
+    let mut input = String::new();
+    let read = io::stdin()
+      .read_line(&mut input)
+      .panic_on_error("Failed to read input");
+    if read == 0 {
+      return None;
+    }
+    match input.trim().to_lowercase().as_str() {
+      "q" | "quit" => return None,
+      "" | "s" | "skip" => return Some(None),
+      choice => match choice.parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => return Some(Some(n - 1)),
+        _ => println!("Invalid input."),
+      },
+    }
+  }
+}
@@ -13,12 +13,26 @@ pub fn run(args: Vec<String>) {
     std::process::exit(1);
   }
   let relative_path = &args[2];
-  common::validate_path(relative_path);
-  let toml_file_path = common::get_bookmark_file_path(relative_path);
-  if !toml_file_path.exists() {
-    eprintln!("Bookmark file does not exist: {}", toml_file_path.display());
+  common::validate_bookmark_path(relative_path);
+  let store = common::open_store();
+  if !store.borrow().contains(relative_path) {
+    eprintln!(
+      "Bookmark file does not exist: {}",
+      store.borrow().location(relative_path)
+    );
     std::process::exit(1);
   }
+  // Stores without a file per bookmark are edited through a temporary one
+  let file_path = store.borrow().file_path(relative_path);
+  let temporary = file_path.is_none();
+  let toml_file_path = file_path.unwrap_or_else(|| {
+    let bookmark = common::load_bookmark(relative_path);
+    let content =
+      toml::to_string(&bookmark).panic_on_error("Failed to serialize bookmark");
+    let path = common::get_local_state_dir_path().join("edit.toml");
+    fs::write(&path, content).panic_on_error("Failed to write bookmark file");
+    path
+  });
   // Get preferred editor from $EDITOR, or default to nano
   let editor = env::var("EDITOR").unwrap_or_else(|_| "nano".to_string());
   // Store last modified timestamp before editing
@@ -38,9 +52,34 @@ pub fn run(args: Vec<String>) {
   let metadata_after = fs::metadata(&toml_file_path)
     .and_then(|m| m.modified())
     .ok();
+  if temporary {
+    let content = fs::read_to_string(&toml_file_path)
+      .panic_on_error("Failed to read bookmark file");
+    fs::remove_file(&toml_file_path).panic_on_error("Failed to remove file");
+    if metadata_before != metadata_after {
+      let bookmark = toml::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Invalid bookmark, no changes made: {}", e);
+        std::process::exit(1);
+      });
+      store
+        .borrow_mut()
+        .save(relative_path, &bookmark)
+        .panic_on_error("Failed to write bookmark");
+    }
+  }
   if metadata_before != metadata_after {
-    let comment = format!("Edit bookmark {}", &toml_file_path.display());
-    common::git_commit(&comment);
+    let comment = format!("Edit bookmark {}", relative_path);
+    let url = common::try_load_bookmark(relative_path)
+      .map(|bookmark| bookmark.url)
+      .unwrap_or_default();
+    common::git_commit(
+      &comment,
+      common::CommitInfo {
+        action: "edit",
+        path: relative_path,
+        url: &url,
+      },
+    );
     println!("Bookmark edited successfully as {}", &relative_path);
   } else {
     println!("No changes made.");
@@ -3,6 +3,8 @@
 // ****************************************************************************
 
 use super::common;
+use super::config;
+use crate::Store;
 
 pub fn run(args: Vec<String>) {
   let relative_path = if args.len() < 3 {
@@ -12,8 +14,12 @@ pub fn run(args: Vec<String>) {
   } else {
     args[2].clone()
   };
-  common::validate_path(&relative_path);
-  let url = common::get_url(&relative_path);
-  common::validate_url(&url);
-  println!("{}", url);
+  let bookmark = Store::from_config_file(config::get_config_file_path())
+    .and_then(|store| store.get(&relative_path))
+    .unwrap_or_else(|e| {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    });
+  common::validate_url(&bookmark.url);
+  println!("{}", bookmark.url);
 }
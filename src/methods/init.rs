@@ -4,6 +4,7 @@
 // - store path
 // - git
 // - git remote url
+// - git merge driver
 // ****************************************************************************
 
 use std::fs::{self, File};
@@ -11,7 +12,13 @@ use std::io::{self, Write};
 use std::path::Path;
 
 use super::common::{self, PanicOnError};
+#[cfg(feature = "native-git")]
+use super::config::GitBackend;
 use super::config::{self, Config};
+use super::journal;
+use super::merge_driver;
+#[cfg(feature = "native-git")]
+use super::native_git;
 
 pub fn run() {
   let storage_path = prompt_valid_bookmark_store_path();
@@ -24,16 +31,20 @@ pub fn run() {
     .read_line(&mut input)
     .panic_on_error("Failed to read input");
   let use_git = !matches!(input.trim().to_lowercase().as_str(), "n" | "no");
+  // Running init again only changes the answered settings
+  let previous_config = load_previous_config();
   let config = Config {
     git: use_git,
-    pull_before_push: Some(false),
-    rebase_on_pull: Some(false),
-    remote: None,
+    pull_before_push: previous_config.pull_before_push.or(Some(false)),
+    rebase_on_pull: previous_config.rebase_on_pull.or(Some(false)),
     dir: storage_path,
+    ..previous_config
   };
   config::save_config(&config);
   if use_git {
     handle_git(&config);
+  } else {
+    journal::prepare();
   }
   let config_file_path = config::get_config_file_path();
   println!(
@@ -42,6 +53,14 @@ pub fn run() {
   );
 }
 
+/// The configuration of an earlier init, or the default one.
+fn load_previous_config() -> Config {
+  fs::read_to_string(config::get_config_file_path())
+    .ok()
+    .and_then(|content| toml::from_str(&content).ok())
+    .unwrap_or_default()
+}
+
 fn prompt_valid_bookmark_store_path() -> String {
   loop {
     let mut storage_path = String::new();
@@ -86,44 +105,86 @@ fn prompt_valid_bookmark_store_path() -> String {
 
 fn handle_git(previous_config: &Config) {
   let git_remote = prompt_remote_url();
+  let branch_name = git_remote.as_ref().map(|_| prompt_branch_name());
   let bookmark_store_dir_path = common::get_bookmark_store_dir_path();
-
-  common::run_command(
-    "git",
-    &["init"],
+  init_repository(
+    previous_config,
     &bookmark_store_dir_path,
-    "Failed to initialize Git repository",
+    git_remote.as_deref(),
+    branch_name.as_deref(),
   );
   println!(
     "Git repository initialized at {}",
     bookmark_store_dir_path.display()
   );
-
   if let Some(remote) = &git_remote {
-    let branch_name = prompt_branch_name();
+    println!("Git remote repository set to {}", remote);
+  }
+
+  let config = Config {
+    git: true,
+    remote: git_remote,
+    ..previous_config.clone()
+  };
+  config::save_config(&config);
+  if prompt_merge_driver() {
+    merge_driver::install();
+  }
+}
+
+/// Creates the repository of the store and pulls the branch of the remote.
+#[cfg_attr(not(feature = "native-git"), allow(unused_variables))]
+fn init_repository(
+  previous_config: &Config,
+  bookmark_store_dir_path: &Path,
+  git_remote: Option<&str>,
+  branch_name: Option<&str>,
+) {
+  #[cfg(feature = "native-git")]
+  if previous_config.git_backend.unwrap_or_default() == GitBackend::Native {
+    let initialized = native_git::init(
+      bookmark_store_dir_path,
+      git_remote,
+      branch_name.unwrap_or("main"),
+    );
+    if let Err(e) = initialized {
+      eprintln!("Failed to initialize Git repository: {}", e.message());
+      std::process::exit(1);
+    }
+    return;
+  }
+  common::run_command(
+    "git",
+    &["init"],
+    bookmark_store_dir_path,
+    "Failed to initialize Git repository",
+  );
+  if let (Some(remote), Some(branch_name)) = (git_remote, branch_name) {
     common::run_command(
       "git",
       &["remote", "add", "origin", remote],
-      &bookmark_store_dir_path,
+      bookmark_store_dir_path,
       "Failed to add remote repository",
     );
-    println!("Git remote repository set to {}", remote);
     common::run_command(
       "git",
-      &["pull", "origin", &branch_name],
-      &bookmark_store_dir_path,
+      &["pull", "origin", branch_name],
+      bookmark_store_dir_path,
       "Failed to pull from origin",
     );
   }
+}
 
-  let config = Config {
-    git: true,
-    pull_before_push: Some(false),
-    rebase_on_pull: Some(false),
-    remote: git_remote,
-    dir: previous_config.dir.clone(),
-  };
-  config::save_config(&config);
+fn prompt_merge_driver() -> bool {
+  print!("Do you want to merge concurrent bookmark edits with the tempesta merge driver? (Y/n): ");
+  io::stdout()
+    .flush()
+    .panic_on_error("Failed to flush stdout");
+  let mut input = String::new();
+  io::stdin()
+    .read_line(&mut input)
+    .panic_on_error("Failed to read input");
+  !matches!(input.trim().to_lowercase().as_str(), "n" | "no")
 }
 
 fn prompt_remote_url() -> Option<String> {
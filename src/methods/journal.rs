@@ -0,0 +1,208 @@
+// ****************************************************************************
+// Local journal of the changes made with git disabled. After every command a
+// snapshot of the store is compared with the previous one and the changed
+// files are recorded with their content before and after, so that `undo` can
+// replay them backwards.
+// ****************************************************************************
+
+use super::common::{self, PanicOnError};
+use super::config;
+use super::store::BookmarkStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Journal {
+  entries: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+  message: String,
+  time: String,
+  changes: Vec<FileChange>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileChange {
+  path: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  before: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  after: Option<String>,
+}
+
+/// Records the changes of the store in `dir` since the last snapshot under
+/// the given message. Without a snapshot, when none was taken by `start`, it
+/// only takes the one the next changes are compared with.
+pub fn record(
+  dir: &Path,
+  store: &dyn BookmarkStore,
+  message: &str,
+) -> Result<(), String> {
+  let current = take_snapshot(store);
+  let Some(previous) = load_snapshot(dir) else {
+    return save_snapshot(dir, &current);
+  };
+  let mut changes = Vec::new();
+  for (path, before) in &previous {
+    if current.get(path) != Some(before) {
+      changes.push(FileChange {
+        path: path.clone(),
+        before: Some(before.clone()),
+        after: current.get(path).cloned(),
+      });
+    }
+  }
+  for (path, after) in &current {
+    if !previous.contains_key(path) {
+      changes.push(FileChange {
+        path: path.clone(),
+        before: None,
+        after: Some(after.clone()),
+      });
+    }
+  }
+  if !changes.is_empty() {
+    let mut journal = load_journal(dir);
+    journal.entries.push(Entry {
+      message: message.to_string(),
+      time: common::now_timestamp(),
+      changes,
+    });
+    let overflow = journal.entries.len().saturating_sub(MAX_JOURNAL_ENTRIES);
+    journal.entries.drain(..overflow);
+    save_journal(dir, &journal)?;
+  }
+  save_snapshot(dir, &current)
+}
+
+/// Takes the snapshot before a command changes a store without git, when there
+/// is none yet, so that its first change is recorded too.
+pub fn prepare() {
+  if config::get_config_file_path().is_file() && !config::load_config().git {
+    let store = common::open_store();
+    start(
+      &common::get_bookmark_store_dir_path(),
+      store.borrow().as_ref(),
+    )
+    .panic_on_error("Failed to write snapshot file");
+  }
+}
+
+/// Takes the first snapshot of the store in `dir`, when there is none yet.
+pub fn start(dir: &Path, store: &dyn BookmarkStore) -> Result<(), String> {
+  if load_snapshot(dir).is_some() {
+    return Ok(());
+  }
+  save_snapshot(dir, &take_snapshot(store))
+}
+
+/// Restores the files changed by the last journal entry and returns its
+/// message, or `None` when the journal is empty.
+pub fn undo() -> Option<String> {
+  let dir = common::get_bookmark_store_dir_path();
+  let mut journal = load_journal(&dir);
+  let entry = journal.entries.pop()?;
+  let store = common::open_store();
+  let mut store = store.borrow_mut();
+  for change in entry.changes {
+    // Without a file per bookmark, the content is the bookmark as TOML
+    let Some(file_path) = store.file_path(&change.path) else {
+      match change.before {
+        Some(content) => {
+          let bookmark = toml::from_str(&content)
+            .panic_on_error("Failed to parse journal content");
+          store
+            .save(&change.path, &bookmark)
+            .panic_on_error("Failed to write bookmark");
+        }
+        None => {
+          if store.contains(&change.path) {
+            store
+              .remove(&change.path)
+              .panic_on_error("Failed to remove bookmark");
+          }
+        }
+      }
+      continue;
+    };
+    match change.before {
+      Some(content) => {
+        if let Some(parent) = file_path.parent() {
+          fs::create_dir_all(parent)
+            .panic_on_error("Failed to create directory");
+        }
+        fs::write(&file_path, content)
+          .panic_on_error("Failed to write bookmark file");
+      }
+      None => {
+        if file_path.exists() {
+          fs::remove_file(&file_path).panic_on_error("Failed to remove file");
+        }
+        if let Some(parent) = file_path.parent() {
+          common::cleanup_empty_parents(parent)
+            .panic_on_error("Failed to clean up empty directories");
+        }
+      }
+    }
+  }
+  save_journal(&dir, &journal).panic_on_error("Failed to write journal file");
+  save_snapshot(&dir, &take_snapshot(store.as_ref()))
+    .panic_on_error("Failed to write snapshot file");
+  Some(entry.message)
+}
+
+/// Content of every bookmark file of the store, keyed by bookmark path.
+fn take_snapshot(store: &dyn BookmarkStore) -> BTreeMap<String, String> {
+  store
+    .list(None)
+    .into_iter()
+    .filter_map(|path| {
+      let content = match store.file_path(&path) {
+        Some(file_path) => fs::read_to_string(file_path).ok()?,
+        None => toml::to_string(&store.load(&path).ok()?).ok()?,
+      };
+      Some((path, content))
+    })
+    .collect()
+}
+
+fn get_snapshot_file_path(dir: &Path) -> PathBuf {
+  dir.join(common::LOCAL_STATE_DIR).join("snapshot.toml")
+}
+
+fn get_journal_file_path(dir: &Path) -> PathBuf {
+  dir.join(common::LOCAL_STATE_DIR).join("journal.toml")
+}
+
+fn load_snapshot(dir: &Path) -> Option<BTreeMap<String, String>> {
+  let content = fs::read_to_string(get_snapshot_file_path(dir)).ok()?;
+  toml::from_str(&content).ok()
+}
+
+fn save_snapshot(
+  dir: &Path,
+  snapshot: &BTreeMap<String, String>,
+) -> Result<(), String> {
+  common::create_local_state_dir(dir)?;
+  let content = toml::to_string(snapshot).map_err(|e| e.to_string())?;
+  fs::write(get_snapshot_file_path(dir), content).map_err(|e| e.to_string())
+}
+
+fn load_journal(dir: &Path) -> Journal {
+  fs::read_to_string(get_journal_file_path(dir))
+    .ok()
+    .and_then(|content| toml::from_str(&content).ok())
+    .unwrap_or_default()
+}
+
+fn save_journal(dir: &Path, journal: &Journal) -> Result<(), String> {
+  common::create_local_state_dir(dir)?;
+  let content = toml::to_string(journal).map_err(|e| e.to_string())?;
+  fs::write(get_journal_file_path(dir), content).map_err(|e| e.to_string())
+}
@@ -3,8 +3,6 @@
 // ****************************************************************************
 
 use super::common;
-use std::fs;
-use std::path::PathBuf;
 
 pub fn run(args: Vec<String>) {
   let bookmarks = if args.len() > 2 {
@@ -31,27 +29,16 @@ pub fn run(args: Vec<String>) {
       }
     }
   }
+  let store = common::open_store();
+  let store = store.borrow();
   let formatted = bookmarks.iter().map(|path| {
-    let mut current_path = common::get_bookmark_store_dir_path();
-    current_path.push(PathBuf::from(path));
-    let full_path = format!("{}.toml", &current_path.display());
-    let url =
-      extract_url_from_toml(&full_path).unwrap_or_else(|_| "N/A".to_string());
+    let url = store
+      .load(path)
+      .map(|bookmark| bookmark.url)
+      .unwrap_or_else(|_| "N/A".to_string());
     format!("{}{}{}", path, divisor, url)
   });
   for line in formatted {
     println!("{}", line);
   }
 }
-
-fn extract_url_from_toml(
-  path: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-  let content = fs::read_to_string(path)?;
-  let toml: toml::Value = toml::from_str(&content)?;
-  toml
-    .get("url")
-    .and_then(|v| v.as_str())
-    .map(String::from)
-    .ok_or_else(|| "Missing or invalid `url`".into())
-}
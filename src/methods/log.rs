@@ -0,0 +1,287 @@
+// ****************************************************************************
+// Show the history of the store from git: how a single bookmark URL and tags
+// changed over time, following moves, or a changelog of the whole store
+// ****************************************************************************
+
+use super::common::{self, Bookmark};
+use super::config;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Commit {
+  commit: String,
+  date: String,
+  author: String,
+  message: String,
+  changes: Vec<Change>,
+}
+
+#[derive(Serialize)]
+pub struct Change {
+  pub change: &'static str,
+  pub path: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub previous_path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BookmarkEntry {
+  commit: String,
+  date: String,
+  author: String,
+  message: String,
+  change: &'static str,
+  path: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  previous_path: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  url: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  previous_url: Option<String>,
+  tags: Vec<String>,
+  tags_added: Vec<String>,
+  tags_removed: Vec<String>,
+}
+
+pub fn run(mut args: Vec<String>) {
+  let json = common::take_flag(&mut args, &["--json"]);
+  let limit = common::take_option(&mut args, "--limit").map(|value| {
+    value.parse::<usize>().unwrap_or_else(|_| {
+      eprintln!("Invalid limit: {}", value);
+      std::process::exit(1);
+    })
+  });
+  if !config::load_config().git {
+    eprintln!("Git is disabled: the history of the store is not available.");
+    std::process::exit(1);
+  }
+  common::require_directory_store("history per bookmark");
+  match args.get(2) {
+    Some(relative_path) => {
+      let mut entries = bookmark_history(relative_path);
+      if entries.is_empty() {
+        eprintln!("No history found for {}", relative_path);
+        std::process::exit(1);
+      }
+      entries.truncate(limit.unwrap_or(usize::MAX));
+      if json {
+        common::print_json(&entries);
+      } else {
+        entries.iter().for_each(print_bookmark_entry);
+      }
+    }
+    None => {
+      let mut commits = read_log(&["-M"]);
+      commits.retain(|commit| !commit.changes.is_empty());
+      commits.truncate(limit.unwrap_or(usize::MAX));
+      if json {
+        common::print_json(&commits);
+      } else {
+        commits.iter().for_each(print_commit);
+      }
+    }
+  }
+}
+
+/// Changes of one bookmark, newest first. The file is followed across moves
+/// and the URL and tags of every version are compared with the previous one.
+fn bookmark_history(relative_path: &str) -> Vec<BookmarkEntry> {
+  let file = format!("{}.toml", relative_path);
+  let commits = read_log(&["--follow", "--", &file]);
+  let mut entries = Vec::new();
+  let mut previous: Option<(String, Bookmark)> = None;
+  for commit in commits.into_iter().rev() {
+    let Some(change) = commit.changes.into_iter().next() else {
+      continue;
+    };
+    let current = if change.change == "removed" {
+      None
+    } else {
+      load_bookmark_at(&commit.commit, &change.path)
+        .map(|bookmark| (change.path.clone(), bookmark))
+    };
+    let previous_url = previous.as_ref().map(|(_, b)| b.url.clone());
+    let previous_tags = previous
+      .as_ref()
+      .map(|(_, b)| b.tags.clone())
+      .unwrap_or_default();
+    let (url, tags) = match &current {
+      Some((_, bookmark)) => {
+        (Some(bookmark.url.clone()), bookmark.tags.clone())
+      }
+      None => (None, Vec::new()),
+    };
+    let change_kind = match (&previous, &current) {
+      (None, _) => "added",
+      (_, None) => "removed",
+      (Some((old_path, _)), Some((new_path, _))) if old_path != new_path => {
+        "moved"
+      }
+      _ => "modified",
+    };
+    entries.push(BookmarkEntry {
+      commit: commit.commit,
+      date: commit.date,
+      author: commit.author,
+      message: commit.message,
+      change: change_kind,
+      path: change.path,
+      previous_path: previous
+        .as_ref()
+        .map(|(path, _)| path.clone())
+        .filter(|_| change_kind == "moved"),
+      previous_url: previous_url
+        .filter(|previous| Some(previous) != url.as_ref()),
+      tags_added: tags
+        .iter()
+        .filter(|tag| !previous_tags.contains(tag))
+        .cloned()
+        .collect(),
+      tags_removed: previous_tags
+        .iter()
+        .filter(|tag| !tags.contains(tag))
+        .cloned()
+        .collect(),
+      url,
+      tags,
+    });
+    previous = current;
+  }
+  entries.reverse();
+  entries
+}
+
+/// Commits of the store, newest first, with the bookmarks they changed.
+fn read_log(extra_args: &[&str]) -> Vec<Commit> {
+  let mut args = vec![
+    "log",
+    "--name-status",
+    "--format=%x1e%H%x1f%at%x1f%an%x1f%s",
+  ];
+  args.extend_from_slice(extra_args);
+  let Some(log) = common::git_output(&args) else {
+    return Vec::new();
+  };
+  log
+    .split('\x1e')
+    .filter_map(|record| {
+      let (header, body) = record.split_once('\n')?;
+      let fields: Vec<&str> = header.split('\x1f').collect();
+      let [commit, time, author, message] = fields[..] else {
+        return None;
+      };
+      let changes = body.lines().filter_map(parse_change).collect();
+      Some(Commit {
+        commit: commit.to_string(),
+        date: common::format_timestamp(time.parse().unwrap_or(0)),
+        author: author.to_string(),
+        message: message.to_string(),
+        changes,
+      })
+    })
+    .collect()
+}
+
+/// Parses a `--name-status` line such as `M\tpath.toml` or
+/// `R100\told.toml\tnew.toml`, skipping files that are not bookmarks.
+pub fn parse_change(line: &str) -> Option<Change> {
+  let mut fields = line.split('\t');
+  let status = fields.next()?;
+  let first = fields.next()?;
+  let (path, previous_path) = match fields.next() {
+    Some(second) => (second, Some(first)),
+    None => (first, None),
+  };
+  let change = match status.chars().next()? {
+    'A' => "added",
+    'D' => "removed",
+    'R' => "moved",
+    _ => "modified",
+  };
+  Some(Change {
+    change,
+    path: bookmark_path(path)?,
+    previous_path: previous_path.and_then(bookmark_path),
+  })
+}
+
+/// The bookmark path of a file of the store, without the `.toml` extension.
+/// Files in hidden directories are not bookmarks.
+pub fn bookmark_path(file: &str) -> Option<String> {
+  let path = file.strip_suffix(".toml")?;
+  if path.split('/').any(|segment| segment.starts_with('.')) {
+    return None;
+  }
+  Some(path.to_string())
+}
+
+fn load_bookmark_at(commit: &str, relative_path: &str) -> Option<Bookmark> {
+  let content = common::git_output(&[
+    "show",
+    &format!("{}:{}.toml", commit, relative_path),
+  ])?;
+  toml::from_str(&content).ok()
+}
+
+fn print_commit(commit: &Commit) {
+  println!(
+    "{} {} {}: {}",
+    &commit.commit[..7],
+    human_date(&commit.date),
+    commit.author,
+    commit.message
+  );
+  for change in &commit.changes {
+    match &change.previous_path {
+      Some(previous_path) => {
+        println!("  {} {} -> {}", change.change, previous_path, change.path)
+      }
+      None => println!("  {} {}", change.change, change.path),
+    }
+  }
+}
+
+fn print_bookmark_entry(entry: &BookmarkEntry) {
+  println!(
+    "{} {} {}: {}",
+    &entry.commit[..7],
+    human_date(&entry.date),
+    entry.author,
+    entry.message
+  );
+  match entry.change {
+    "added" => println!(
+      "  added {} :: {}",
+      entry.path,
+      entry.url.as_deref().unwrap_or_default()
+    ),
+    "removed" => println!("  removed {}", entry.path),
+    _ => {}
+  }
+  if let Some(previous_path) = &entry.previous_path {
+    println!("  moved {} -> {}", previous_path, entry.path);
+  }
+  if let (Some(previous_url), Some(url)) = (&entry.previous_url, &entry.url) {
+    println!("  url {} -> {}", previous_url, url);
+  }
+  if entry.change == "added" {
+    if !entry.tags.is_empty() {
+      println!("  tags {}", entry.tags.join(", "));
+    }
+  } else if entry.change != "removed"
+    && (!entry.tags_added.is_empty() || !entry.tags_removed.is_empty())
+  {
+    let changes: Vec<String> = entry
+      .tags_added
+      .iter()
+      .map(|tag| format!("+{}", tag))
+      .chain(entry.tags_removed.iter().map(|tag| format!("-{}", tag)))
+      .collect();
+    println!("  tags {}", changes.join(", "));
+  }
+}
+
+/// `2026-01-31T10:20:30Z` as `2026-01-31 10:20`.
+fn human_date(date: &str) -> String {
+  date.get(..16).unwrap_or(date).replacen('T', " ", 1)
+}
@@ -0,0 +1,220 @@
+// ****************************************************************************
+// Git merge driver for bookmark files, registered in `.gitattributes` for
+// `*.toml` and `bookmarks.json`. Git calls it with the common ancestor, the
+// current version (where the result is written) and the other version of a
+// file. The bookmarks are merged field by field, the files of the single file
+// stores bookmark by bookmark, and only divergent URLs conflict.
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::config;
+use super::store::FileFormat;
+use std::collections::BTreeMap;
+use std::fs;
+use std::process::Command;
+
+const GITATTRIBUTES_LINES: [&str; 2] =
+  ["*.toml merge=tempesta", "bookmarks.json merge=tempesta"];
+
+/// A version of a file given by git.
+enum Content {
+  /// The ancestor of a file added on both sides.
+  Empty,
+  Bookmark(Box<Bookmark>),
+  /// The file of a single file store.
+  Store(FileFormat, BTreeMap<String, Bookmark>),
+}
+
+pub fn run(args: Vec<String>) {
+  if args.get(2).map(String::as_str) == Some("--install") {
+    if !config::load_config().git {
+      eprintln!("Git is disabled: there is no merge driver to install.");
+      std::process::exit(1);
+    }
+    install();
+    return;
+  }
+  if args.len() < 5 {
+    eprintln!("Usage: tempesta merge-driver <base> <current> <other>");
+    eprintln!("       tempesta merge-driver --install");
+    std::process::exit(1);
+  }
+  let (base_path, current_path, other_path) = (&args[2], &args[3], &args[4]);
+  std::process::exit(merge_files(base_path, current_path, other_path));
+}
+
+/// Merges the files into `current_path` and returns the exit code expected by
+/// git: 0 when the merge is clean.
+fn merge_files(base_path: &str, current_path: &str, other_path: &str) -> i32 {
+  match (read(base_path), read(current_path), read(other_path)) {
+    (
+      Some(Content::Empty),
+      Some(Content::Bookmark(current)),
+      Some(Content::Bookmark(other)),
+    ) => merge_bookmark(None, *current, *other, current_path),
+    (
+      Some(Content::Bookmark(base)),
+      Some(Content::Bookmark(current)),
+      Some(Content::Bookmark(other)),
+    ) => merge_bookmark(Some(*base), *current, *other, current_path),
+    (
+      Some(Content::Empty),
+      Some(Content::Store(format, current)),
+      Some(Content::Store(_, other)),
+    ) => merge_store(&BTreeMap::new(), &current, &other, format, current_path),
+    (
+      Some(Content::Store(_, base)),
+      Some(Content::Store(format, current)),
+      Some(Content::Store(_, other)),
+    ) => merge_store(&base, &current, &other, format, current_path),
+    // Not bookmarks, fall back to the usual line based merge
+    _ => Command::new("git")
+      .args(["merge-file", current_path, base_path, other_path])
+      .status()
+      .ok()
+      .and_then(|status| status.code())
+      .unwrap_or(1),
+  }
+}
+
+fn merge_bookmark(
+  base: Option<Bookmark>,
+  current: Bookmark,
+  other: Bookmark,
+  current_path: &str,
+) -> i32 {
+  let (merged, conflict) =
+    match common::merge_bookmarks(base.as_ref(), &current, &other) {
+      Ok(merged) => (merged, None),
+      Err(urls) => {
+        let mut other = other;
+        other.url = current.url.clone();
+        let merged = common::merge_bookmarks(base.as_ref(), &current, &other)
+          .ok()
+          .panic_on_error("Failed to merge bookmarks");
+        (merged, Some(urls))
+      }
+    };
+  let mut content =
+    toml::to_string(&merged).panic_on_error("Failed to serialize bookmark");
+  if let Some((current_url, other_url)) = &conflict {
+    // The URL is the first line, replace it with conflict markers
+    let rest = content.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+    content = format!(
+      "<<<<<<< current\nurl = {}\n=======\nurl = {}\n>>>>>>> other\n{}",
+      toml::Value::String(current_url.clone()),
+      toml::Value::String(other_url.clone()),
+      rest
+    );
+  }
+  fs::write(current_path, content)
+    .panic_on_error("Failed to write bookmark file");
+  if conflict.is_some() {
+    1
+  } else {
+    0
+  }
+}
+
+/// Merges the files of a single file store bookmark by bookmark. The
+/// conflicting bookmarks keep the current version and are left to `sync`,
+/// which reads the versions from the index.
+fn merge_store(
+  base: &BTreeMap<String, Bookmark>,
+  current: &BTreeMap<String, Bookmark>,
+  other: &BTreeMap<String, Bookmark>,
+  format: FileFormat,
+  current_path: &str,
+) -> i32 {
+  let mut conflicts = 0;
+  let merged: Result<_, ()> = common::merge_bookmark_maps(
+    base,
+    current,
+    other,
+    |relative_path, base, current, other| match (current, other) {
+      (Some(current), Some(other)) => Ok(Some(
+        common::merge_bookmarks(base, &current, &other).unwrap_or_else(|_| {
+          eprintln!("Conflicting URLs for {}", relative_path);
+          conflicts += 1;
+          current
+        }),
+      )),
+      (current, _) => {
+        eprintln!("{} was removed on one side only", relative_path);
+        conflicts += 1;
+        Ok(current)
+      }
+    },
+  );
+  let content = merged
+    .and_then(|merged| format.serialize(&merged).map_err(|_| ()))
+    .ok()
+    .panic_on_error("Failed to serialize bookmarks");
+  fs::write(current_path, content)
+    .panic_on_error("Failed to write bookmark file");
+  if conflicts > 0 {
+    1
+  } else {
+    0
+  }
+}
+
+/// `None` when the file is neither a bookmark nor the file of a single file
+/// store.
+fn read(path: &str) -> Option<Content> {
+  let content = fs::read_to_string(path).ok()?;
+  if content.trim().is_empty() {
+    return Some(Content::Empty);
+  }
+  if let Ok(bookmark) = toml::from_str(&content) {
+    return Some(Content::Bookmark(Box::new(bookmark)));
+  }
+  [FileFormat::Toml, FileFormat::Json]
+    .into_iter()
+    .find_map(|format| {
+      let bookmarks = format.parse(&content).ok()?;
+      Some(Content::Store(format, bookmarks))
+    })
+}
+
+/// Registers the driver in the git configuration of the store and commits
+/// the `.gitattributes` using it, so that every clone shares it. The driver
+/// runs `tempesta` from the `PATH`, so it survives upgrades and moves of the
+/// executable.
+pub fn install() {
+  let driver = "tempesta merge-driver %O %A %B";
+  common::git_command(
+    &["config", "merge.tempesta.name", "tempesta bookmark merge"],
+    "Failed to configure the merge driver",
+  );
+  common::git_command(
+    &["config", "merge.tempesta.driver", driver],
+    "Failed to configure the merge driver",
+  );
+  let gitattributes_path =
+    common::get_bookmark_store_dir_path().join(".gitattributes");
+  let mut content = fs::read_to_string(&gitattributes_path).unwrap_or_default();
+  let missing: Vec<&str> = GITATTRIBUTES_LINES
+    .into_iter()
+    .filter(|expected| !content.lines().any(|line| line.trim() == *expected))
+    .collect();
+  if !missing.is_empty() {
+    if !content.is_empty() && !content.ends_with('\n') {
+      content.push('\n');
+    }
+    for line in missing {
+      content.push_str(line);
+      content.push('\n');
+    }
+    fs::write(&gitattributes_path, content)
+      .panic_on_error("Failed to write .gitattributes");
+    common::git_commit(
+      "Merge bookmarks with the tempesta merge driver",
+      common::CommitInfo {
+        action: "install",
+        ..Default::default()
+      },
+    );
+  }
+  println!("Merge driver installed for bookmark files");
+}
@@ -0,0 +1,120 @@
+// ****************************************************************************
+// Fetch a web page and extract its title, description and keywords from the
+// <title> element, OpenGraph properties and meta tags
+// ****************************************************************************
+
+use super::common::{self, PanicOnError};
+use regex::Regex;
+use std::time::Duration;
+
+const MAX_SUGGESTED_TAGS: usize = 5;
+
+#[derive(Default)]
+pub struct Metadata {
+  pub title: Option<String>,
+  pub description: Option<String>,
+  pub keywords: Vec<String>,
+}
+
+pub fn fetch(url: &str, timeout: Duration) -> Result<Metadata, String> {
+  let agent = common::http_agent(timeout, 10);
+  let response = agent.get(url).call().map_err(|e| e.to_string())?;
+  if !response.content_type().contains("html") {
+    return Err(format!("not an HTML page ({})", response.content_type()));
+  }
+  let html = response.into_string().map_err(|e| e.to_string())?;
+  Ok(parse(&html))
+}
+
+pub fn parse(html: &str) -> Metadata {
+  let mut metadata = Metadata::default();
+  let mut og_title = None;
+  let mut og_description = None;
+  let meta_re =
+    Regex::new(r"(?is)<meta\s[^>]*>").panic_on_error("Invalid metadata regex");
+  let attribute_re =
+    Regex::new(r#"(?is)([a-z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+      .panic_on_error("Invalid metadata regex");
+  for meta in meta_re.find_iter(html) {
+    let mut key = None;
+    let mut content = None;
+    for captures in attribute_re.captures_iter(meta.as_str()) {
+      let value = captures
+        .get(2)
+        .or_else(|| captures.get(3))
+        .map(|m| decode_entities(m.as_str().trim()));
+      match captures[1].to_lowercase().as_str() {
+        "name" | "property" => key = value.map(|v| v.to_lowercase()),
+        "content" => content = value,
+        _ => {}
+      }
+    }
+    let (Some(key), Some(content)) = (key, content) else {
+      continue;
+    };
+    if content.is_empty() {
+      continue;
+    }
+    match key.as_str() {
+      "og:title" => og_title = Some(content),
+      "og:description" => og_description = Some(content),
+      "description" => metadata.description = Some(content),
+      "keywords" => metadata
+        .keywords
+        .extend(content.split(',').map(|k| k.trim().to_string())),
+      "article:tag" => metadata.keywords.push(content),
+      _ => {}
+    }
+  }
+  let title_re = Regex::new(r"(?is)<title[^>]*>(.*?)</title>")
+    .panic_on_error("Invalid metadata regex");
+  metadata.title = og_title.or_else(|| {
+    title_re
+      .captures(html)
+      .map(|captures| decode_entities(captures[1].trim()))
+      .filter(|title| !title.is_empty())
+  });
+  metadata.description = og_description.or(metadata.description);
+  metadata
+}
+
+/// Tags suggested from the page keywords, as valid path-like slugs.
+pub fn suggest_tags(metadata: &Metadata, existing: &[String]) -> Vec<String> {
+  let mut tags: Vec<String> = Vec::new();
+  for keyword in &metadata.keywords {
+    let tag = common::slugify(keyword, 30);
+    if !tag.is_empty() && !tags.contains(&tag) && !existing.contains(&tag) {
+      tags.push(tag);
+    }
+  }
+  tags.truncate(MAX_SUGGESTED_TAGS);
+  tags
+}
+
+fn decode_entities(text: &str) -> String {
+  let entity_re = Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|[a-zA-Z]+);")
+    .panic_on_error("Invalid metadata regex");
+  let decoded = entity_re.replace_all(text, |captures: &regex::Captures| {
+    let entity = &captures[1];
+    let code = if let Some(hex) = entity.strip_prefix("#x") {
+      u32::from_str_radix(hex, 16).ok()
+    } else if let Some(decimal) = entity.strip_prefix('#') {
+      decimal.parse().ok()
+    } else {
+      match entity {
+        "amp" => Some('&' as u32),
+        "lt" => Some('<' as u32),
+        "gt" => Some('>' as u32),
+        "quot" => Some('"' as u32),
+        "apos" => Some('\'' as u32),
+        "nbsp" => Some(' ' as u32),
+        _ => None,
+      }
+    };
+    code
+      .and_then(char::from_u32)
+      .map(String::from)
+      .unwrap_or_else(|| captures[0].to_string())
+  });
+  decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,94 @@
+// ****************************************************************************
+// Move every bookmark to another storage backend: one TOML file per bookmark,
+// a single TOML or JSON file, or an SQLite database. See `store.rs`.
+// ****************************************************************************
+
+use super::common::{self, PanicOnError};
+use super::config::{self, StoreBackend};
+use super::store;
+
+pub fn run(mut args: Vec<String>) {
+  let Some(target) = common::take_option(&mut args, "--to") else {
+    print_usage();
+  };
+  let target = match target.as_str() {
+    "directory" => StoreBackend::Directory,
+    "toml" => StoreBackend::Toml,
+    "json" => StoreBackend::Json,
+    "sqlite" => StoreBackend::Sqlite,
+    _ => print_usage(),
+  };
+  let mut config = config::load_config();
+  let current = config.store_backend.unwrap_or_default();
+  if current == target {
+    println!(
+      "The bookmarks are already stored with the {} backend",
+      target
+    );
+    return;
+  }
+  let store_dir_path = common::get_bookmark_store_dir_path();
+  let source = common::open_store();
+  let mut bookmarks = Vec::new();
+  let mut invalid = 0;
+  let loaded = source.borrow().load_all(None);
+  for (path, bookmark) in loaded {
+    match bookmark {
+      Ok(bookmark) => bookmarks.push((path, bookmark)),
+      Err(e) => {
+        eprintln!("{}: {}", path, e);
+        invalid += 1;
+      }
+    }
+  }
+  if invalid > 0 {
+    eprintln!("Fix or remove the {} invalid bookmarks first", invalid);
+    std::process::exit(1);
+  }
+  // A bookmark at the root named `bookmarks` is stored in the same file as
+  // the single TOML file backend
+  let uses_file =
+    |backend| matches!(backend, StoreBackend::Directory | StoreBackend::Toml);
+  if uses_file(current)
+    && uses_file(target)
+    && bookmarks.iter().any(|(path, _)| path == "bookmarks")
+  {
+    eprintln!("Move the bookmark `bookmarks` elsewhere before migrating");
+    std::process::exit(1);
+  }
+  let mut destination =
+    store::open(target, &store_dir_path).unwrap_or_else(|e| {
+      eprintln!("Cannot open the {} store: {}", target, e);
+      std::process::exit(1);
+    });
+  for (path, bookmark) in &bookmarks {
+    destination
+      .save(path, bookmark)
+      .panic_on_error("Failed to write bookmark");
+  }
+  source
+    .borrow_mut()
+    .clear()
+    .panic_on_error("Failed to remove the previous store");
+  config.store_backend = Some(target);
+  config::save_config(&config);
+  let comment = format!("Migrate bookmarks to the {} store", target);
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "migrate",
+      ..Default::default()
+    },
+  );
+  println!(
+    "Migrated {} bookmarks from the {} to the {} store",
+    bookmarks.len(),
+    current,
+    target
+  );
+}
+
+fn print_usage() -> ! {
+  eprintln!("Usage: tempesta migrate-store --to <directory|toml|json|sqlite>");
+  std::process::exit(1);
+}
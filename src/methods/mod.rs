@@ -1,12 +1,32 @@
 pub mod add;
+pub mod batch;
+pub mod check;
 pub mod common;
 pub mod completion;
 pub mod config;
+pub mod dedupe;
 pub mod edit;
 pub mod get;
 pub mod init;
+pub mod journal;
 pub mod list;
+pub mod log;
+pub mod merge_driver;
+pub mod metadata;
+pub mod migrate_store;
 pub mod r#move;
+#[cfg(feature = "native-git")]
+pub mod native_git;
 pub mod open;
+pub mod queue;
 pub mod remove;
+pub mod restore;
+pub mod review;
+pub mod serve;
+pub mod status;
+pub mod store;
+pub mod sync;
+pub mod trash;
+pub mod undo;
 pub mod update;
+pub mod verify;
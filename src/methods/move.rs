@@ -2,10 +2,9 @@
 // Move a bookmark
 // ****************************************************************************
 
-use super::common::{self, PanicOnError};
-use std::fs;
+use super::common;
+use crate::store::{self, Store};
 use std::io::{self, Write};
-use std::path::Path;
 
 pub fn run(args: Vec<String>) {
   if args.len() < 4 {
@@ -14,51 +13,33 @@ pub fn run(args: Vec<String>) {
   }
 
   let relative_path_from = &args[2];
-  common::validate_path(relative_path_from);
+  common::validate_bookmark_path(relative_path_from);
   let relative_path_to = &args[3];
   common::validate_path(relative_path_to);
 
-  let toml_from_file_path = common::get_bookmark_file_path(relative_path_from);
-  if !toml_from_file_path.exists() {
-    eprintln!("Path {:?} do not exists", &toml_from_file_path.to_str());
+  if !common::bookmark_exists(relative_path_from) {
+    eprintln!(
+      "Path {:?} do not exists",
+      common::open_store().borrow().location(relative_path_from)
+    );
     std::process::exit(1);
   }
 
-  let toml_to_file_path = if relative_path_to.ends_with('/') {
-    let directory_path = Path::new(relative_path_to);
-    let file_name = toml_from_file_path.file_stem().unwrap();
-    let full_path = &directory_path.join(file_name).display().to_string();
-    common::get_bookmark_file_path(full_path)
-  } else {
-    common::get_bookmark_file_path(relative_path_to)
-  };
+  let destination_path =
+    common::move_destination(relative_path_from, relative_path_to);
 
-  if toml_to_file_path.exists() && !prompt_for_overwrite(&toml_to_file_path) {
+  if common::bookmark_exists(&destination_path)
+    && !prompt_for_overwrite(
+      &common::open_store().borrow().location(&destination_path),
+    )
+  {
     println!("Move operation aborted.");
     std::process::exit(0);
   }
 
-  if let Some(parent) = toml_to_file_path.parent() {
-    if !parent.exists() {
-      fs::create_dir_all(parent)
-        .panic_on_error("Failed to create destination directory")
-    }
-  }
-
-  fs::rename(&toml_from_file_path, &toml_to_file_path)
-    .panic_on_error("Failed to move bookmark file");
-
-  // After successful move, cleanup empty parent directories
-  if let Some(parent) = toml_from_file_path.parent() {
-    cleanup_empty_parents(parent)
-      .panic_on_error("Warning: Failed to clean up empty directories")
-  }
-
-  let comment = format!(
-    "Move bookmark from {} to {}",
-    &relative_path_from, &relative_path_to
+  store::exit_on_error(
+    Store::shared().move_over(relative_path_from, &destination_path),
   );
-  common::git_commit(&comment);
 
   println!(
     "Bookmark moved successfully from {} to {}",
@@ -66,10 +47,10 @@ pub fn run(args: Vec<String>) {
   );
 }
 
-fn prompt_for_overwrite(destination: &Path) -> bool {
+fn prompt_for_overwrite(destination: &str) -> bool {
   print!(
     "A bookmark already exists at {}. Overwrite? [Y/n]: ",
-    destination.display()
+    destination
   );
   io::stdout().flush().expect("Failed to flush stdout");
 
@@ -89,28 +70,3 @@ fn prompt_for_overwrite(destination: &Path) -> bool {
     }
   }
 }
-
-/// Recursively removes parent directories if they are empty.
-/// Stops once a non-empty directory is found or the root is reached.
-fn cleanup_empty_parents(starting_dir: &Path) -> std::io::Result<()> {
-  let mut current = starting_dir.to_path_buf();
-  loop {
-    // If directory does not exist (already removed), we just break.
-    if !current.exists() {
-      break;
-    }
-    // Check if the directory is empty.
-    let is_empty = fs::read_dir(&current)?.next().is_none();
-    if is_empty {
-      // Try to remove the directory.
-      fs::remove_dir(&current)?;
-      // Move to parent directory for the next check.
-      if !current.pop() {
-        break; // Reached the root.
-      }
-    } else {
-      break; // Stop if we hit a non-empty directory.
-    }
-  }
-  Ok(())
-}
@@ -0,0 +1,237 @@
+// ****************************************************************************
+// In process git backend built on libgit2, used instead of the `git` binary to
+// initialize, commit, fetch, fast-forward and push the store. Commands reading
+// the history and merges with conflicts still run the `git` binary.
+// ****************************************************************************
+
+use super::config::Config;
+use git2::{
+  BranchType, Cred, CredentialType, FetchOptions, IndexAddOption, PushOptions,
+  RemoteCallbacks, Repository, Signature,
+};
+use std::env;
+use std::path::Path;
+
+/// Creates the repository of the store and, with a remote, checks out its
+/// branch when it already exists there.
+pub fn init(
+  dir: &Path,
+  remote_url: Option<&str>,
+  branch: &str,
+) -> Result<(), git2::Error> {
+  let repo = Repository::init(dir)?;
+  if let Some(remote_url) = remote_url {
+    repo.set_head(&format!("refs/heads/{}", branch))?;
+    let mut remote = repo.remote("origin", remote_url)?;
+    let git_config = repo.config()?;
+    remote.fetch(
+      &[] as &[&str],
+      Some(&mut fetch_options(&git_config)),
+      None,
+    )?;
+    if let Ok(reference) =
+      repo.find_reference(&format!("refs/remotes/origin/{}", branch))
+    {
+      let commit = reference.peel_to_commit()?;
+      let mut local = repo.branch(branch, &commit, false)?;
+      local.set_upstream(Some(&format!("origin/{}", branch)))?;
+      repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+    }
+  }
+  Ok(())
+}
+
+/// Stages every change of the store and commits it. Returns `false` when
+/// there is nothing to commit.
+pub fn commit(
+  dir: &Path,
+  config: &Config,
+  message: &str,
+) -> Result<bool, git2::Error> {
+  let repo = Repository::open(dir)?;
+  let mut index = repo.index()?;
+  index.update_all(["*"], None)?;
+  index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+  index.write()?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let parent = match repo.head() {
+    Ok(head) => Some(head.peel_to_commit()?),
+    Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+    Err(e) => return Err(e),
+  };
+  if parent
+    .as_ref()
+    .is_some_and(|parent| parent.tree_id() == tree.id())
+  {
+    return Ok(false);
+  }
+  let author = signature(&repo, config, "AUTHOR")?;
+  let committer = signature(&repo, config, "COMMITTER")?;
+  repo.commit(
+    Some("HEAD"),
+    &author,
+    &committer,
+    message,
+    &tree,
+    &parent.iter().collect::<Vec<_>>(),
+  )?;
+  Ok(true)
+}
+
+/// The identity of the store, else the one of the environment like the `git`
+/// binary, else the one of the git configuration.
+fn signature(
+  repo: &Repository,
+  config: &Config,
+  role: &str,
+) -> Result<Signature<'static>, git2::Error> {
+  let git_config = repo.config()?;
+  let name = config
+    .git_author_name
+    .clone()
+    .or_else(|| env::var(format!("GIT_{}_NAME", role)).ok())
+    .or_else(|| git_config.get_string("user.name").ok());
+  let email = config
+    .git_author_email
+    .clone()
+    .or_else(|| env::var(format!("GIT_{}_EMAIL", role)).ok())
+    .or_else(|| git_config.get_string("user.email").ok());
+  match (name, email) {
+    (Some(name), Some(email)) => Signature::now(&name, &email),
+    _ => Err(git2::Error::from_str(
+      "author identity unknown, set git_author_name and git_author_email",
+    )),
+  }
+}
+
+pub fn fetch(dir: &Path) -> Result<(), git2::Error> {
+  let repo = Repository::open(dir)?;
+  let git_config = repo.config()?;
+  let mut remote = repo.find_remote("origin")?;
+  remote.fetch(&[] as &[&str], Some(&mut fetch_options(&git_config)), None)?;
+  Ok(())
+}
+
+/// Moves the current branch to the remote one when it only adds commits.
+/// Fails when the branches diverged: merging them is the job of `sync`.
+pub fn fast_forward(dir: &Path, upstream: &str) -> Result<(), git2::Error> {
+  let repo = Repository::open(dir)?;
+  let upstream_commit = repo
+    .find_reference(&format!("refs/remotes/{}", upstream))?
+    .peel_to_commit()?;
+  let annotated = repo.find_annotated_commit(upstream_commit.id())?;
+  let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+  if analysis.is_up_to_date() {
+    return Ok(());
+  }
+  if !analysis.is_fast_forward() && !analysis.is_unborn() {
+    return Err(git2::Error::from_str(
+      "the local and remote branches diverged",
+    ));
+  }
+  repo.checkout_tree(
+    upstream_commit.as_object(),
+    Some(git2::build::CheckoutBuilder::new().safe()),
+  )?;
+  let head = repo.find_reference("HEAD")?;
+  let branch = head.symbolic_target().unwrap_or("HEAD").to_string();
+  repo.reference(
+    &branch,
+    upstream_commit.id(),
+    true,
+    &format!("fast-forward to {}", upstream),
+  )?;
+  Ok(())
+}
+
+/// The remote branch tracked by the current branch, or the one with the same
+/// name on origin.
+pub fn upstream(dir: &Path) -> Option<String> {
+  let repo = Repository::open(dir).ok()?;
+  let head = repo.find_reference("HEAD").ok()?;
+  let branch_name = head.symbolic_target()?.strip_prefix("refs/heads/")?;
+  if let Ok(upstream) = repo
+    .find_branch(branch_name, BranchType::Local)
+    .and_then(|branch| branch.upstream())
+  {
+    return upstream.name().ok()?.map(str::to_string);
+  }
+  let upstream = format!("origin/{}", branch_name);
+  repo
+    .find_reference(&format!("refs/remotes/{}", upstream))
+    .ok()
+    .map(|_| upstream)
+}
+
+/// Pushes every local branch to origin, tracking the ones without upstream.
+pub fn push(dir: &Path) -> Result<(), git2::Error> {
+  let repo = Repository::open(dir)?;
+  let git_config = repo.config()?;
+  let mut names = Vec::new();
+  for branch in repo.branches(Some(BranchType::Local))? {
+    if let Some(name) = branch?.0.name()? {
+      names.push(name.to_string());
+    }
+  }
+  let refspecs: Vec<String> = names
+    .iter()
+    .map(|name| format!("refs/heads/{0}:refs/heads/{0}", name))
+    .collect();
+  let mut rejected = Vec::new();
+  {
+    let mut callbacks = remote_callbacks(&git_config);
+    callbacks.push_update_reference(|reference, status| {
+      if let Some(status) = status {
+        rejected.push(format!("{}: {}", reference, status));
+      }
+      Ok(())
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    repo
+      .find_remote("origin")?
+      .push(&refspecs, Some(&mut options))?;
+  }
+  if !rejected.is_empty() {
+    return Err(git2::Error::from_str(&format!(
+      "rejected {}",
+      rejected.join(", ")
+    )));
+  }
+  for name in names {
+    let mut branch = repo.find_branch(&name, BranchType::Local)?;
+    if branch.upstream().is_err() {
+      branch.set_upstream(Some(&format!("origin/{}", name)))?;
+    }
+  }
+  Ok(())
+}
+
+fn fetch_options(git_config: &git2::Config) -> FetchOptions<'_> {
+  let mut options = FetchOptions::new();
+  options.remote_callbacks(remote_callbacks(git_config));
+  options
+}
+
+/// Authenticates with the SSH agent or the git credential helpers, trying
+/// each of them once.
+fn remote_callbacks(git_config: &git2::Config) -> RemoteCallbacks<'_> {
+  let mut callbacks = RemoteCallbacks::new();
+  let mut tried = CredentialType::empty();
+  callbacks.credentials(move |url, username, allowed| {
+    let remaining = allowed - tried;
+    if remaining.contains(CredentialType::SSH_KEY) {
+      tried |= CredentialType::SSH_KEY;
+      Cred::ssh_key_from_agent(username.unwrap_or("git"))
+    } else if remaining.contains(CredentialType::USER_PASS_PLAINTEXT) {
+      tried |= CredentialType::USER_PASS_PLAINTEXT;
+      Cred::credential_helper(git_config, url, username)
+    } else if remaining.contains(CredentialType::DEFAULT) {
+      tried |= CredentialType::DEFAULT;
+      Cred::default()
+    } else {
+      Err(git2::Error::from_str("no valid credentials"))
+    }
+  });
+  callbacks
+}
@@ -1,19 +1,83 @@
 // ****************************************************************************
-// Open a bookmark in the browser
+// Open one or many bookmarks in the browser or with the opener set for their
+// scheme. Bookmarks can be selected by path, directory, glob or tag.
 // ****************************************************************************
 
 use super::common::{self, PanicOnError};
+use super::config;
+use std::io::{self, Write};
+use std::thread;
 
-pub fn run(args: Vec<String>) {
-  let relative_path = if args.len() < 3 {
-    // No path provided, try to invoke finder
-    eprintln!("Usage: tempesta open <path>");
+const DEFAULT_OPEN_CONFIRM_THRESHOLD: usize = 10;
+
+pub fn run(mut args: Vec<String>) {
+  let browser_profile = common::take_option(&mut args, "--browser");
+  if let Some(profile) = &browser_profile {
+    if !common::has_browser_profile(profile) {
+      eprintln!("Unknown browser profile: {}", profile);
+      std::process::exit(1);
+    }
+  }
+  let tags = common::take_options(&mut args, "--tag");
+  let delay = common::take_option(&mut args, "--delay").map(|value| {
+    common::parse_duration(&value).unwrap_or_else(|| {
+      eprintln!("Invalid delay: {}", value);
+      std::process::exit(1);
+    })
+  });
+  let assume_yes = common::take_flag(&mut args, &["--yes", "-y"]);
+  let random = common::take_flag(&mut args, &["--random"]);
+  if args.len() < 3 && tags.is_empty() && !random {
+    eprintln!(
+      "Usage: tempesta open <path|dir|glob>... [--tag <tag>] [--random] \
+      [--browser <profile>] [--delay <duration>] [--yes]"
+    );
+    std::process::exit(1);
+  }
+  let mut relative_paths = common::select_bookmarks(&args[2..], &tags);
+  if relative_paths.is_empty() {
+    eprintln!("No bookmarks found.");
     std::process::exit(1);
-  } else {
-    args[2].clone()
-  };
-  common::validate_path(&relative_path);
-  let url = common::get_url(&relative_path);
-  common::validate_url(&url);
-  webbrowser::open(&url).panic_on_error("Failed to open browser");
+  }
+  if random {
+    let index = common::random_index(relative_paths.len());
+    relative_paths = vec![relative_paths.swap_remove(index)];
+  }
+  let threshold = config::load_config()
+    .open_confirm_threshold
+    .unwrap_or(DEFAULT_OPEN_CONFIRM_THRESHOLD);
+  if relative_paths.len() > threshold
+    && !assume_yes
+    && !prompt_for_open(relative_paths.len())
+  {
+    println!("Operation cancelled.");
+    return;
+  }
+  for (index, relative_path) in relative_paths.iter().enumerate() {
+    if index > 0 {
+      if let Some(delay) = delay {
+        thread::sleep(delay);
+      }
+    }
+    let bookmark = common::load_bookmark(relative_path);
+    common::validate_url(&bookmark.url);
+    let browser = browser_profile
+      .clone()
+      .or(bookmark.browser)
+      .or_else(|| common::get_directory_browser(relative_path));
+    common::open_url(&bookmark.url, browser.as_deref());
+    common::record_visit(relative_path);
+  }
+}
+
+fn prompt_for_open(count: usize) -> bool {
+  print!("Open {} bookmarks? (y/N): ", count);
+  io::stdout()
+    .flush()
+    .panic_on_error("Failed to flush stdout");
+  let mut input = String::new();
+  io::stdin()
+    .read_line(&mut input)
+    .panic_on_error("Failed to read input");
+  matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
 }
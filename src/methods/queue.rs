@@ -0,0 +1,132 @@
+// ****************************************************************************
+// Reading queue: bookmarks flagged as unread, added under the inbox directory
+// and consumed oldest first (or at random)
+// ****************************************************************************
+
+use super::common::{self, Bookmark};
+use super::config;
+
+const DEFAULT_QUEUE_DIR: &str = "inbox";
+
+pub fn run(args: Vec<String>) {
+  if args.len() < 3 {
+    print_usage();
+  }
+  let sub_args = args[2..].to_vec();
+  match args[2].as_str() {
+    "add" | "a" => add(sub_args),
+    "next" | "n" => next(sub_args),
+    "list" | "l" | "ls" => list(),
+    _ => print_usage(),
+  }
+}
+
+fn print_usage() -> ! {
+  eprintln!("Usage: tempesta queue add <url> [tags...]");
+  eprintln!("       tempesta queue next [--random] [--tag <tag>]");
+  eprintln!("       tempesta queue list");
+  std::process::exit(1);
+}
+
+fn add(args: Vec<String>) {
+  if args.len() < 2 {
+    print_usage();
+  }
+  let url = &args[1];
+  common::validate_url(url);
+  let relative_path = common::generate_bookmark_path(
+    &format!("{}/{{slug}}", get_queue_dir()),
+    url,
+    None,
+  );
+  common::validate_bookmark_path(&relative_path);
+  let bookmark = Bookmark {
+    url: url.to_owned(),
+    tags: args[2..].to_vec(),
+    unread: Some(true),
+    created_at: Some(common::now_timestamp()),
+    ..Default::default()
+  };
+  common::store_bookmark(&relative_path, &bookmark);
+  let comment = format!("Queue bookmark {}", &relative_path);
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "queue",
+      path: &relative_path,
+      url: &bookmark.url,
+    },
+  );
+  println!("Bookmark queued successfully as {}", &relative_path);
+}
+
+fn next(mut args: Vec<String>) {
+  let random = common::take_flag(&mut args, &["--random"]);
+  let tags = common::take_options(&mut args, "--tag");
+  let mut unread = get_unread_bookmarks();
+  unread.retain(|(_, bookmark)| tags.iter().all(|t| bookmark.tags.contains(t)));
+  if unread.is_empty() {
+    println!("The reading queue is empty.");
+    return;
+  }
+  let index = if random {
+    common::random_index(unread.len())
+  } else {
+    0
+  };
+  let (relative_path, mut bookmark) = unread.swap_remove(index);
+  common::validate_url(&bookmark.url);
+  let browser = bookmark
+    .browser
+    .clone()
+    .or_else(|| common::get_directory_browser(&relative_path));
+  common::open_url(&bookmark.url, browser.as_deref());
+  common::record_visit(&relative_path);
+  bookmark.unread = None;
+  common::store_bookmark(&relative_path, &bookmark);
+  let comment = format!("Mark bookmark {} as read", &relative_path);
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "read",
+      path: &relative_path,
+      url: &bookmark.url,
+    },
+  );
+  println!("Opened {} and marked it as read", &relative_path);
+}
+
+fn list() {
+  let unread = get_unread_bookmarks();
+  if unread.is_empty() {
+    println!("The reading queue is empty.");
+    return;
+  }
+  for (relative_path, bookmark) in unread {
+    println!("{} :: {}", relative_path, bookmark.url);
+  }
+}
+
+/// Unread bookmarks of the whole store, oldest first. Bookmarks without a
+/// creation time come first.
+fn get_unread_bookmarks() -> Vec<(String, Bookmark)> {
+  let mut unread: Vec<(String, Bookmark)> = common::load_bookmarks(None)
+    .into_iter()
+    .filter(|(_, bookmark)| bookmark.unread == Some(true))
+    .collect();
+  unread.sort_by_cached_key(|(_, bookmark)| {
+    bookmark
+      .created_at
+      .as_deref()
+      .and_then(common::parse_timestamp)
+  });
+  unread
+}
+
+fn get_queue_dir() -> String {
+  config::load_config()
+    .queue_dir
+    .unwrap_or_else(|| DEFAULT_QUEUE_DIR.to_string())
+    .trim_end_matches('/')
+    .to_string()
+}
@@ -1,42 +1,44 @@
 // ****************************************************************************
-// Remove a bookmark
+// Remove a bookmark. Unless the trash is disabled or `--permanent` is given,
+// removed bookmarks are moved into the trash.
 // ****************************************************************************
 
 use super::common::{self, PanicOnError};
-use std::fs;
+use super::trash;
+use crate::store::{self, Store};
 use std::io::{self, Write};
 
-pub fn run(args: Vec<String>) {
+pub fn run(mut args: Vec<String>) {
+  let permanent = common::take_flag(&mut args, &["--permanent"]);
   if args.len() < 3 {
-    eprintln!("Usage: tempesta remove <path>");
+    eprintln!("Usage: tempesta remove <path> [--permanent]");
     std::process::exit(1);
   }
+  let use_trash = !permanent && trash::is_enabled();
   let relative_path = &args[2];
-  let toml_file_path = common::get_bookmark_file_path(relative_path);
-  if toml_file_path.exists() {
-    fs::remove_file(&toml_file_path).panic_on_error("Failed to remove file");
+  common::validate_path(relative_path);
+  if common::bookmark_exists(relative_path) {
+    // Files that are not valid bookmarks can be removed too
+    store::exit_on_error(
+      Store::shared().remove_bookmark(relative_path, use_trash),
+    );
     println!("Bookmark removed successfully as {}", &relative_path);
-    let mut parent_dir = toml_file_path.parent();
-    while let Some(dir) = parent_dir {
-      if fs::remove_dir(dir).is_ok() {
-        parent_dir = dir.parent();
-      } else {
-        break;
-      }
-    }
-    let comment = format!("Remove bookmark {}", &relative_path);
-    common::git_commit(&comment);
     return;
   }
   let mut given_path = common::get_bookmark_store_dir_path();
   given_path.push(relative_path);
-  if !given_path.is_dir() {
-    eprintln!("Bookmark not found: {}", toml_file_path.display());
+  let bookmark_paths =
+    common::find_toml_bookmark_files(Some(relative_path.clone()));
+  if !given_path.is_dir() && bookmark_paths.is_empty() {
+    eprintln!(
+      "Bookmark not found: {}",
+      common::open_store().borrow().location(relative_path)
+    );
     return;
   }
   let prompt_message = format!(
     "Bookmark not found as a file, but '{}' is a directory. \
-    Do you want to delete it and all its bookmarks? [Y/n] ",
+    Do you want to delete it and all its bookmarks? [y/N] ",
     relative_path
   );
   print!("{}", prompt_message);
@@ -48,12 +50,8 @@ pub fn run(args: Vec<String>) {
     .read_line(&mut input)
     .panic_on_error("Cannot read input delete dir");
   let input = input.trim().to_lowercase();
-  if input.is_empty() || input == "y" || input == "yes" {
-    fs::remove_dir_all(&given_path)
-      .panic_on_error("Failed to remove directory");
-    let comment =
-      format!("Removed directory {} and all bookmarks", relative_path);
-    common::git_commit(&comment);
+  if input == "y" || input == "yes" {
+    store::exit_on_error(Store::shared().remove_dir(relative_path, use_trash));
     println!("Directory and all bookmarks removed: {}", relative_path);
     return;
   }
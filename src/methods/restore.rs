@@ -0,0 +1,88 @@
+// ****************************************************************************
+// Restore a deleted or changed bookmark from the git history, either as it
+// was before its last change or as it was at a given revision or date
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::config;
+use std::fs;
+
+pub fn run(mut args: Vec<String>) {
+  let at = common::take_option(&mut args, "--at");
+  if args.len() < 3 {
+    eprintln!("Usage: tempesta restore <path> [--at <revision|date>]");
+    std::process::exit(1);
+  }
+  if !config::load_config().git {
+    eprintln!("Git is disabled: bookmarks can only be restored from git.");
+    std::process::exit(1);
+  }
+  common::require_directory_store("history per bookmark");
+  let relative_path = &args[2];
+  common::validate_bookmark_path(relative_path);
+  let file = format!("{}.toml", relative_path);
+  let commit = match &at {
+    Some(at) => resolve_revision(at),
+    // The version before the last commit touching the bookmark
+    None => common::git_output(&["rev-list", "-1", "HEAD", "--", &file])
+      .map(|output| output.trim().to_string())
+      .filter(|commit| !commit.is_empty())
+      .map(|commit| format!("{}^", commit)),
+  };
+  let Some(commit) = commit else {
+    eprintln!("No history found for {}", relative_path);
+    std::process::exit(1);
+  };
+  let Some(content) =
+    common::git_output(&["show", &format!("{}:{}", commit, file)])
+  else {
+    eprintln!(
+      "Bookmark {} does not exist at {}",
+      relative_path,
+      at.as_deref().unwrap_or("its previous version")
+    );
+    std::process::exit(1);
+  };
+  toml::from_str::<Bookmark>(&content)
+    .panic_on_error("Failed to parse TOML content");
+  let toml_file_path = common::get_bookmark_file_path(relative_path);
+  if fs::read_to_string(&toml_file_path).is_ok_and(|current| current == content)
+  {
+    println!("Bookmark {} is already at that version", relative_path);
+    return;
+  }
+  fs::write(&toml_file_path, &content)
+    .panic_on_error("Failed to write bookmark file");
+  let short = common::git_output(&["rev-parse", "--short", &commit])
+    .map(|output| output.trim().to_string())
+    .unwrap_or(commit);
+  let comment = format!("Restore bookmark {} from {}", relative_path, short);
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "restore",
+      path: relative_path,
+      ..Default::default()
+    },
+  );
+  println!("Bookmark {} restored from {}", relative_path, short);
+}
+
+/// Resolves a revision, or a date to the last commit made before it.
+fn resolve_revision(at: &str) -> Option<String> {
+  let output = match common::parse_timestamp(at) {
+    Some(seconds) => {
+      let before = format!("--before={}", common::format_timestamp(seconds));
+      common::git_output(&["rev-list", "-1", &before, "HEAD"])
+    }
+    None => common::git_output(&[
+      "rev-parse",
+      "--verify",
+      "--quiet",
+      &format!("{}^{{commit}}", at),
+    ]),
+  };
+  output
+    .map(|output| output.trim().to_string())
+    .filter(|commit| !commit.is_empty())
+}
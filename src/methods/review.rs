@@ -0,0 +1,239 @@
+// ****************************************************************************
+// Review stale bookmarks: the ones past `expires_at` or `review_after`, and
+// the ones that were not visited nor updated in the last N days. Every
+// bookmark can be kept, updated, archived or deleted. All the decisions of a
+// session end up in one commit.
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::config::{self, StoreBackend};
+use super::trash;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const DEFAULT_REVIEW_DAYS: i64 = 180;
+const DEFAULT_ARCHIVE_DIR: &str = "archive";
+
+#[derive(Default)]
+struct Summary {
+  kept: usize,
+  updated: usize,
+  archived: usize,
+  deleted: usize,
+}
+
+pub fn run(mut args: Vec<String>) {
+  let config = config::load_config();
+  let days = match common::take_option(&mut args, "--days") {
+    Some(value) => value.parse::<i64>().unwrap_or_else(|_| {
+      eprintln!("Invalid number of days: {}", value);
+      std::process::exit(1);
+    }),
+    None => config.review_days.unwrap_or(DEFAULT_REVIEW_DAYS),
+  };
+  let list_only = common::take_flag(&mut args, &["--list"]);
+  let archive_dir = config
+    .archive_dir
+    .unwrap_or_else(|| DEFAULT_ARCHIVE_DIR.to_string())
+    .trim_end_matches('/')
+    .to_string();
+  let sub_path = args.get(2).cloned();
+  let stale = find_stale_bookmarks(sub_path, days, &archive_dir);
+  if stale.is_empty() {
+    println!("Nothing to review.");
+    return;
+  }
+  if list_only {
+    for (relative_path, bookmark, reason) in &stale {
+      println!("{} :: {} ({})", relative_path, bookmark.url, reason);
+    }
+    return;
+  }
+  let mut summary = Summary::default();
+  for (relative_path, mut bookmark, reason) in stale {
+    println!("{} :: {} ({})", relative_path, bookmark.url, reason);
+    let Some(action) = prompt_action() else {
+      break;
+    };
+    match action {
+      'k' => {
+        bookmark.expires_at = None;
+        bookmark.review_after = Some(common::format_timestamp(
+          common::now_seconds() + days * 86400,
+        ));
+        common::store_bookmark(&relative_path, &bookmark);
+        summary.kept += 1;
+      }
+      'u' => {
+        let Some(url) = prompt_url() else {
+          break;
+        };
+        bookmark.url = url;
+        bookmark.expires_at = None;
+        bookmark.review_after = None;
+        bookmark.updated_at = Some(common::now_timestamp());
+        common::store_bookmark(&relative_path, &bookmark);
+        summary.updated += 1;
+      }
+      'a' => {
+        let archive_path = common::dedupe_bookmark_path(&format!(
+          "{}/{}",
+          archive_dir, relative_path
+        ));
+        common::rename_bookmark(&relative_path, &archive_path);
+        println!("Bookmark archived as {}", archive_path);
+        summary.archived += 1;
+      }
+      'd' => {
+        if trash::is_enabled() {
+          trash::trash_bookmark(&relative_path);
+        } else {
+          common::remove_bookmark(&relative_path);
+        }
+        println!("Bookmark removed");
+        summary.deleted += 1;
+      }
+      _ => {}
+    }
+  }
+  let changes =
+    summary.kept + summary.updated + summary.archived + summary.deleted;
+  if changes == 0 {
+    println!("No changes made.");
+    return;
+  }
+  let comment = format!(
+    "Review bookmarks: {} kept, {} updated, {} archived, {} deleted",
+    summary.kept, summary.updated, summary.archived, summary.deleted
+  );
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "review",
+      ..Default::default()
+    },
+  );
+  println!(
+    "Review completed: {} kept, {} updated, {} archived, {} deleted",
+    summary.kept, summary.updated, summary.archived, summary.deleted
+  );
+}
+
+/// Returns the bookmarks to review with the reason they are listed for.
+/// Bookmarks in the archive directory are never listed.
+fn find_stale_bookmarks(
+  sub_path: Option<String>,
+  days: i64,
+  archive_dir: &str,
+) -> Vec<(String, Bookmark, String)> {
+  let now = common::now_seconds();
+  let visits = common::load_visits();
+  // The commits only date single bookmarks when each has its own file
+  let git_times = match config::load_config().store_backend.unwrap_or_default()
+  {
+    StoreBackend::Directory => get_git_change_times(),
+    _ => HashMap::new(),
+  };
+  let archive_prefix = format!("{}/", archive_dir);
+  let mut stale = Vec::new();
+  for (relative_path, bookmark) in common::load_bookmarks(sub_path) {
+    if relative_path.starts_with(&archive_prefix) {
+      continue;
+    }
+    let timestamp = |value: &Option<String>| {
+      value.as_deref().and_then(common::parse_timestamp)
+    };
+    let reason = if timestamp(&bookmark.expires_at).is_some_and(|t| t <= now) {
+      Some("expired".to_string())
+    } else if timestamp(&bookmark.review_after).is_some_and(|t| t <= now) {
+      Some("review due".to_string())
+    } else if bookmark.review_after.is_some() {
+      None
+    } else {
+      let last_activity = [
+        timestamp(&bookmark.updated_at),
+        timestamp(&bookmark.created_at),
+        visits
+          .get(&relative_path)
+          .and_then(|v| common::parse_timestamp(v)),
+        git_times.get(&format!("{}.toml", relative_path)).copied(),
+      ]
+      .into_iter()
+      .flatten()
+      .max();
+      last_activity
+        .filter(|t| now - t > days * 86400)
+        .map(|_| format!("not visited nor updated in {} days", days))
+    };
+    if let Some(reason) = reason {
+      stale.push((relative_path, bookmark, reason));
+    }
+  }
+  stale
+}
+
+/// Time of the last commit touching each file of the store.
+fn get_git_change_times() -> HashMap<String, i64> {
+  let mut times = HashMap::new();
+  let Some(log) = common::git_output(&["log", "--format=@%ct", "--name-only"])
+  else {
+    return times;
+  };
+  let mut current_time = 0;
+  for line in log.lines() {
+    if let Some(time) = line.strip_prefix('@') {
+      current_time = time.parse().unwrap_or(0);
+    } else if !line.is_empty() {
+      times.entry(line.to_string()).or_insert(current_time);
+    }
+  }
+  times
+}
+
+/// Returns `None` when the user quits the review.
+fn prompt_action() -> Option<char> {
+  loop {
+    print!("[k]eep, [u]pdate, [a]rchive, [d]elete, [s]kip, [q]uit? ");
+    io::stdout()
+      .flush()
+      .panic_on_error("Failed to flush stdout");
+    let mut input = String::new();
+    let read = io::stdin()
+      .read_line(&mut input)
+      .panic_on_error("Failed to read input");
+    if read == 0 {
+      return None;
+    }
+    match input.trim().to_lowercase().as_str() {
+      "k" | "keep" => return Some('k'),
+      "u" | "update" => return Some('u'),
+      "a" | "archive" => return Some('a'),
+      "d" | "delete" => return Some('d'),
+      "" | "s" | "skip" => return Some('s'),
+      "q" | "quit" => return None,
+      _ => println!("Invalid input."),
+    }
+  }
+}
+
+/// Asks again until the URL is valid. Returns `None` at the end of the input.
+fn prompt_url() -> Option<String> {
+  loop {
+    print!("New URL: ");
+    io::stdout()
+      .flush()
+      .panic_on_error("Failed to flush stdout");
+    let mut input = String::new();
+    let read = io::stdin()
+      .read_line(&mut input)
+      .panic_on_error("Failed to read input");
+    if read == 0 {
+      return None;
+    }
+    let url = input.trim();
+    match common::check_url(url) {
+      Ok(()) => return Some(url.to_string()),
+      Err(e) => eprintln!("{}", e),
+    }
+  }
+}
@@ -0,0 +1,714 @@
+// ****************************************************************************
+// Serve the store on localhost: an HTML start page to browse and search the
+// bookmarks, a JSON API to read and change them, committed like the CLI does,
+// and `/go/<path>` go-links redirecting to the bookmarked URLs. With
+// `--go-links` every other path is a go-link, to serve `http://go/<path>`.
+// Requests are handled one at a time, so the changes are committed in order.
+// Only the pages of localhost can use the API: requests naming another host or
+// coming from another origin are refused, and the changes need a JSON body,
+// which pages of other origins cannot send without asking first.
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::config;
+use crate::{Error, Store};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 7878;
+
+/// Larger bodies are refused before being read.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Longer request lines and header lines, and more headers, are refused.
+const MAX_LINE_SIZE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+/// The hosts the server answers as, with any port.
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+struct Request {
+  method: String,
+  /// The decoded path, without the query.
+  path: String,
+  query: Vec<(String, String)>,
+  host: Option<String>,
+  origin: Option<String>,
+  content_type: Option<String>,
+  body: Vec<u8>,
+}
+
+struct Response {
+  status: u16,
+  content_type: &'static str,
+  location: Option<String>,
+  body: String,
+}
+
+#[derive(Serialize)]
+struct Entry {
+  path: String,
+  #[serde(flatten)]
+  bookmark: Bookmark,
+}
+
+#[derive(Deserialize)]
+struct NewBookmark {
+  path: String,
+  url: String,
+  #[serde(default)]
+  tags: Vec<String>,
+  keyword: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BookmarkChanges {
+  url: Option<String>,
+  tags: Option<Vec<String>>,
+  /// An empty keyword removes it.
+  keyword: Option<String>,
+}
+
+pub fn run(mut args: Vec<String>) {
+  let port = common::take_option(&mut args, "--port")
+    .map(|value| {
+      value.parse::<u16>().unwrap_or_else(|_| {
+        eprintln!("Invalid port: {}", value);
+        std::process::exit(1);
+      })
+    })
+    .unwrap_or(DEFAULT_PORT);
+  let go_links = common::take_flag(&mut args, &["--go-links"]);
+  // Fail now rather than on every request
+  if let Err(e) = open_store() {
+    eprintln!("{}", e);
+    std::process::exit(1);
+  }
+  let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+    eprintln!("Cannot listen on port {}: {}", port, e);
+    std::process::exit(1);
+  });
+  println!("Serving the bookmarks on http://localhost:{}/", port);
+  for stream in listener.incoming().flatten() {
+    handle_connection(stream, go_links);
+  }
+}
+
+fn handle_connection(mut stream: TcpStream, go_links: bool) {
+  let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+  let request = match read_request(&mut stream) {
+    Ok(request) => request,
+    Err(response) => {
+      let _ = write_response(&mut stream, response);
+      return;
+    }
+  };
+  if !is_local(&request, go_links) {
+    let _ = write_response(&mut stream, error(403, "Forbidden host or origin"));
+    return;
+  }
+  // A bug in a handler must not take the server down
+  let response =
+    panic::catch_unwind(AssertUnwindSafe(|| handle(&request, go_links)))
+      .unwrap_or_else(|_| error(500, "Internal error"));
+  let _ = write_response(&mut stream, response);
+}
+
+/// The request, or the error response when it is malformed or too large.
+fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+  let malformed = || error(400, "Malformed request");
+  let mut reader = BufReader::new(stream);
+  let request_line =
+    read_line(&mut reader, || error(414, "Request line too long"))?;
+  let mut parts = request_line.split_whitespace();
+  let method = parts.next().ok_or_else(malformed)?.to_string();
+  let target = parts.next().ok_or_else(malformed)?;
+  let mut content_length = 0;
+  let (mut host, mut origin, mut content_type) = (None, None, None);
+  for headers in 0.. {
+    let too_large = || error(431, "Request headers too large");
+    if headers == MAX_HEADERS {
+      return Err(too_large());
+    }
+    let line = read_line(&mut reader, too_large)?;
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      let value = value.trim();
+      match name.to_ascii_lowercase().as_str() {
+        "content-length" => {
+          content_length = value.parse().map_err(|_| malformed())?
+        }
+        "host" => host = Some(value.to_string()),
+        "origin" => origin = Some(value.to_string()),
+        "content-type" => content_type = Some(value.to_string()),
+        _ => {}
+      }
+    }
+  }
+  if content_length > MAX_BODY_SIZE {
+    return Err(error(413, "Request body too large"));
+  }
+  let mut body = vec![0; content_length];
+  reader.read_exact(&mut body).map_err(|_| malformed())?;
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+  Ok(Request {
+    method,
+    path: percent_decode(path),
+    query: url::form_urlencoded::parse(query.as_bytes())
+      .into_owned()
+      .collect(),
+    host,
+    origin,
+    content_type,
+    body,
+  })
+}
+
+/// Reads a line of the request head, refused with the `too_long` response
+/// past `MAX_LINE_SIZE`.
+fn read_line(
+  reader: &mut impl BufRead,
+  too_long: impl Fn() -> Response,
+) -> Result<String, Response> {
+  let mut line = Vec::new();
+  let read = reader
+    .take(MAX_LINE_SIZE as u64)
+    .read_until(b'\n', &mut line)
+    .map_err(|_| error(400, "Malformed request"))?;
+  if read == MAX_LINE_SIZE && !line.ends_with(b"\n") {
+    return Err(too_long());
+  }
+  Ok(String::from_utf8_lossy(&line).to_string())
+}
+
+/// Whether the request names this server as its host, and comes from one of
+/// its pages when sent by a page. This keeps other sites out, even with a
+/// domain resolving to `127.0.0.1`. `go` is a host too with `--go-links`.
+fn is_local(request: &Request, go_links: bool) -> bool {
+  let is_local_host = |host: &str| {
+    let name = match host.rsplit_once(':') {
+      Some((name, port))
+        if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) =>
+      {
+        name
+      }
+      _ => host,
+    };
+    let name = name.to_ascii_lowercase();
+    LOCAL_HOSTS.contains(&name.as_str()) || (go_links && name == "go")
+  };
+  let Some(host) = &request.host else {
+    return false;
+  };
+  if !is_local_host(host) {
+    return false;
+  }
+  match &request.origin {
+    Some(origin) => origin
+      .strip_prefix("http://")
+      .or_else(|| origin.strip_prefix("https://"))
+      .is_some_and(is_local_host),
+    None => true,
+  }
+}
+
+/// Whether the body is declared as JSON, as required to change bookmarks.
+fn is_json(request: &Request) -> bool {
+  request.content_type.as_deref().is_some_and(|content_type| {
+    let media_type = content_type.split(';').next().unwrap_or_default();
+    media_type.trim().eq_ignore_ascii_case("application/json")
+  })
+}
+
+fn write_response(
+  stream: &mut TcpStream,
+  response: Response,
+) -> std::io::Result<()> {
+  let mut head = format!(
+    "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+    response.status,
+    reason(response.status),
+    response.content_type,
+    response.body.len()
+  );
+  if let Some(location) = &response.location {
+    head.push_str(&format!("Location: {}\r\n", location));
+  }
+  head.push_str("Connection: close\r\n\r\n");
+  stream.write_all(head.as_bytes())?;
+  stream.write_all(response.body.as_bytes())?;
+  stream.flush()
+}
+
+fn handle(request: &Request, go_links: bool) -> Response {
+  let mut store = match open_store() {
+    Ok(store) => store,
+    Err(e) => return error_response(&e),
+  };
+  let method = request.method.as_str();
+  let path = request.path.as_str();
+  if let Some(link) = path.strip_prefix("/go/") {
+    return match method {
+      "GET" => go(&store, link, "/go/"),
+      _ => error(405, "Method not allowed"),
+    };
+  }
+  if let Some(relative_path) = path.strip_prefix("/bookmarks/") {
+    return match method {
+      "GET" => store.get(relative_path).map_or_else(
+        |e| error_response(&e),
+        |bookmark| json(200, &entry(relative_path, bookmark)),
+      ),
+      "PATCH" if !is_json(request) => json_required(),
+      "PATCH" => update(&mut store, relative_path, &request.body),
+      "DELETE" => store.remove(relative_path).map_or_else(
+        |e| error_response(&e),
+        |bookmark| json(200, &entry(relative_path, bookmark)),
+      ),
+      _ => error(405, "Method not allowed"),
+    };
+  }
+  match (method, path) {
+    ("GET", "/") => page(&store, query_value(request, "q")),
+    ("GET", "/bookmarks") => list(&store, request),
+    ("POST", "/bookmarks") if !is_json(request) => json_required(),
+    ("POST", "/bookmarks") => add(&mut store, &request.body),
+    (_, "/" | "/bookmarks") => error(405, "Method not allowed"),
+    ("GET", _) if go_links => go(&store, &path[1..], "/"),
+    _ => error(404, "Not found"),
+  }
+}
+
+/// Opened for every request, to see the changes made with the CLI.
+fn open_store() -> crate::Result<Store> {
+  Store::from_config_file(config::get_config_file_path())
+}
+
+/// Resolves a go-link: the longest bookmark path starting the link, then the
+/// keyword of a bookmark as its first segment. The segments left are filled
+/// in the URL. Otherwise a page suggests the bookmarks looking alike.
+fn go(store: &Store, link: &str, prefix: &str) -> Response {
+  let segments: Vec<&str> = link
+    .split('/')
+    .filter(|segment| !segment.is_empty())
+    .collect();
+  if segments.is_empty() {
+    return page(store, None);
+  }
+  for end in (1..=segments.len()).rev() {
+    if let Ok(bookmark) = store.get(&segments[..end].join("/")) {
+      return redirect(store, &expand_go_link(&bookmark.url, &segments[end..]));
+    }
+  }
+  let bookmarks = match store.list(None) {
+    Ok(bookmarks) => bookmarks,
+    Err(e) => return error_response(&e),
+  };
+  let keyword = segments[0].to_lowercase();
+  let found = bookmarks.iter().find(|(_, bookmark)| {
+    bookmark
+      .keyword
+      .as_ref()
+      .is_some_and(|k| k.to_lowercase() == keyword)
+  });
+  if let Some((_, bookmark)) = found {
+    return redirect(store, &expand_go_link(&bookmark.url, &segments[1..]));
+  }
+  not_found_page(store, &bookmarks, &segments, prefix)
+}
+
+/// Fills `{1}`, `{2}`, ... with the segments following the bookmark in the
+/// go-link and `{*}` with all of them. Without placeholders the segments are
+/// appended to the path of the URL.
+fn expand_go_link(url: &str, segments: &[&str]) -> String {
+  let segments: Vec<String> = segments
+    .iter()
+    .map(|segment| percent_encode(segment))
+    .collect();
+  let placeholder = Regex::new(r"\{(\d+|\*)\}").panic_on_error("Invalid regex");
+  if placeholder.is_match(url) {
+    return placeholder
+      .replace_all(url, |captures: &regex::Captures| match &captures[1] {
+        "*" => segments.join("/"),
+        index => index
+          .parse::<usize>()
+          .ok()
+          .and_then(|index| segments.get(index.checked_sub(1)?))
+          .cloned()
+          .unwrap_or_default(),
+      })
+      .to_string();
+  }
+  if segments.is_empty() {
+    return url.to_string();
+  }
+  let end = url.find(['?', '#']).unwrap_or(url.len());
+  format!(
+    "{}/{}{}",
+    url[..end].trim_end_matches('/'),
+    segments.join("/"),
+    &url[end..]
+  )
+}
+
+/// The fallback of an unknown go-link: the bookmarks matching its words, then
+/// the ones whose path or keyword has its letters in the same order.
+fn not_found_page(
+  store: &Store,
+  bookmarks: &[(String, Bookmark)],
+  segments: &[&str],
+  prefix: &str,
+) -> Response {
+  let link = segments.join("/");
+  let mut candidates: Vec<&String> = match store.search(&segments.join(" ")) {
+    Ok(found) => bookmarks
+      .iter()
+      .filter(|(path, _)| found.iter().any(|(other, _)| other == path))
+      .map(|(path, _)| path)
+      .collect(),
+    Err(e) => return error_response(&e),
+  };
+  let mut alike: Vec<&String> = bookmarks
+    .iter()
+    .filter(|(path, bookmark)| {
+      is_subsequence(&link, path)
+        || bookmark
+          .keyword
+          .as_ref()
+          .is_some_and(|keyword| is_subsequence(&link, keyword))
+    })
+    .map(|(path, _)| path)
+    .filter(|path| !candidates.contains(path))
+    .collect();
+  alike.sort_by_key(|path| path.len());
+  candidates.extend(alike);
+  let mut body = format!(
+    "<p>No go-link for <code>{}</code>.</p>\n",
+    escape_html(&link)
+  );
+  if candidates.is_empty() {
+    body.push_str("<p>No bookmarks found.</p>\n");
+  } else {
+    body.push_str("<p>Did you mean:</p>\n<ul>\n");
+    for path in candidates {
+      body.push_str(&format!(
+        "<li><a href=\"{}{}\">{}</a></li>\n",
+        prefix,
+        escape_html(path),
+        escape_html(path)
+      ));
+    }
+    body.push_str("</ul>\n");
+  }
+  html_page(404, &segments.join(" "), &body)
+}
+
+/// Whether the letters of `pattern` appear in `text` in the same order,
+/// ignoring the case.
+fn is_subsequence(pattern: &str, text: &str) -> bool {
+  let mut text = text.chars().flat_map(char::to_lowercase);
+  pattern
+    .chars()
+    .flat_map(char::to_lowercase)
+    .all(|c| text.any(|t| t == c))
+}
+
+/// `GET /bookmarks?tag=<tag>&q=<query>`: the bookmarks with every given tag
+/// and matching the query.
+fn list(store: &Store, request: &Request) -> Response {
+  let found = match query_value(request, "q") {
+    Some(query) => store.search(query),
+    None => store.list(None),
+  };
+  let tags: Vec<&String> = request
+    .query
+    .iter()
+    .filter(|(name, _)| name == "tag")
+    .map(|(_, value)| value)
+    .collect();
+  match found {
+    Ok(found) => {
+      let entries: Vec<Entry> = found
+        .into_iter()
+        .filter(|(_, bookmark)| tags.iter().all(|t| bookmark.tags.contains(t)))
+        .map(|(path, bookmark)| Entry { path, bookmark })
+        .collect();
+      json(200, &entries)
+    }
+    Err(e) => error_response(&e),
+  }
+}
+
+fn add(store: &mut Store, body: &[u8]) -> Response {
+  let new: NewBookmark = match serde_json::from_slice(body) {
+    Ok(new) => new,
+    Err(e) => return error(400, &format!("Invalid bookmark: {}", e)),
+  };
+  let url = common::clean_url_with(&new.url, store.config());
+  let added =
+    store.add_with_keyword(&new.path, &url, &new.tags, new.keyword.as_deref());
+  match added {
+    Ok(bookmark) => json(201, &entry(&new.path, bookmark)),
+    Err(e) => error_response(&e),
+  }
+}
+
+fn update(store: &mut Store, relative_path: &str, body: &[u8]) -> Response {
+  let changes: BookmarkChanges = match serde_json::from_slice(body) {
+    Ok(changes) => changes,
+    Err(e) => return error(400, &format!("Invalid changes: {}", e)),
+  };
+  // `Some(None)` removes the keyword
+  let keyword: Option<Option<&str>> = changes
+    .keyword
+    .as_deref()
+    .map(|keyword| Some(keyword).filter(|keyword| !keyword.is_empty()));
+  let current = match store.get(relative_path) {
+    Ok(current) => current,
+    Err(e) => return error_response(&e),
+  };
+  let url = changes
+    .url
+    .map(|url| common::clean_url_with(&url, store.config()))
+    .unwrap_or(current.url);
+  let tags = changes.tags.unwrap_or(current.tags);
+  match store.update_with_keyword(relative_path, &url, &tags, keyword) {
+    Ok(bookmark) => json(200, &entry(relative_path, bookmark)),
+    Err(e) => error_response(&e),
+  }
+}
+
+/// The start page: the tree of the store, or the bookmarks matching the
+/// search.
+fn page(store: &Store, query: Option<&str>) -> Response {
+  let found = match query.filter(|query| !query.trim().is_empty()) {
+    Some(query) => store.search(query),
+    None => store.list(None),
+  };
+  let bookmarks = match found {
+    Ok(bookmarks) => bookmarks,
+    Err(e) => return error_response(&e),
+  };
+  let mut tree = Tree::default();
+  for (path, bookmark) in &bookmarks {
+    let mut node = &mut tree;
+    let segments: Vec<&str> = path.split('/').collect();
+    for segment in &segments[..segments.len() - 1] {
+      node = node.dirs.entry(segment.to_string()).or_default();
+    }
+    node.bookmarks.push((path, bookmark));
+  }
+  let mut body = String::new();
+  if bookmarks.is_empty() {
+    body.push_str("<p>No bookmarks found.</p>\n");
+  } else {
+    render_tree(store, &tree, &mut body);
+  }
+  html_page(200, query.unwrap_or_default(), &body)
+}
+
+/// The page around the body, with the search box filled with the query.
+fn html_page(status: u16, query: &str, body: &str) -> Response {
+  let html = format!(
+    concat!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+      "<title>Tempesta</title>\n<style>\n",
+      "body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}\n",
+      "ul {{ list-style: none; padding-left: 1.2em; }}\n",
+      "summary {{ cursor: pointer; font-weight: bold; }}\n",
+      ".tag {{ color: #777; font-size: 0.85em; margin-left: 0.5em; }}\n",
+      "</style>\n</head>\n<body>\n<h1>Bookmarks</h1>\n",
+      "<form method=\"get\" action=\"/\">\n",
+      "<input type=\"search\" name=\"q\" value=\"{}\" autofocus ",
+      "placeholder=\"Search\">\n</form>\n{}</body>\n</html>\n"
+    ),
+    escape_html(query),
+    body
+  );
+  Response {
+    status,
+    content_type: "text/html; charset=utf-8",
+    location: None,
+    body: html,
+  }
+}
+
+#[derive(Default)]
+struct Tree<'a> {
+  dirs: BTreeMap<String, Tree<'a>>,
+  bookmarks: Vec<(&'a String, &'a Bookmark)>,
+}
+
+fn render_tree(store: &Store, tree: &Tree, html: &mut String) {
+  html.push_str("<ul>\n");
+  for (name, dir) in &tree.dirs {
+    html.push_str(&format!(
+      "<li><details open><summary>{}</summary>\n",
+      escape_html(name)
+    ));
+    render_tree(store, dir, html);
+    html.push_str("</details></li>\n");
+  }
+  for (path, bookmark) in &tree.bookmarks {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = escape_html(bookmark.title.as_deref().unwrap_or(name));
+    // URLs that cannot be linked are shown, not followed
+    if is_safe_url(store, &bookmark.url) {
+      html.push_str(&format!(
+        "<li><a href=\"{}\" title=\"{}\">{}</a>",
+        escape_html(&bookmark.url),
+        escape_html(&bookmark.url),
+        name
+      ));
+    } else {
+      html.push_str(&format!(
+        "<li><span title=\"{}\">{}</span>",
+        escape_html(&bookmark.url),
+        name
+      ));
+    }
+    for tag in &bookmark.tags {
+      html
+        .push_str(&format!("<span class=\"tag\">#{}</span>", escape_html(tag)));
+    }
+    html.push_str("</li>\n");
+  }
+  html.push_str("</ul>\n");
+}
+
+fn entry(path: &str, bookmark: Bookmark) -> Entry {
+  Entry {
+    path: path.to_string(),
+    bookmark,
+  }
+}
+
+fn query_value<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+  request
+    .query
+    .iter()
+    .find(|(key, _)| key == name)
+    .map(|(_, value)| value.as_str())
+}
+
+fn json<T: Serialize>(status: u16, value: &T) -> Response {
+  Response {
+    status,
+    content_type: "application/json",
+    location: None,
+    body: serde_json::to_string_pretty(value)
+      .panic_on_error("Failed to serialize JSON"),
+  }
+}
+
+fn redirect(store: &Store, url: &str) -> Response {
+  if !is_safe_url(store, url) {
+    return error(400, "The bookmarked URL is not allowed");
+  }
+  Response {
+    status: 302,
+    content_type: "text/plain; charset=utf-8",
+    location: Some(url.to_string()),
+    body: format!("Redirecting to {}\n", url),
+  }
+}
+
+/// Whether a bookmarked URL can be linked or redirected to: allowed by the
+/// configuration and without control characters, which would end the
+/// `Location` header.
+fn is_safe_url(store: &Store, url: &str) -> bool {
+  !url.chars().any(char::is_control)
+    && common::check_url_with(url, store.config().allowed_schemes.as_deref())
+      .is_ok()
+}
+
+fn error(status: u16, message: &str) -> Response {
+  json(status, &serde_json::json!({ "error": message }))
+}
+
+fn json_required() -> Response {
+  error(415, "The body must be sent as application/json")
+}
+
+fn error_response(e: &Error) -> Response {
+  let status = match e {
+    Error::InvalidPath(_) | Error::InvalidUrl(_) => 400,
+    Error::NotFound(_) => 404,
+    Error::AlreadyExists(_) | Error::KeywordTaken { .. } => 409,
+    Error::Config(_) | Error::Storage(_) | Error::Git(_) => 500,
+  };
+  error(status, &e.to_string())
+}
+
+fn reason(status: u16) -> &'static str {
+  match status {
+    200 => "OK",
+    201 => "Created",
+    302 => "Found",
+    400 => "Bad Request",
+    403 => "Forbidden",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    409 => "Conflict",
+    413 => "Payload Too Large",
+    414 => "URI Too Long",
+    415 => "Unsupported Media Type",
+    431 => "Request Header Fields Too Large",
+    _ => "Internal Server Error",
+  }
+}
+
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// Escapes everything but the unreserved characters of URLs, to put a segment
+/// of a go-link in the target URL.
+fn percent_encode(segment: &str) -> String {
+  segment
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        (byte as char).to_string()
+      }
+      _ => format!("%{:02X}", byte),
+    })
+    .collect()
+}
+
+/// Decodes the `%XX` escapes of a URL path, such as the non ASCII letters of
+/// the bookmark paths.
+fn percent_decode(path: &str) -> String {
+  let bytes = path.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = bytes
+      .get(i + 1..i + 3)
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match (bytes[i], hex) {
+      (b'%', Some(byte)) => {
+        decoded.push(byte);
+        i += 3;
+      }
+      (byte, _) => {
+        decoded.push(byte);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&decoded).to_string()
+}
@@ -0,0 +1,132 @@
+// ****************************************************************************
+// Show the health of the store and its state compared to the remote: the
+// bookmark count, the branch, the commits ahead of and behind the remote,
+// the uncommitted changes and the files that are not valid bookmarks.
+// ****************************************************************************
+
+use super::common;
+use super::config;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Status {
+  store: String,
+  bookmarks: usize,
+  git: bool,
+  push_mode: Option<String>,
+  branch: Option<String>,
+  upstream: Option<String>,
+  ahead: Option<usize>,
+  behind: Option<usize>,
+  uncommitted: Vec<Change>,
+  errors: Vec<ParseError>,
+}
+
+#[derive(Serialize)]
+struct Change {
+  status: String,
+  path: String,
+}
+
+#[derive(Serialize)]
+struct ParseError {
+  path: String,
+  error: String,
+}
+
+pub fn run(mut args: Vec<String>) {
+  let json = common::take_flag(&mut args, &["--json"]);
+  let fetch = common::take_flag(&mut args, &["--fetch"]);
+  let config = config::load_config();
+  if fetch
+    && config.git
+    && common::git_output(&["remote", "get-url", "origin"]).is_some()
+    && common::git_output(&["fetch", "origin"]).is_none()
+  {
+    eprintln!("Cannot fetch from remote origin");
+  }
+  let status = get_status(&config);
+  if json {
+    common::print_json(&status);
+  } else {
+    print_status(&status);
+  }
+}
+
+fn get_status(config: &config::Config) -> Status {
+  let mut bookmarks = 0;
+  let mut errors = Vec::new();
+  let loaded = common::open_store().borrow().load_all(None);
+  for (path, bookmark) in loaded {
+    match bookmark {
+      Ok(_) => bookmarks += 1,
+      Err(error) => errors.push(ParseError { path, error }),
+    }
+  }
+  let branch = common::git_output(&["symbolic-ref", "--short", "HEAD"])
+    .map(|branch| branch.trim().to_string());
+  let ahead_behind = common::count_ahead_behind();
+  let uncommitted = common::git_output(&["status", "--porcelain"])
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| {
+      let (status, path) = line.split_at_checked(3)?;
+      Some(Change {
+        status: status.trim().to_string(),
+        path: path.to_string(),
+      })
+    })
+    .collect();
+  Status {
+    store: config.dir.clone(),
+    bookmarks,
+    git: config.git,
+    push_mode: config
+      .git
+      .then(|| config.push_mode.unwrap_or_default().to_string()),
+    branch,
+    upstream: ahead_behind.and_then(|_| common::get_upstream()),
+    ahead: ahead_behind.map(|(ahead, _)| ahead),
+    behind: ahead_behind.map(|(_, behind)| behind),
+    uncommitted,
+    errors,
+  }
+}
+
+fn print_status(status: &Status) {
+  println!("Bookmark store directory: {}", status.store);
+  println!("Bookmarks:                {}", status.bookmarks);
+  println!(
+    "Git:                      {}",
+    if status.git { "enabled" } else { "disabled" }
+  );
+  if let Some(branch) = &status.branch {
+    println!("Branch:                   {}", branch);
+  }
+  if let Some(push_mode) = &status.push_mode {
+    println!("Push mode:                {}", push_mode);
+  }
+  if status.git {
+    match (status.ahead, status.behind) {
+      (Some(ahead), Some(behind)) => println!(
+        "Remote:                   {} ({} ahead, {} behind)",
+        status.upstream.as_deref().unwrap_or("never pushed"),
+        ahead,
+        behind
+      ),
+      _ => println!("Remote:                   None"),
+    }
+  }
+  if !status.uncommitted.is_empty() {
+    println!("Uncommitted changes:");
+    for change in &status.uncommitted {
+      println!("  {} {}", change.status, change.path);
+    }
+  }
+  if !status.errors.is_empty() {
+    println!("Invalid bookmark files:");
+    for error in &status.errors {
+      println!("  {}: {}", error.path, error.error);
+    }
+  }
+}
@@ -0,0 +1,466 @@
+// ****************************************************************************
+// Storage of the bookmarks, selected with `store_backend`. The directory store
+// keeps one TOML file per bookmark in a tree mirroring the paths. The single
+// file stores keep every bookmark in `bookmarks.toml` or `bookmarks.json`, and
+// the SQLite store in `bookmarks.sqlite`, at the root of the store directory.
+// ****************************************************************************
+
+use super::common::Bookmark;
+#[cfg(feature = "sqlite")]
+use super::common::PanicOnError;
+use super::config::StoreBackend;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub trait BookmarkStore {
+  /// Paths of the bookmarks under the directory, or of every bookmark,
+  /// sorted. Paths with a segment starting with a dot are local state and
+  /// skipped.
+  fn list(&self, sub_path: Option<&str>) -> Vec<String>;
+
+  /// The bookmark, or why it cannot be read.
+  fn load(&self, path: &str) -> Result<Bookmark, String>;
+
+  fn save(&mut self, path: &str, bookmark: &Bookmark) -> Result<(), String>;
+
+  fn remove(&mut self, path: &str) -> Result<(), String>;
+
+  /// Where the bookmark is stored, for the messages.
+  fn location(&self, path: &str) -> String;
+
+  fn contains(&self, path: &str) -> bool {
+    self.list(None).iter().any(|listed| listed == path)
+  }
+
+  /// Every bookmark under the directory with its path.
+  fn load_all(
+    &self,
+    sub_path: Option<&str>,
+  ) -> Vec<(String, Result<Bookmark, String>)> {
+    self
+      .list(sub_path)
+      .into_iter()
+      .map(|path| {
+        let bookmark = self.load(&path);
+        (path, bookmark)
+      })
+      .collect()
+  }
+
+  fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+    let bookmark = self.load(from)?;
+    self.save(to, &bookmark)?;
+    self.remove(from)
+  }
+
+  /// The file of the bookmark, when each bookmark has its own.
+  fn file_path(&self, _path: &str) -> Option<PathBuf> {
+    None
+  }
+
+  /// Removes every bookmark and the files holding them.
+  fn clear(&mut self) -> Result<(), String>;
+}
+
+/// Opens the store of the backend in the directory.
+pub fn open(
+  backend: StoreBackend,
+  dir: &Path,
+) -> Result<Box<dyn BookmarkStore>, String> {
+  match backend {
+    StoreBackend::Directory => Ok(Box::new(DirectoryStore::new(dir))),
+    StoreBackend::Toml | StoreBackend::Json => {
+      let (file_name, format) =
+        single_file(backend).expect("Not a single file backend");
+      FileStore::open(dir.join(file_name), format)
+        .map(|store| Box::new(store) as Box<dyn BookmarkStore>)
+    }
+    #[cfg(feature = "sqlite")]
+    StoreBackend::Sqlite => SqliteStore::open(&dir.join(SQLITE_FILE))
+      .map(|store| Box::new(store) as Box<dyn BookmarkStore>),
+    #[cfg(not(feature = "sqlite"))]
+    StoreBackend::Sqlite => {
+      Err("tempesta was built without the sqlite feature".to_string())
+    }
+  }
+}
+
+const SQLITE_FILE: &str = "bookmarks.sqlite";
+
+/// The git pathspec matching the files that hold the bookmarks.
+pub fn pathspec(backend: StoreBackend) -> &'static str {
+  match backend {
+    StoreBackend::Directory => "*.toml",
+    StoreBackend::Sqlite => SQLITE_FILE,
+    StoreBackend::Toml | StoreBackend::Json => {
+      single_file(backend).expect("Not a single file backend").0
+    }
+  }
+}
+
+/// The file holding every bookmark of the single file backends.
+pub fn single_file(
+  backend: StoreBackend,
+) -> Option<(&'static str, FileFormat)> {
+  match backend {
+    StoreBackend::Toml => Some(("bookmarks.toml", FileFormat::Toml)),
+    StoreBackend::Json => Some(("bookmarks.json", FileFormat::Json)),
+    StoreBackend::Directory | StoreBackend::Sqlite => None,
+  }
+}
+
+fn is_hidden(path: &str) -> bool {
+  path.split('/').any(|segment| segment.starts_with('.'))
+}
+
+fn is_under(path: &str, sub_path: Option<&str>) -> bool {
+  match sub_path {
+    Some(sub_path) => path
+      .strip_prefix(sub_path.trim_end_matches('/'))
+      .is_some_and(|rest| rest.starts_with('/')),
+    None => true,
+  }
+}
+
+// ****************************************************************************
+// One TOML file per bookmark
+// ****************************************************************************
+
+pub struct DirectoryStore {
+  root: PathBuf,
+}
+
+impl DirectoryStore {
+  pub fn new(root: &Path) -> Self {
+    DirectoryStore {
+      root: root.to_path_buf(),
+    }
+  }
+
+  fn path_of(&self, path: &str) -> PathBuf {
+    self.root.join(format!("{}.toml", path))
+  }
+
+  /// Removes the directories left empty up to the root.
+  fn cleanup_empty_parents(&self, file_path: &Path) {
+    let mut dir = file_path.parent();
+    while let Some(current) = dir {
+      if current == self.root || fs::remove_dir(current).is_err() {
+        break;
+      }
+      dir = current.parent();
+    }
+  }
+}
+
+impl BookmarkStore for DirectoryStore {
+  fn list(&self, sub_path: Option<&str>) -> Vec<String> {
+    fn visit_dir(dir: &Path, root: &Path, bookmarks: &mut Vec<String>) {
+      let Ok(entries) = fs::read_dir(dir) else {
+        return;
+      };
+      for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+          continue; // skip .git and tempesta local state
+        } else if path.is_dir() {
+          visit_dir(&path, root, bookmarks);
+        } else if path.is_file()
+          && path.extension().is_some_and(|ext| ext == "toml")
+        {
+          if let Some(relative_str) =
+            path.strip_prefix(root).ok().and_then(|p| p.to_str())
+          {
+            bookmarks.push(relative_str.trim_end_matches(".toml").to_string());
+          }
+        }
+      }
+    }
+    let mut bookmarks = Vec::new();
+    let search_dir = match sub_path {
+      Some(sub_path) => self.root.join(sub_path),
+      None => self.root.clone(),
+    };
+    visit_dir(&search_dir, &self.root, &mut bookmarks);
+    bookmarks.sort();
+    bookmarks
+  }
+
+  fn load(&self, path: &str) -> Result<Bookmark, String> {
+    let content = fs::read_to_string(self.path_of(path))
+      .map_err(|e| format!("Failed to read TOML: {}", e))?;
+    toml::from_str(&content).map_err(|e| e.message().to_string())
+  }
+
+  fn save(&mut self, path: &str, bookmark: &Bookmark) -> Result<(), String> {
+    let file_path = self.path_of(path);
+    if let Some(parent) = file_path.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let content = toml::to_string(bookmark).map_err(|e| e.to_string())?;
+    fs::write(file_path, content).map_err(|e| e.to_string())
+  }
+
+  fn remove(&mut self, path: &str) -> Result<(), String> {
+    let file_path = self.path_of(path);
+    fs::remove_file(&file_path).map_err(|e| e.to_string())?;
+    self.cleanup_empty_parents(&file_path);
+    Ok(())
+  }
+
+  fn location(&self, path: &str) -> String {
+    self.path_of(path).display().to_string()
+  }
+
+  fn contains(&self, path: &str) -> bool {
+    self.path_of(path).is_file()
+  }
+
+  /// Moves the file as it is, even when it is not a valid bookmark.
+  fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+    let from_file_path = self.path_of(from);
+    let to_file_path = self.path_of(to);
+    if let Some(parent) = to_file_path.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&from_file_path, &to_file_path).map_err(|e| e.to_string())?;
+    self.cleanup_empty_parents(&from_file_path);
+    Ok(())
+  }
+
+  fn file_path(&self, path: &str) -> Option<PathBuf> {
+    Some(self.path_of(path))
+  }
+
+  fn clear(&mut self) -> Result<(), String> {
+    for path in self.list(None) {
+      self.remove(&path)?;
+    }
+    Ok(())
+  }
+}
+
+// ****************************************************************************
+// Every bookmark in a single TOML or JSON file, keyed by path
+// ****************************************************************************
+
+#[derive(Clone, Copy)]
+pub enum FileFormat {
+  Toml,
+  Json,
+}
+
+impl FileFormat {
+  pub fn parse(
+    self,
+    content: &str,
+  ) -> Result<BTreeMap<String, Bookmark>, String> {
+    match self {
+      FileFormat::Toml => {
+        toml::from_str(content).map_err(|e| e.message().to_string())
+      }
+      FileFormat::Json => {
+        serde_json::from_str(content).map_err(|e| e.to_string())
+      }
+    }
+  }
+
+  pub fn serialize(
+    self,
+    bookmarks: &BTreeMap<String, Bookmark>,
+  ) -> Result<String, String> {
+    match self {
+      FileFormat::Toml => toml::to_string(bookmarks).map_err(|e| e.to_string()),
+      FileFormat::Json => {
+        serde_json::to_string_pretty(bookmarks).map_err(|e| e.to_string())
+      }
+    }
+  }
+}
+
+struct FileStore {
+  file_path: PathBuf,
+  format: FileFormat,
+  bookmarks: BTreeMap<String, Bookmark>,
+}
+
+impl FileStore {
+  fn open(file_path: PathBuf, format: FileFormat) -> Result<Self, String> {
+    let bookmarks = match fs::read_to_string(&file_path) {
+      Ok(content) => format
+        .parse(&content)
+        .map_err(|e| format!("{}: {}", file_path.display(), e))?,
+      Err(_) => BTreeMap::new(),
+    };
+    Ok(FileStore {
+      file_path,
+      format,
+      bookmarks,
+    })
+  }
+
+  fn write(&self) -> Result<(), String> {
+    let content = self.format.serialize(&self.bookmarks)?;
+    fs::write(&self.file_path, content).map_err(|e| e.to_string())
+  }
+}
+
+impl BookmarkStore for FileStore {
+  fn list(&self, sub_path: Option<&str>) -> Vec<String> {
+    self
+      .bookmarks
+      .keys()
+      .filter(|path| !is_hidden(path) && is_under(path, sub_path))
+      .cloned()
+      .collect()
+  }
+
+  fn load(&self, path: &str) -> Result<Bookmark, String> {
+    self
+      .bookmarks
+      .get(path)
+      .cloned()
+      .ok_or_else(|| format!("Bookmark not found: {}", path))
+  }
+
+  fn save(&mut self, path: &str, bookmark: &Bookmark) -> Result<(), String> {
+    self.bookmarks.insert(path.to_string(), bookmark.clone());
+    self.write()
+  }
+
+  fn remove(&mut self, path: &str) -> Result<(), String> {
+    if self.bookmarks.remove(path).is_none() {
+      return Err(format!("Bookmark not found: {}", path));
+    }
+    self.write()
+  }
+
+  fn location(&self, path: &str) -> String {
+    format!("{} ({})", self.file_path.display(), path)
+  }
+
+  fn contains(&self, path: &str) -> bool {
+    self.bookmarks.contains_key(path)
+  }
+
+  fn clear(&mut self) -> Result<(), String> {
+    self.bookmarks.clear();
+    match fs::remove_file(&self.file_path) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+      _ => Ok(()),
+    }
+  }
+}
+
+// ****************************************************************************
+// Every bookmark in an SQLite database, for very large collections
+// ****************************************************************************
+
+#[cfg(feature = "sqlite")]
+struct SqliteStore {
+  file_path: PathBuf,
+  connection: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+  fn open(file_path: &Path) -> Result<Self, String> {
+    let connection =
+      rusqlite::Connection::open(file_path).map_err(|e| e.to_string())?;
+    // The bookmark is kept as TOML so that new fields need no migration
+    connection
+      .execute(
+        "CREATE TABLE IF NOT EXISTS bookmarks (
+          path TEXT PRIMARY KEY,
+          url TEXT NOT NULL,
+          bookmark TEXT NOT NULL
+        )",
+        (),
+      )
+      .map_err(|e| e.to_string())?;
+    Ok(SqliteStore {
+      file_path: file_path.to_path_buf(),
+      connection,
+    })
+  }
+
+  fn query_paths(&self, sql: &str, params: &[&str]) -> Vec<String> {
+    let mut statement = self
+      .connection
+      .prepare(sql)
+      .panic_on_error("Invalid SQLite query");
+    statement
+      .query_map(rusqlite::params_from_iter(params), |row| row.get(0))
+      .map(|rows| rows.filter_map(|row| row.ok()).collect())
+      .unwrap_or_default()
+  }
+}
+
+#[cfg(feature = "sqlite")]
+impl BookmarkStore for SqliteStore {
+  fn list(&self, sub_path: Option<&str>) -> Vec<String> {
+    let paths = match sub_path {
+      Some(sub_path) => self.query_paths(
+        "SELECT path FROM bookmarks WHERE substr(path, 1, length(?1)) = ?1
+          ORDER BY path",
+        &[&format!("{}/", sub_path.trim_end_matches('/'))],
+      ),
+      None => self.query_paths("SELECT path FROM bookmarks ORDER BY path", &[]),
+    };
+    paths.into_iter().filter(|path| !is_hidden(path)).collect()
+  }
+
+  fn load(&self, path: &str) -> Result<Bookmark, String> {
+    let content: String = self
+      .connection
+      .query_row(
+        "SELECT bookmark FROM bookmarks WHERE path = ?1",
+        [path],
+        |row| row.get(0),
+      )
+      .map_err(|_| format!("Bookmark not found: {}", path))?;
+    toml::from_str(&content).map_err(|e| e.message().to_string())
+  }
+
+  fn save(&mut self, path: &str, bookmark: &Bookmark) -> Result<(), String> {
+    let content = toml::to_string(bookmark).map_err(|e| e.to_string())?;
+    self
+      .connection
+      .execute(
+        "INSERT OR REPLACE INTO bookmarks (path, url, bookmark)
+          VALUES (?1, ?2, ?3)",
+        [path, &bookmark.url, &content],
+      )
+      .map(|_| ())
+      .map_err(|e| e.to_string())
+  }
+
+  fn remove(&mut self, path: &str) -> Result<(), String> {
+    match self
+      .connection
+      .execute("DELETE FROM bookmarks WHERE path = ?1", [path])
+    {
+      Ok(0) => Err(format!("Bookmark not found: {}", path)),
+      Ok(_) => Ok(()),
+      Err(e) => Err(e.to_string()),
+    }
+  }
+
+  fn location(&self, path: &str) -> String {
+    format!("{} ({})", self.file_path.display(), path)
+  }
+
+  fn contains(&self, path: &str) -> bool {
+    !self
+      .query_paths("SELECT path FROM bookmarks WHERE path = ?1", &[path])
+      .is_empty()
+  }
+
+  fn clear(&mut self) -> Result<(), String> {
+    match fs::remove_file(&self.file_path) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+      _ => Ok(()),
+    }
+  }
+}
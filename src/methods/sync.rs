@@ -0,0 +1,387 @@
+// ****************************************************************************
+// Synchronize the store with the remote: fetch, merge or rebase (following
+// `rebase_on_pull`) and push, then report the bookmarks added, changed or
+// removed by the remote. Conflicting bookmarks are merged field by field, the
+// single file stores bookmark by bookmark, and the user is asked when both
+// sides changed the URL. When a conflict cannot be resolved the sync is
+// aborted and the store is left as it was. An SQLite database cannot be
+// merged, those stores are not synced.
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::config::{self, StoreBackend};
+use super::log;
+use super::store::{self, FileFormat};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+
+pub fn run() {
+  let config = config::load_config();
+  if !config.git {
+    eprintln!("Git is disabled: there is nothing to sync.");
+    std::process::exit(1);
+  }
+  if config.store_backend == Some(StoreBackend::Sqlite) {
+    eprintln!(
+      "The sqlite store backend cannot be synced: git cannot merge the \
+      database. Migrate to another backend to sync."
+    );
+    std::process::exit(1);
+  }
+  if common::git_output(&["remote", "get-url", "origin"]).is_none() {
+    eprintln!(
+      "No remote configured: add one with `git remote add origin <url>`"
+    );
+    std::process::exit(1);
+  }
+  let old_head = rev_parse("HEAD");
+  println!("Fetching changes from remote origin...");
+  if !common::git_fetch() {
+    eprintln!("Cannot fetch from remote origin");
+    std::process::exit(1);
+  }
+  if let Some(upstream) = common::get_upstream() {
+    if common::git_fast_forward(&upstream) {
+      // Nothing to merge
+    } else if config.rebase_on_pull == Some(true) && old_head.is_some() {
+      // An unborn branch can only be merged into
+      rebase(&upstream);
+    } else {
+      merge(&upstream);
+    }
+  }
+  println!("Pushing changes to remote origin...");
+  if !common::git_push() {
+    eprintln!("Cannot push to remote origin");
+    std::process::exit(1);
+  }
+  report(old_head);
+}
+
+fn merge(upstream: &str) {
+  // Stores initialized on two machines each start with their own commit
+  if common::git_output(&[
+    "merge",
+    "--no-edit",
+    "--allow-unrelated-histories",
+    upstream,
+  ])
+  .is_some()
+  {
+    return;
+  }
+  if !resolve_conflicts(true)
+    || common::git_output(&["-c", "core.editor=true", "commit", "--no-edit"])
+      .is_none()
+  {
+    common::git_output(&["merge", "--abort"]);
+    abort();
+  }
+}
+
+fn rebase(upstream: &str) {
+  let mut done = common::git_output(&["rebase", upstream]).is_some();
+  while !done {
+    // While rebasing, "ours" is the remote and "theirs" the local commit
+    if !resolve_conflicts(false) {
+      common::git_output(&["rebase", "--abort"]);
+      abort();
+    }
+    done =
+      common::git_output(&["-c", "core.editor=true", "rebase", "--continue"])
+        .is_some();
+  }
+}
+
+fn abort() -> ! {
+  eprintln!("Sync aborted: the store was left as it was before the sync.");
+  std::process::exit(1);
+}
+
+/// Resolves every conflicted file and stages the result. Returns `false`
+/// when a conflict cannot be resolved or the user aborts.
+fn resolve_conflicts(local_is_ours: bool) -> bool {
+  let Some(output) =
+    common::git_output(&["diff", "--name-only", "--diff-filter=U"])
+  else {
+    return false;
+  };
+  let files: Vec<&str> = output.lines().collect();
+  if files.is_empty() {
+    eprintln!("Cannot merge the remote changes");
+    return false;
+  }
+  let single_file =
+    store::single_file(config::load_config().store_backend.unwrap_or_default());
+  for file in files {
+    if let Some((_, format)) = single_file.filter(|(name, _)| *name == file) {
+      if !resolve_store_file(file, format, local_is_ours) {
+        return false;
+      }
+      continue;
+    }
+    let Some(relative_path) = log::bookmark_path(file) else {
+      eprintln!("Cannot merge {}: it is not a bookmark", file);
+      return false;
+    };
+    let stage = |number: u8| -> Result<Option<Bookmark>, ()> {
+      let Some(content) =
+        common::git_output(&["show", &format!(":{}:{}", number, file)])
+      else {
+        return Ok(None);
+      };
+      toml::from_str(&content).map(Some).map_err(|_| {
+        eprintln!("Cannot merge {}: it is not a valid bookmark", file);
+      })
+    };
+    let (Ok(base), Ok(ours), Ok(theirs)) = (stage(1), stage(2), stage(3))
+    else {
+      return false;
+    };
+    let Ok(resolved) = resolve_bookmark(
+      &relative_path,
+      base.as_ref(),
+      ours,
+      theirs,
+      local_is_ours,
+    ) else {
+      return false;
+    };
+    match resolved {
+      Some(bookmark) => {
+        let content = toml::to_string(&bookmark)
+          .panic_on_error("Failed to serialize bookmark");
+        fs::write(common::get_bookmark_store_dir_path().join(file), content)
+          .panic_on_error("Failed to write bookmark file");
+        common::git_output(&["add", file]);
+      }
+      None => {
+        common::git_output(&["rm", "--quiet", file]);
+      }
+    }
+    println!("Merged {}", relative_path);
+  }
+  true
+}
+
+/// Merges the conflicting versions of a bookmark, asking the user when both
+/// sides changed its URL or one side removed it. `Err` when the user aborts.
+fn resolve_bookmark(
+  relative_path: &str,
+  base: Option<&Bookmark>,
+  ours: Option<Bookmark>,
+  theirs: Option<Bookmark>,
+  local_is_ours: bool,
+) -> Result<Option<Bookmark>, ()> {
+  let (local, remote) = if local_is_ours {
+    (ours, theirs)
+  } else {
+    (theirs, ours)
+  };
+  match (local, remote) {
+    (Some(mut local), Some(mut remote)) => {
+      match common::merge_bookmarks(base, &local, &remote) {
+        Ok(merged) => Ok(Some(merged)),
+        Err((local_url, remote_url)) => {
+          let url =
+            prompt_url(relative_path, &local_url, &remote_url).ok_or(())?;
+          remote.url = url.clone();
+          local.url = url;
+          Ok(common::merge_bookmarks(base, &local, &remote).ok())
+        }
+      }
+    }
+    (Some(kept), None) | (None, Some(kept)) => {
+      match prompt_keep(relative_path).ok_or(())? {
+        true => Ok(Some(kept)),
+        false => Ok(None),
+      }
+    }
+    (None, None) => Ok(None),
+  }
+}
+
+/// Merges the conflicting versions of the file of a single file store
+/// bookmark by bookmark and stages the result.
+fn resolve_store_file(
+  file: &str,
+  format: FileFormat,
+  local_is_ours: bool,
+) -> bool {
+  let stage = |number: u8| -> Result<BTreeMap<String, Bookmark>, ()> {
+    let Some(content) =
+      common::git_output(&["show", &format!(":{}:{}", number, file)])
+    else {
+      return Ok(BTreeMap::new());
+    };
+    format.parse(&content).map_err(|e| {
+      eprintln!("Cannot merge {}: {}", file, e);
+    })
+  };
+  let (Ok(base), Ok(ours), Ok(theirs)) = (stage(1), stage(2), stage(3)) else {
+    return false;
+  };
+  let merged: Result<_, ()> = common::merge_bookmark_maps(
+    &base,
+    &ours,
+    &theirs,
+    |relative_path, base, ours, theirs| {
+      let resolved =
+        resolve_bookmark(relative_path, base, ours, theirs, local_is_ours)?;
+      println!("Merged {}", relative_path);
+      Ok(resolved)
+    },
+  );
+  let Ok(merged) = merged else {
+    return false;
+  };
+  let content = format
+    .serialize(&merged)
+    .panic_on_error("Failed to serialize bookmarks");
+  fs::write(common::get_bookmark_store_dir_path().join(file), content)
+    .panic_on_error("Failed to write bookmark file");
+  common::git_output(&["add", file]);
+  true
+}
+
+/// Returns the URL to keep, or `None` to abort.
+fn prompt_url(
+  relative_path: &str,
+  local_url: &str,
+  remote_url: &str,
+) -> Option<String> {
+  println!("Conflicting URLs for {}:", relative_path);
+  println!("  1) {} (local)", local_url);
+  println!("  2) {} (remote)", remote_url);
+  loop {
+    match prompt("Keep which one? [1/2, a to abort]: ")?.as_str() {
+      "1" => return Some(local_url.to_string()),
+      "2" => return Some(remote_url.to_string()),
+      "a" | "abort" => return None,
+      _ => println!("Invalid input."),
+    }
+  }
+}
+
+/// Asks whether to keep a bookmark removed on one side and changed on the
+/// other. Returns `None` to abort.
+fn prompt_keep(relative_path: &str) -> Option<bool> {
+  println!(
+    "{} was removed on one side and changed on the other.",
+    relative_path
+  );
+  loop {
+    match prompt("[k]eep, [d]elete or [a]bort? ")?.as_str() {
+      "k" | "keep" => return Some(true),
+      "d" | "delete" => return Some(false),
+      "a" | "abort" => return None,
+      _ => println!("Invalid input."),
+    }
+  }
+}
+
+/// Reads an answer, `None` at the end of the input.
+fn prompt(message: &str) -> Option<String> {
+  print!("{}", message);
+  io::stdout()
+    .flush()
+    .panic_on_error("Failed to flush stdout");
+  let mut input = String::new();
+  let read = io::stdin()
+    .read_line(&mut input)
+    .panic_on_error("Failed to read input");
+  if read == 0 {
+    return None;
+  }
+  Some(input.trim().to_lowercase())
+}
+
+fn rev_parse(revision: &str) -> Option<String> {
+  common::git_output(&["rev-parse", "--verify", "--quiet", revision])
+    .map(|output| output.trim().to_string())
+}
+
+/// The bookmark files changed since the previous head.
+fn diff_files(old_head: Option<&str>) -> Vec<log::Change> {
+  let output = match old_head {
+    Some(old_head) => {
+      common::git_output(&["diff", "--name-status", "-M", old_head, "HEAD"])
+    }
+    None => common::git_output(&["ls-tree", "-r", "--name-only", "HEAD"]).map(
+      |files| files.lines().map(|file| format!("A\t{}\n", file)).collect(),
+    ),
+  };
+  output
+    .unwrap_or_default()
+    .lines()
+    .filter_map(log::parse_change)
+    .collect()
+}
+
+/// The bookmarks of a single file store changed since the previous head.
+fn diff_store_file(
+  old_head: Option<&str>,
+  file: &str,
+  format: FileFormat,
+) -> Vec<log::Change> {
+  let load = |revision: Option<&str>| {
+    revision
+      .and_then(|revision| {
+        common::git_output(&["show", &format!("{}:{}", revision, file)])
+      })
+      .and_then(|content| format.parse(&content).ok())
+      .unwrap_or_default()
+  };
+  let (old, new) = (load(old_head), load(Some("HEAD")));
+  let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+  paths.sort();
+  paths.dedup();
+  paths
+    .into_iter()
+    .filter_map(|path| {
+      let change = match (old.get(path), new.get(path)) {
+        (None, Some(_)) => "added",
+        (Some(_), None) => "removed",
+        (Some(before), Some(after)) if before != after => "modified",
+        _ => return None,
+      };
+      Some(log::Change {
+        change,
+        path: path.clone(),
+        previous_path: None,
+      })
+    })
+    .collect()
+}
+
+/// Prints the bookmarks changed between the previous head and the new one.
+fn report(old_head: Option<String>) {
+  let new_head = rev_parse("HEAD");
+  if old_head == new_head {
+    println!("Already up to date.");
+    return;
+  }
+  let single_file =
+    store::single_file(config::load_config().store_backend.unwrap_or_default());
+  let changes = match single_file {
+    Some((file, format)) => diff_store_file(old_head.as_deref(), file, format),
+    None => diff_files(old_head.as_deref()),
+  };
+  for change in &changes {
+    match &change.previous_path {
+      Some(previous_path) => {
+        println!("  {} {} -> {}", change.change, previous_path, change.path)
+      }
+      None => println!("  {} {}", change.change, change.path),
+    }
+  }
+  let count = |kind: &str| changes.iter().filter(|c| c.change == kind).count();
+  println!(
+    "Synced: {} added, {} changed, {} removed, {} moved",
+    count("added"),
+    count("modified"),
+    count("removed"),
+    count("moved")
+  );
+}
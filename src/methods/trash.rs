@@ -0,0 +1,202 @@
+// ****************************************************************************
+// Trash: removed bookmarks are moved into the `.trash` directory of the store
+// with their deletion time, hidden from the other commands, until they are
+// restored or the trash is emptied. The trash is local and never committed.
+// ****************************************************************************
+
+use super::common::{self, Bookmark, PanicOnError};
+use super::config;
+use super::store::{BookmarkStore, DirectoryStore};
+use crate::store::{self, Error, Store};
+use std::fs;
+use std::path::Path;
+
+const TRASH_DIR: &str = ".trash";
+
+pub fn run(args: Vec<String>) {
+  if args.len() < 3 {
+    print_usage();
+  }
+  let sub_args = args[2..].to_vec();
+  match args[2].as_str() {
+    "list" | "l" | "ls" => list(),
+    "restore" => restore(sub_args),
+    "empty" => empty(sub_args),
+    _ => print_usage(),
+  }
+}
+
+fn print_usage() -> ! {
+  eprintln!("Usage: tempesta trash list");
+  eprintln!("       tempesta trash restore <path>");
+  eprintln!("       tempesta trash empty [--older-than <duration>]");
+  std::process::exit(1);
+}
+
+/// Whether removed bookmarks go to the trash rather than being deleted.
+pub fn is_enabled() -> bool {
+  config::load_config().trash.unwrap_or(true)
+}
+
+/// Moves the bookmark from the store into the trash, recording the deletion
+/// time. Files that are not valid bookmarks are moved as they are.
+pub fn trash_bookmark(relative_path: &str) {
+  move_to_trash(
+    &common::get_bookmark_store_dir_path(),
+    common::open_store().borrow_mut().as_mut(),
+    relative_path,
+  )
+  .panic_on_error("Failed to move bookmark to the trash");
+}
+
+/// Moves the bookmark from the store in `store_dir` into its trash, like
+/// `trash_bookmark`.
+pub fn move_to_trash(
+  store_dir: &Path,
+  store: &mut dyn BookmarkStore,
+  relative_path: &str,
+) -> Result<(), String> {
+  match (store.load(relative_path), store.file_path(relative_path)) {
+    (Ok(bookmark), _) => {
+      save_to_trash(store_dir, relative_path, bookmark)?;
+      store.remove(relative_path)
+    }
+    (Err(_), Some(file_path)) => {
+      let trash_store = open_trash(store_dir);
+      let trash_path = common::dedupe_path_in(&trash_store, relative_path);
+      ensure_gitignore(store_dir).map_err(|e| e.to_string())?;
+      let trash_file_path = trash_store
+        .file_path(&trash_path)
+        .ok_or("The trash is a directory")?;
+      if let Some(parent) = trash_file_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+      }
+      fs::rename(&file_path, trash_file_path).map_err(|e| e.to_string())?;
+      if let Some(parent) = file_path.parent() {
+        common::cleanup_empty_parents(parent).map_err(|e| e.to_string())?;
+      }
+      Ok(())
+    }
+    (Err(e), None) => Err(e),
+  }
+}
+
+/// Saves the bookmark into the trash of the store directory with its deletion
+/// time and returns its path there, deduped when already taken.
+pub fn save_to_trash(
+  store_dir: &Path,
+  relative_path: &str,
+  mut bookmark: Bookmark,
+) -> Result<String, String> {
+  let mut trash_store = open_trash(store_dir);
+  let trash_path = common::dedupe_path_in(&trash_store, relative_path);
+  ensure_gitignore(store_dir).map_err(|e| e.to_string())?;
+  bookmark.deleted_at = Some(common::now_timestamp());
+  trash_store.save(&trash_path, &bookmark)?;
+  Ok(trash_path)
+}
+
+fn list() {
+  let trashed = load_trash();
+  if trashed.is_empty() {
+    println!("The trash is empty.");
+    return;
+  }
+  for (relative_path, bookmark) in trashed {
+    println!(
+      "{} :: {} :: deleted {}",
+      relative_path,
+      bookmark.url,
+      bookmark
+        .deleted_at
+        .as_deref()
+        .unwrap_or("at an unknown time")
+    );
+  }
+}
+
+fn restore(args: Vec<String>) {
+  if args.len() < 2 {
+    print_usage();
+  }
+  let trashed_path = &args[1];
+  common::validate_bookmark_path(trashed_path);
+  match Store::shared().restore_from_trash(trashed_path) {
+    Err(Error::NotFound(_)) => {
+      eprintln!("Bookmark not found in the trash: {}", trashed_path);
+      std::process::exit(1);
+    }
+    Err(Error::AlreadyExists(_)) => {
+      eprintln!(
+        "A bookmark already exists at {}, move it before restoring",
+        trashed_path
+      );
+      std::process::exit(1);
+    }
+    restored => {
+      store::exit_on_error(restored);
+    }
+  }
+  println!("Bookmark restored successfully as {}", trashed_path);
+}
+
+fn empty(mut args: Vec<String>) {
+  let older_than =
+    common::take_option(&mut args, "--older-than").map(|value| {
+      common::parse_duration(&value).unwrap_or_else(|| {
+        eprintln!("Invalid duration: {}", value);
+        std::process::exit(1);
+      })
+    });
+  let now = common::now_seconds();
+  let mut trash_store = get_trash_store();
+  let mut removed = 0;
+  for (relative_path, bookmark) in load_trash() {
+    let deleted_at = bookmark
+      .deleted_at
+      .as_deref()
+      .and_then(common::parse_timestamp)
+      .unwrap_or(0);
+    if older_than.is_some_and(|age| now - deleted_at < age.as_secs() as i64) {
+      continue;
+    }
+    trash_store
+      .remove(&relative_path)
+      .panic_on_error("Failed to remove file");
+    removed += 1;
+  }
+  println!("Removed {} bookmarks from the trash", removed);
+}
+
+/// Bookmarks in the trash keyed by their original path, oldest deletion
+/// first.
+fn load_trash() -> Vec<(String, Bookmark)> {
+  let mut trashed: Vec<(String, Bookmark)> = get_trash_store()
+    .load_all(None)
+    .into_iter()
+    .filter_map(|(path, bookmark)| Some((path, bookmark.ok()?)))
+    .collect();
+  trashed.sort_by(|(_, a), (_, b)| a.deleted_at.cmp(&b.deleted_at));
+  trashed
+}
+
+fn get_trash_store() -> DirectoryStore {
+  open_trash(&common::get_bookmark_store_dir_path())
+}
+
+/// The trash of the store in `store_dir`. It keeps one file per bookmark
+/// whatever the store backend.
+pub fn open_trash(store_dir: &Path) -> DirectoryStore {
+  DirectoryStore::new(&store_dir.join(TRASH_DIR))
+}
+
+/// The trash is local to this machine, keep it out of git.
+fn ensure_gitignore(store_dir: &Path) -> std::io::Result<()> {
+  let trash_dir_path = store_dir.join(TRASH_DIR);
+  let gitignore_path = trash_dir_path.join(".gitignore");
+  if !gitignore_path.exists() {
+    fs::create_dir_all(&trash_dir_path)?;
+    fs::write(gitignore_path, "*\n")?;
+  }
+  Ok(())
+}
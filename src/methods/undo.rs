@@ -0,0 +1,71 @@
+// ****************************************************************************
+// Undo the last change made by tempesta. With git the last tempesta commit
+// that was not undone yet is reverted with a new commit, otherwise the local
+// journal is replayed backwards.
+// ****************************************************************************
+
+use super::common;
+use super::config;
+use super::journal;
+use std::collections::HashSet;
+
+pub fn run() {
+  if !config::load_config().git {
+    match journal::undo() {
+      Some(message) => println!("Undid \"{}\"", message),
+      None => println!("Nothing to undo."),
+    }
+    return;
+  }
+  let Some((commit, subject)) = find_last_undoable_commit() else {
+    println!("Nothing to undo.");
+    return;
+  };
+  if common::git_output(&["revert", "--no-commit", &commit]).is_none() {
+    common::git_output(&["revert", "--abort"]);
+    eprintln!(
+      "Cannot undo \"{}\": it conflicts with later changes to the store",
+      subject
+    );
+    std::process::exit(1);
+  }
+  let comment = format!("Undo \"{}\"\n\nReverts: {}", subject, commit);
+  common::git_commit(
+    &comment,
+    common::CommitInfo {
+      action: "undo",
+      ..Default::default()
+    },
+  );
+  println!("Undid \"{}\"", subject);
+}
+
+/// The newest commit created by tempesta that is neither an undo nor already
+/// undone, with its subject.
+fn find_last_undoable_commit() -> Option<(String, String)> {
+  let log = common::git_output(&["log", "--format=%x1e%H%x1f%s%x1f%b"])?;
+  let mut undone = HashSet::new();
+  for record in log.split('\x1e') {
+    let mut fields = record.splitn(3, '\x1f');
+    let (Some(commit), Some(subject), Some(body)) =
+      (fields.next(), fields.next(), fields.next())
+    else {
+      continue;
+    };
+    if !body
+      .lines()
+      .any(|line| line.trim() == common::COMMIT_TRAILER)
+    {
+      continue;
+    }
+    let reverted = body
+      .lines()
+      .find_map(|line| line.trim().strip_prefix("Reverts: "));
+    if let Some(reverted) = reverted {
+      undone.insert(reverted.to_string());
+    } else if !undone.contains(commit) {
+      return Some((commit.to_string(), subject.to_string()));
+    }
+  }
+  None
+}
@@ -3,25 +3,23 @@
 // ****************************************************************************
 
 use super::common;
+use crate::store::{self, Store};
 
-pub fn run(args: Vec<String>) {
+pub fn run(mut args: Vec<String>) {
+  let raw = common::take_flag(&mut args, &["--raw"]);
   if args.len() < 4 {
-    eprintln!("Usage: tempesta update <path> <url> [tags...]");
+    eprintln!("Usage: tempesta update <path> <url> [tags...] [--raw]");
     std::process::exit(1);
   }
   let relative_path = &args[2];
-  common::validate_path(relative_path);
-  let toml_file_path = common::get_bookmark_file_path(relative_path);
-  if !toml_file_path.exists() {
-    eprintln!("Path {:?} do not exists", &toml_file_path.to_str());
+  common::validate_bookmark_path(relative_path);
+  if !common::bookmark_exists(relative_path) {
+    eprintln!("Path {:?} do not exists", relative_path);
     std::process::exit(1);
   }
   let url = &args[3];
   common::validate_url(url);
-  let tags = &args[4..].to_vec();
-  common::store_bookmark(&toml_file_path, url, tags);
-  let comment = format!("Update bookmark {}", &relative_path);
-  common::git_commit(&comment);
-
+  let url = &common::clean_url_unless_raw(url, raw);
+  store::exit_on_error(Store::shared().update(relative_path, url, &args[4..]));
   println!("Bookmark updated successfully as {}", &relative_path);
 }
@@ -0,0 +1,156 @@
+// ****************************************************************************
+// Verify that every commit touching the bookmarks is signed by an allowed key.
+// The keys are listed in the `allowed_signers` file of the configuration: SSH
+// keys in the git allowed signers format (`<principal> <key-type> <key>`) and
+// GPG keys by fingerprint, one per line. The file must be outside the store,
+// otherwise whoever can push could allow their own key.
+// ****************************************************************************
+
+use super::common::{self, PanicOnError};
+use super::config;
+use super::store;
+use std::fs;
+
+pub fn run(args: Vec<String>) {
+  let config = config::load_config();
+  if !config.git {
+    eprintln!("Git is disabled: there are no commits to verify.");
+    std::process::exit(1);
+  }
+  let Some(allowed_signers_path) = config.allowed_signers.as_deref() else {
+    eprintln!(
+      "No allowed signers: set allowed_signers to a file listing the keys"
+    );
+    std::process::exit(1);
+  };
+  let allowed_signers_path = common::expand_tilde(allowed_signers_path);
+  let store_dir = common::get_bookmark_store_dir_path();
+  let inside_store = |path: &std::path::Path| match (
+    path.canonicalize(),
+    store_dir.canonicalize(),
+  ) {
+    (Ok(path), Ok(store_dir)) => path.starts_with(store_dir),
+    _ => path.starts_with(&store_dir),
+  };
+  if inside_store(&allowed_signers_path) {
+    eprintln!(
+      "The allowed signers must be outside the store: {}",
+      allowed_signers_path.display()
+    );
+    std::process::exit(1);
+  }
+  let Ok(allowed_signers) = fs::read_to_string(&allowed_signers_path) else {
+    eprintln!(
+      "Cannot read the allowed signers: {}",
+      allowed_signers_path.display()
+    );
+    std::process::exit(1);
+  };
+  let (ssh_signers, gpg_fingerprints) = parse_allowed_signers(&allowed_signers);
+  // git checks the SSH signatures against an allowed signers file, give it
+  // only the SSH keys
+  let ssh_signers_path =
+    common::get_local_state_dir_path().join("allowed_ssh_signers");
+  fs::write(&ssh_signers_path, ssh_signers.join("\n"))
+    .panic_on_error("Failed to write the allowed SSH signers");
+  let allowed_signers_config =
+    format!("gpg.ssh.allowedSignersFile={}", ssh_signers_path.display());
+  let mut log_args = vec![
+    "-c",
+    &allowed_signers_config,
+    "log",
+    "--format=%H%x1f%G?%x1f%GF%x1f%GP%x1f%s",
+  ];
+  if let Some(range) = args.get(2) {
+    log_args.push(range);
+  }
+  let pathspec = store::pathspec(config.store_backend.unwrap_or_default());
+  log_args.extend(["--", pathspec]);
+  let Some(output) = common::git_output(&log_args) else {
+    eprintln!("Cannot read the history of the store");
+    std::process::exit(1);
+  };
+  let mut count_args = vec!["rev-list", "--count"];
+  count_args.push(args.get(2).map(String::as_str).unwrap_or("HEAD"));
+  let commits: usize = common::git_output(&count_args)
+    .and_then(|count| count.trim().parse().ok())
+    .unwrap_or(0);
+  let mut verified = 0;
+  let mut rejected = 0;
+  for line in output.lines() {
+    let fields: Vec<&str> = line.split('\x1f').collect();
+    let [commit, status, fingerprint, primary_fingerprint, subject] =
+      fields[..]
+    else {
+      continue;
+    };
+    let is_ssh = fingerprint.starts_with("SHA256:");
+    let is_allowed = |fingerprint: &str| {
+      let fingerprint = fingerprint.to_uppercase();
+      !fingerprint.is_empty() && gpg_fingerprints.contains(&fingerprint)
+    };
+    let error = match status {
+      "N" => Some("not signed".to_string()),
+      "B" => Some("bad signature".to_string()),
+      "E" => Some("the signature cannot be checked".to_string()),
+      "X" | "Y" => Some("signed with an expired key".to_string()),
+      "R" => Some("signed with a revoked key".to_string()),
+      // An SSH key is only good when it is in the allowed signers file
+      "G" if is_ssh => None,
+      "G" | "U"
+        if !is_ssh
+          && (is_allowed(fingerprint) || is_allowed(primary_fingerprint)) =>
+      {
+        None
+      }
+      _ => Some(format!(
+        "signed by a key that is not allowed ({})",
+        fingerprint
+      )),
+    };
+    match error {
+      Some(error) => {
+        println!("{} {}: {}", &commit[..commit.len().min(7)], subject, error);
+        rejected += 1;
+      }
+      None => verified += 1,
+    }
+  }
+  if rejected > 0 {
+    eprintln!(
+      "{} of {} commits are not signed by an allowed key",
+      rejected,
+      verified + rejected
+    );
+    std::process::exit(1);
+  }
+  // Commits that all miss the bookmarks mean a wrong pathspec, not a clean
+  // history
+  if verified == 0 && commits > 0 {
+    eprintln!(
+      "None of the {} commits changes {}: nothing was verified",
+      commits, pathspec
+    );
+    std::process::exit(1);
+  }
+  println!("Verified {} commits", verified);
+}
+
+/// Splits the allowed signers into SSH entries and GPG fingerprints, dropping
+/// the comments and the blank lines.
+fn parse_allowed_signers(content: &str) -> (Vec<String>, Vec<String>) {
+  let mut ssh_signers = Vec::new();
+  let mut gpg_fingerprints = Vec::new();
+  for line in content.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if line.contains(char::is_whitespace) {
+      ssh_signers.push(line.to_string());
+    } else {
+      gpg_fingerprints.push(line.to_uppercase());
+    }
+  }
+  (ssh_signers, gpg_fingerprints)
+}
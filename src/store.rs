@@ -0,0 +1,517 @@
+// ****************************************************************************
+// The `Store` of the library: typed operations on the bookmarks of a store.
+// Changes are committed and pushed following the configuration of the store,
+// like the CLI does, and every failure is returned as an `Error`.
+// ****************************************************************************
+
+use crate::methods::common::{self, Bookmark, CommitInfo, SharedStore};
+use crate::methods::config::{self, Config};
+use crate::methods::journal;
+use crate::methods::store::{self as backend, BookmarkStore};
+use crate::methods::trash;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub enum Error {
+  /// The bookmark path has spaces or special characters.
+  InvalidPath(String),
+  /// The URL is malformed or its scheme is not allowed.
+  InvalidUrl(String),
+  NotFound(String),
+  AlreadyExists(String),
+  /// Another bookmark already has the go-link keyword.
+  KeywordTaken {
+    keyword: String,
+    path: String,
+  },
+  /// The configuration file cannot be read.
+  Config(String),
+  /// The bookmarks cannot be read or written.
+  Storage(String),
+  /// Committing, pushing or journaling the change failed. The change itself
+  /// is saved.
+  Git(String),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::InvalidPath(e) | Error::InvalidUrl(e) => f.write_str(e),
+      Error::NotFound(path) => write!(f, "Bookmark not found: {}", path),
+      Error::AlreadyExists(path) => {
+        write!(f, "A bookmark already exists at {}", path)
+      }
+      Error::KeywordTaken { keyword, path } => {
+        write!(f, "The keyword {} is already used by {}", keyword, path)
+      }
+      Error::Config(e) => write!(f, "Cannot read the configuration: {}", e),
+      Error::Storage(e) => write!(f, "Cannot access the bookmarks: {}", e),
+      Error::Git(e) => write!(f, "Git failed: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The bookmarks of a store. Without git the changes are recorded in the
+/// journal used by `tempesta undo`, like the changes of the CLI.
+pub struct Store {
+  dir: PathBuf,
+  config: Config,
+  bookmarks: SharedStore,
+  /// Prints where the bookmarks are stored and when the changes are pushed,
+  /// like the commands of the CLI.
+  verbose: bool,
+}
+
+impl Store {
+  /// Opens the store in the directory, with one file per bookmark and
+  /// without git.
+  pub fn open(dir: impl AsRef<Path>) -> Result<Store> {
+    Store::with_config(Config {
+      dir: dir.as_ref().display().to_string(),
+      ..Default::default()
+    })
+  }
+
+  /// Opens the store described by a `tempesta.toml` configuration file.
+  pub fn from_config_file(path: impl AsRef<Path>) -> Result<Store> {
+    let content =
+      fs::read_to_string(path).map_err(|e| Error::Config(e.to_string()))?;
+    let config = toml::from_str(&content)
+      .map_err(|e| Error::Config(e.message().to_string()))?;
+    Store::with_config(config)
+  }
+
+  pub fn with_config(config: Config) -> Result<Store> {
+    let dir = common::expand_tilde(&config.dir);
+    fs::create_dir_all(&dir).map_err(|e| Error::Storage(e.to_string()))?;
+    let bookmarks =
+      backend::open(config.store_backend.unwrap_or_default(), &dir)
+        .map_err(Error::Storage)?;
+    Ok(Store {
+      dir,
+      config,
+      bookmarks: Rc::new(RefCell::new(bookmarks)),
+      verbose: false,
+    })
+  }
+
+  /// The store of the CLI configuration, sharing the bookmarks opened by the
+  /// command.
+  pub(crate) fn shared() -> Store {
+    Store {
+      dir: common::get_bookmark_store_dir_path(),
+      config: config::load_config(),
+      bookmarks: common::open_store(),
+      verbose: true,
+    }
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  pub fn config(&self) -> &Config {
+    &self.config
+  }
+
+  /// Adds a bookmark. The URL is stored as given, without the cleaning
+  /// `tempesta add` applies.
+  pub fn add(
+    &mut self,
+    path: &str,
+    url: &str,
+    tags: &[String],
+  ) -> Result<Bookmark> {
+    self.add_with_keyword(path, url, tags, None)
+  }
+
+  /// Adds a bookmark with its go-link keyword, in a single commit.
+  pub fn add_with_keyword(
+    &mut self,
+    path: &str,
+    url: &str,
+    tags: &[String],
+    keyword: Option<&str>,
+  ) -> Result<Bookmark> {
+    check_path(path)?;
+    if self.bookmarks.borrow().contains(path) {
+      return Err(Error::AlreadyExists(path.to_string()));
+    }
+    self.put(
+      path,
+      Bookmark {
+        url: url.to_string(),
+        tags: tags.to_vec(),
+        keyword: keyword.map(str::to_string),
+        created_at: Some(common::now_timestamp()),
+        ..Default::default()
+      },
+    )
+  }
+
+  /// Adds a complete bookmark, replacing the one at the path.
+  pub(crate) fn put(
+    &mut self,
+    path: &str,
+    bookmark: Bookmark,
+  ) -> Result<Bookmark> {
+    check_path(path)?;
+    self.check_url(&bookmark.url)?;
+    if let Some(keyword) = &bookmark.keyword {
+      self.check_keyword(path, keyword)?;
+    }
+    self.save(path, &bookmark)?;
+    self.commit(
+      &format!("Add bookmark {}", path),
+      CommitInfo {
+        action: "add",
+        path,
+        url: &bookmark.url,
+      },
+    )?;
+    Ok(bookmark)
+  }
+
+  pub fn get(&self, path: &str) -> Result<Bookmark> {
+    check_path(path)?;
+    let bookmarks = self.bookmarks.borrow();
+    if !bookmarks.contains(path) {
+      return Err(Error::NotFound(path.to_string()));
+    }
+    bookmarks.load(path).map_err(Error::Storage)
+  }
+
+  /// Replaces the URL and the tags of a bookmark, keeping its other fields.
+  pub fn update(
+    &mut self,
+    path: &str,
+    url: &str,
+    tags: &[String],
+  ) -> Result<Bookmark> {
+    self.update_with_keyword(path, url, tags, None)
+  }
+
+  /// Same as `update`, also setting the keyword when given, or removing it
+  /// with `Some(None)`. Nothing is saved unless every field is valid, and the
+  /// change is a single commit.
+  pub fn update_with_keyword(
+    &mut self,
+    path: &str,
+    url: &str,
+    tags: &[String],
+    keyword: Option<Option<&str>>,
+  ) -> Result<Bookmark> {
+    let mut bookmark = self.get(path)?;
+    self.check_url(url)?;
+    if let Some(Some(keyword)) = keyword {
+      self.check_keyword(path, keyword)?;
+    }
+    bookmark.url = url.to_string();
+    bookmark.tags = tags.to_vec();
+    if let Some(keyword) = keyword {
+      bookmark.keyword = keyword.map(str::to_string);
+    }
+    bookmark.updated_at = Some(common::now_timestamp());
+    self.save(path, &bookmark)?;
+    self.commit(
+      &format!("Update bookmark {}", path),
+      CommitInfo {
+        action: "update",
+        path,
+        url,
+      },
+    )?;
+    Ok(bookmark)
+  }
+
+  /// Sets the go-link keyword of a bookmark, or removes it with `None`.
+  pub fn set_keyword(
+    &mut self,
+    path: &str,
+    keyword: Option<&str>,
+  ) -> Result<Bookmark> {
+    let mut bookmark = self.get(path)?;
+    if let Some(keyword) = keyword {
+      self.check_keyword(path, keyword)?;
+    }
+    bookmark.keyword = keyword.map(str::to_string);
+    bookmark.updated_at = Some(common::now_timestamp());
+    self.save(path, &bookmark)?;
+    self.commit(
+      &format!("Update bookmark {}", path),
+      CommitInfo {
+        action: "update",
+        path,
+        url: &bookmark.url,
+      },
+    )?;
+    Ok(bookmark)
+  }
+
+  /// Moves a bookmark to a new path, which must be free.
+  pub fn r#move(&mut self, from: &str, to: &str) -> Result<()> {
+    check_path(to)?;
+    if self.bookmarks.borrow().contains(to) {
+      return Err(Error::AlreadyExists(to.to_string()));
+    }
+    self.move_over(from, to)
+  }
+
+  /// Moves a bookmark to a new path, replacing the bookmark there.
+  pub(crate) fn move_over(&mut self, from: &str, to: &str) -> Result<()> {
+    check_path(from)?;
+    check_path(to)?;
+    if !self.bookmarks.borrow().contains(from) {
+      return Err(Error::NotFound(from.to_string()));
+    }
+    self.start_journal()?;
+    self
+      .bookmarks
+      .borrow_mut()
+      .rename(from, to)
+      .map_err(Error::Storage)?;
+    self.commit(
+      &format!("Move bookmark from {} to {}", from, to),
+      CommitInfo {
+        action: "move",
+        path: to,
+        ..Default::default()
+      },
+    )
+  }
+
+  /// Removes a bookmark and returns it. It goes to the trash unless `trash`
+  /// is disabled in the configuration.
+  pub fn remove(&mut self, path: &str) -> Result<Bookmark> {
+    let bookmark = self.get(path)?;
+    self.remove_bookmark(path, self.config.trash.unwrap_or(true))?;
+    Ok(bookmark)
+  }
+
+  /// Removes a bookmark, into the trash or for good. Files that are not
+  /// valid bookmarks are removed too.
+  pub(crate) fn remove_bookmark(
+    &mut self,
+    path: &str,
+    use_trash: bool,
+  ) -> Result<()> {
+    check_path(path)?;
+    if !self.bookmarks.borrow().contains(path) {
+      return Err(Error::NotFound(path.to_string()));
+    }
+    let url = self
+      .bookmarks
+      .borrow()
+      .load(path)
+      .map(|bookmark| bookmark.url)
+      .unwrap_or_default();
+    self.start_journal()?;
+    self.discard(path, use_trash)?;
+    self.commit(
+      &format!("Remove bookmark {}", path),
+      CommitInfo {
+        action: "remove",
+        path,
+        url: &url,
+      },
+    )
+  }
+
+  /// Removes every bookmark under the directory, into the trash or for good,
+  /// and what is left in the directory.
+  pub(crate) fn remove_dir(
+    &mut self,
+    sub_path: &str,
+    use_trash: bool,
+  ) -> Result<()> {
+    common::check_path(sub_path).map_err(Error::InvalidPath)?;
+    let paths = self.bookmarks.borrow().list(Some(sub_path));
+    self.start_journal()?;
+    for path in paths {
+      self.discard(&path, use_trash)?;
+    }
+    // Whatever is left in the directory of the directory store
+    let dir_path = self.dir.join(sub_path);
+    if dir_path.is_dir() {
+      fs::remove_dir_all(&dir_path)
+        .map_err(|e| Error::Storage(e.to_string()))?;
+    }
+    let sub_path = sub_path.trim_end_matches('/');
+    self.commit(
+      &format!("Removed directory {} and all bookmarks", sub_path),
+      CommitInfo {
+        action: "remove",
+        path: sub_path,
+        ..Default::default()
+      },
+    )
+  }
+
+  /// Puts a bookmark of the trash back at its path, which must be free.
+  pub(crate) fn restore_from_trash(&mut self, path: &str) -> Result<Bookmark> {
+    check_path(path)?;
+    let mut trash_store = trash::open_trash(&self.dir);
+    if !trash_store.contains(path) {
+      return Err(Error::NotFound(path.to_string()));
+    }
+    let mut bookmark = trash_store.load(path).map_err(Error::Storage)?;
+    if self.bookmarks.borrow().contains(path) {
+      return Err(Error::AlreadyExists(path.to_string()));
+    }
+    bookmark.deleted_at = None;
+    self.save(path, &bookmark)?;
+    trash_store.remove(path).map_err(Error::Storage)?;
+    self.commit(
+      &format!("Restore bookmark {} from the trash", path),
+      CommitInfo {
+        action: "restore",
+        path,
+        url: &bookmark.url,
+      },
+    )?;
+    Ok(bookmark)
+  }
+
+  /// Every bookmark under the directory, or of the store, sorted by path.
+  /// Files that are not valid bookmarks are skipped.
+  pub fn list(
+    &self,
+    sub_path: Option<&str>,
+  ) -> Result<Vec<(String, Bookmark)>> {
+    if let Some(sub_path) = sub_path {
+      common::check_path(sub_path).map_err(Error::InvalidPath)?;
+    }
+    Ok(
+      self
+        .bookmarks
+        .borrow()
+        .load_all(sub_path)
+        .into_iter()
+        .filter_map(|(path, bookmark)| Some((path, bookmark.ok()?)))
+        .collect(),
+    )
+  }
+
+  /// Bookmarks matching every word of the query, ignoring the case, in their
+  /// path, URL, title, description, keyword or tags.
+  pub fn search(&self, query: &str) -> Result<Vec<(String, Bookmark)>> {
+    let words: Vec<String> =
+      query.split_whitespace().map(str::to_lowercase).collect();
+    let mut found = self.list(None)?;
+    found.retain(|(path, bookmark)| {
+      let fields: Vec<String> = [
+        Some(path),
+        Some(&bookmark.url),
+        bookmark.title.as_ref(),
+        bookmark.description.as_ref(),
+        bookmark.keyword.as_ref(),
+      ]
+      .into_iter()
+      .flatten()
+      .chain(&bookmark.tags)
+      .map(|field| field.to_lowercase())
+      .collect();
+      words
+        .iter()
+        .all(|word| fields.iter().any(|field| field.contains(word.as_str())))
+    });
+    Ok(found)
+  }
+
+  /// A keyword is a single segment, used by no other bookmark.
+  fn check_keyword(&self, path: &str, keyword: &str) -> Result<()> {
+    common::check_keyword(keyword).map_err(Error::InvalidPath)?;
+    let owner = common::find_keyword_owner(
+      self.bookmarks.borrow().as_ref(),
+      keyword,
+      path,
+    );
+    match owner {
+      Some(owner) => Err(Error::KeywordTaken {
+        keyword: keyword.to_string(),
+        path: owner,
+      }),
+      None => Ok(()),
+    }
+  }
+
+  fn check_url(&self, url: &str) -> Result<()> {
+    common::check_url_with(url, self.config.allowed_schemes.as_deref())
+      .map_err(Error::InvalidUrl)
+  }
+
+  fn save(&self, path: &str, bookmark: &Bookmark) -> Result<()> {
+    self.start_journal()?;
+    let mut bookmarks = self.bookmarks.borrow_mut();
+    bookmarks.save(path, bookmark).map_err(Error::Storage)?;
+    if self.verbose {
+      println!("Bookmark file stored at {}", bookmarks.location(path));
+    }
+    Ok(())
+  }
+
+  /// Removes the bookmark from the store, moving it into the trash first.
+  fn discard(&self, path: &str, use_trash: bool) -> Result<()> {
+    let mut bookmarks = self.bookmarks.borrow_mut();
+    if use_trash {
+      trash::move_to_trash(&self.dir, bookmarks.as_mut(), path)
+    } else {
+      bookmarks.remove(path)
+    }
+    .map_err(Error::Storage)
+  }
+
+  /// Takes the snapshot the journal compares the change about to be made
+  /// with, when git is disabled. Reading the store writes nothing.
+  fn start_journal(&self) -> Result<()> {
+    if self.config.git {
+      return Ok(());
+    }
+    journal::start(&self.dir, self.bookmarks.borrow().as_ref())
+      .map_err(Error::Storage)
+  }
+
+  /// Commits the change and pushes it according to `push_mode`, or records
+  /// it in the journal without git.
+  fn commit(&self, comment: &str, info: CommitInfo) -> Result<()> {
+    if self.verbose {
+      common::announce_push(&self.config);
+    }
+    common::commit_changes(
+      &self.config,
+      &self.dir,
+      self.bookmarks.borrow().as_ref(),
+      comment,
+      &info,
+    )
+    .map_err(Error::Git)
+  }
+}
+
+/// Prints the error of a command and exits, unless only committing the saved
+/// change failed.
+pub(crate) fn exit_on_error<T>(result: Result<T>) -> Option<T> {
+  match result {
+    Ok(value) => Some(value),
+    Err(Error::Git(e)) => {
+      eprintln!("{}", e);
+      None
+    }
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }
+  }
+}
+
+fn check_path(path: &str) -> Result<()> {
+  common::check_bookmark_path(path).map_err(Error::InvalidPath)
+}
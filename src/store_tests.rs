@@ -0,0 +1,229 @@
+use super::{Config, Error, Store, StoreBackend};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn test_dir(name: &str) -> PathBuf {
+  let dir = env::temp_dir().join(format!("tempesta-library-{}", name));
+  let _ = fs::remove_dir_all(&dir);
+  dir
+}
+
+fn tags(tags: &[&str]) -> Vec<String> {
+  tags.iter().map(|tag| tag.to_string()).collect()
+}
+
+#[test]
+fn store_add_get_update() {
+  let dir = test_dir("add-get-update");
+  let mut store = Store::open(&dir).unwrap();
+  let added = store
+    .add("docs/rust", "https://doc.rust-lang.org", &tags(&["rust"]))
+    .unwrap();
+  assert!(added.created_at.is_some());
+  assert!(dir.join("docs/rust.toml").is_file());
+  let bookmark = store.get("docs/rust").unwrap();
+  assert_eq!(bookmark.url, "https://doc.rust-lang.org");
+  assert_eq!(bookmark.tags, ["rust"]);
+  let updated = store
+    .update("docs/rust", "https://doc.rust-lang.org/std", &[])
+    .unwrap();
+  assert_eq!(updated.created_at, added.created_at);
+  assert!(updated.updated_at.is_some());
+  assert_eq!(store.get("docs/rust").unwrap().url, updated.url);
+  store.set_keyword("docs/rust", Some("rs")).unwrap();
+  assert_eq!(
+    store.get("docs/rust").unwrap().keyword.as_deref(),
+    Some("rs")
+  );
+  assert_eq!(store.search("rs").unwrap().len(), 1);
+  assert!(matches!(
+    store.set_keyword("docs/rust", Some("a/b")),
+    Err(Error::InvalidPath(_))
+  ));
+  let added = store
+    .add_with_keyword("docs/std", "https://doc.rust-lang.org/std", &[], None)
+    .unwrap();
+  assert_eq!(added.keyword, None);
+  assert!(matches!(
+    store.set_keyword("docs/std", Some("RS")),
+    Err(Error::KeywordTaken { path, .. }) if path == "docs/rust"
+  ));
+  assert!(matches!(
+    store.add_with_keyword("docs/core", "https://test.local", &[], Some("rs")),
+    Err(Error::KeywordTaken { .. })
+  ));
+  assert!(!store
+    .list(None)
+    .unwrap()
+    .iter()
+    .any(|(p, _)| p == "docs/core"));
+  // Nothing changes unless every field is valid
+  assert!(matches!(
+    store.update_with_keyword("docs/std", "javascript:", &[], Some(Some("s"))),
+    Err(Error::InvalidUrl(_))
+  ));
+  assert_eq!(store.get("docs/std").unwrap().keyword, None);
+  let updated = store
+    .update_with_keyword("docs/rust", &updated.url, &[], Some(None))
+    .unwrap();
+  assert_eq!(updated.keyword, None);
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn store_errors() {
+  let dir = test_dir("errors");
+  let mut store = Store::open(&dir).unwrap();
+  store.add("one", "https://test.local/one", &[]).unwrap();
+  assert!(matches!(
+    store.add("one", "https://test.local/other", &[]),
+    Err(Error::AlreadyExists(_))
+  ));
+  assert!(matches!(
+    store.add("with space", "https://test.local", &[]),
+    Err(Error::InvalidPath(_))
+  ));
+  for path in ["../escape", "a/./b", "a//b", "/root", ".git/config", "dir/"] {
+    assert!(matches!(
+      store.add(path, "https://test.local", &[]),
+      Err(Error::InvalidPath(_))
+    ));
+  }
+  assert!(matches!(
+    store.add("two", "javascript:alert(1)", &[]),
+    Err(Error::InvalidUrl(_))
+  ));
+  assert!(matches!(store.get("missing"), Err(Error::NotFound(_))));
+  assert!(matches!(
+    store.update("missing", "https://test.local", &[]),
+    Err(Error::NotFound(_))
+  ));
+  store.add("two", "https://test.local/two", &[]).unwrap();
+  assert!(matches!(
+    store.r#move("one", "two"),
+    Err(Error::AlreadyExists(_))
+  ));
+  assert_eq!(
+    store.get("missing").unwrap_err().to_string(),
+    "Bookmark not found: missing"
+  );
+  assert!(matches!(
+    Store::from_config_file(dir.join("missing.toml")),
+    Err(Error::Config(_))
+  ));
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn store_move_remove() {
+  let dir = test_dir("move-remove");
+  let mut store = Store::open(&dir).unwrap();
+  store.add("a/one", "https://test.local/one", &[]).unwrap();
+  store.r#move("a/one", "b/one").unwrap();
+  assert!(!dir.join("a").exists());
+  assert_eq!(store.get("b/one").unwrap().url, "https://test.local/one");
+  let removed = store.remove("b/one").unwrap();
+  assert_eq!(removed.url, "https://test.local/one");
+  assert!(matches!(store.get("b/one"), Err(Error::NotFound(_))));
+  // Removed bookmarks go to the trash by default
+  let trashed = fs::read_to_string(dir.join(".trash/b/one.toml")).unwrap();
+  assert!(trashed.contains("deleted_at"));
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn store_list_search() {
+  let dir = test_dir("list-search");
+  let mut store = Store::with_config(Config {
+    dir: dir.display().to_string(),
+    store_backend: Some(StoreBackend::Json),
+    ..Default::default()
+  })
+  .unwrap();
+  store
+    .add("dev/rust", "https://rust-lang.org", &tags(&["lang"]))
+    .unwrap();
+  store
+    .add("dev/go", "https://go.dev", &tags(&["lang", "google"]))
+    .unwrap();
+  store.add("news", "https://news.local", &[]).unwrap();
+  assert!(dir.join("bookmarks.json").is_file());
+  let paths = |found: Vec<(String, super::Bookmark)>| -> Vec<String> {
+    found.into_iter().map(|(path, _)| path).collect()
+  };
+  assert_eq!(
+    paths(store.list(None).unwrap()),
+    ["dev/go", "dev/rust", "news"]
+  );
+  assert_eq!(
+    paths(store.list(Some("dev")).unwrap()),
+    ["dev/go", "dev/rust"]
+  );
+  assert_eq!(paths(store.search("LANG").unwrap()), ["dev/go", "dev/rust"]);
+  assert_eq!(paths(store.search("lang google").unwrap()), ["dev/go"]);
+  assert_eq!(paths(store.search("news.local").unwrap()), ["news"]);
+  assert!(store.search("missing").unwrap().is_empty());
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn store_commits() {
+  let dir = test_dir("commits");
+  fs::create_dir_all(&dir).unwrap();
+  Command::new("git")
+    .args(["init", "--quiet"])
+    .current_dir(&dir)
+    .status()
+    .unwrap();
+  let mut store = Store::with_config(Config {
+    git: true,
+    dir: dir.display().to_string(),
+    git_author_name: Some("Library Test".to_string()),
+    git_author_email: Some("library@tempesta.local".to_string()),
+    commit_template: Some("{action}: {path}".to_string()),
+    ..Default::default()
+  })
+  .unwrap();
+  store.add("one", "https://test.local/one", &[]).unwrap();
+  store.r#move("one", "two").unwrap();
+  let output = Command::new("git")
+    .args(["log", "--format=%s|%an"])
+    .current_dir(&dir)
+    .output()
+    .unwrap();
+  assert_eq!(
+    String::from_utf8_lossy(&output.stdout),
+    "move: two|Library Test\nadd: one|Library Test\n"
+  );
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn store_journal() {
+  let dir = test_dir("journal");
+  let mut store = Store::open(&dir).unwrap();
+  // Reading the store writes nothing
+  assert!(store.list(None).unwrap().is_empty());
+  assert!(!dir.join(".tempesta").exists());
+  store.add("one", "https://test.local/one", &[]).unwrap();
+  store.r#move("one", "two").unwrap();
+  // Without git the changes are journaled for `tempesta undo`, the first one
+  // too
+  let journal: toml::Value = toml::from_str(
+    &fs::read_to_string(dir.join(".tempesta/journal.toml")).unwrap(),
+  )
+  .unwrap();
+  let messages: Vec<&str> = journal["entries"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|entry| entry["message"].as_str().unwrap())
+    .collect();
+  assert_eq!(
+    messages,
+    ["Add bookmark one", "Move bookmark from one to two"]
+  );
+  fs::remove_dir_all(dir).unwrap();
+}
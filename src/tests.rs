@@ -1,9 +1,12 @@
 use assert_cmd::Command;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
-use super::methods::config::Config;
+use tempesta::{Config, PushMode, StoreBackend};
 
 #[allow(deprecated)]
 fn cargo_bin() -> Result<Command, Box<dyn std::error::Error>> {
@@ -14,17 +17,13 @@ fn get_home() -> String {
   let home_path =
     PathBuf::from(env::var("HOME").expect("HOME environment variable not set"));
   let home_string = home_path.to_str().expect("Cannot convert HOME to str");
-  return home_string.to_string();
+  home_string.to_string()
 }
 fn get_test_config_file_path(home_str: &str, name: &str) -> String {
-  let test_config_file_path =
-    format!("{}/.config/tempesta/test-tempesta-{}.toml", &home_str, name);
-  return test_config_file_path;
+  format!("{}/.config/tempesta/test-tempesta-{}.toml", &home_str, name)
 }
 fn get_test_bookmark_dir_path(home_str: &str, name: &str) -> String {
-  let test_bookmark_dir_path =
-    format!("{}/.test-bookmark-store-{}/", &home_str, name);
-  return test_bookmark_dir_path;
+  format!("{}/.test-bookmark-store-{}/", &home_str, name)
 }
 fn test_setup(name: &str) -> (String, String) {
   let home_str = get_home();
@@ -32,6 +31,8 @@ fn test_setup(name: &str) -> (String, String) {
   let test_bookmark_dir_path = get_test_bookmark_dir_path(&home_str, name);
   println!("config: {}", test_config_file_path);
   println!("store: {}", test_bookmark_dir_path);
+  // init keeps the settings of a config file left by an earlier run
+  test_cleanup(name);
 
   // Create parent directories if they don't exist
   if let Some(parent) = PathBuf::from(&test_config_file_path).parent() {
@@ -44,7 +45,103 @@ fn test_setup(name: &str) -> (String, String) {
     .write_stdin(format!("{}\nno\n", &test_bookmark_dir_path))
     .assert()
     .success();
-  return (test_config_file_path, test_bookmark_dir_path);
+  (test_config_file_path, test_bookmark_dir_path)
+}
+/// Same as `test_setup` but with the store tracked by git.
+fn test_setup_git(name: &str) -> (String, String) {
+  let home_str = get_home();
+  let test_config_file_path = get_test_config_file_path(&home_str, name);
+  let test_bookmark_dir_path = get_test_bookmark_dir_path(&home_str, name);
+  if let Some(parent) = PathBuf::from(&test_config_file_path).parent() {
+    fs::create_dir_all(parent).expect("Failed to create config directory");
+  }
+  // A store left by an earlier run would keep its repository and remote
+  test_cleanup(name);
+  fs::create_dir_all(&test_bookmark_dir_path)
+    .expect("Failed to create store directory");
+  cargo_bin_git()
+    .arg("init")
+    .args(["--config", &test_config_file_path])
+    .write_stdin(format!("{}\nyes\n\nyes\n", &test_bookmark_dir_path))
+    .assert()
+    .success();
+  (test_config_file_path, test_bookmark_dir_path)
+}
+/// Runs tempesta with a git identity, as the test machine may have none.
+fn cargo_bin_git() -> Command {
+  let mut command = cargo_bin().unwrap();
+  // The merge driver runs `tempesta` from the PATH
+  let bin_dir_path = PathBuf::from(command.get_program())
+    .parent()
+    .unwrap()
+    .to_path_buf();
+  let paths = env::var_os("PATH").unwrap_or_default();
+  let path = env::join_paths(
+    std::iter::once(bin_dir_path).chain(env::split_paths(&paths)),
+  )
+  .unwrap();
+  command
+    .env("PATH", path)
+    .env("GIT_AUTHOR_NAME", "Tempesta Test")
+    .env("GIT_AUTHOR_EMAIL", "test@tempesta.local")
+    .env("GIT_COMMITTER_NAME", "Tempesta Test")
+    .env("GIT_COMMITTER_EMAIL", "test@tempesta.local");
+  command
+}
+fn append_config(test_config_file_path: &str, lines: &str) {
+  let mut config = fs::read_to_string(test_config_file_path)
+    .expect("Cannot read test config file");
+  config.push_str(lines);
+  fs::write(test_config_file_path, config)
+    .expect("Cannot write test config file");
+}
+fn wait_for_file(path: &str) -> bool {
+  for _ in 0..50 {
+    if fs::metadata(path).is_ok() {
+      return true;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+  }
+  false
+}
+/// Serves a canned raw HTTP response per path on localhost and returns the
+/// base URL of the server.
+fn spawn_http_server(routes: Vec<(&'static str, String)>) -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").expect("Cannot bind");
+  let base_url = format!("http://{}", listener.local_addr().unwrap());
+  std::thread::spawn(move || {
+    for mut stream in listener.incoming().filter_map(|s| s.ok()) {
+      let mut request = Vec::new();
+      let mut buffer = [0; 1024];
+      while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buffer) {
+          Ok(0) | Err(_) => break,
+          Ok(n) => request.extend_from_slice(&buffer[..n]),
+        }
+      }
+      let request = String::from_utf8_lossy(&request);
+      let path = request.split_whitespace().nth(1).unwrap_or("/");
+      let response = routes
+        .iter()
+        .find(|(route, _)| *route == path)
+        .map(|(_, response)| response.clone())
+        .unwrap_or_else(|| http_response("404 Not Found", "", ""));
+      let _ = stream.write_all(response.as_bytes());
+    }
+  });
+  base_url
+}
+fn http_response(status: &str, headers: &str, body: &str) -> String {
+  format!(
+    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    headers,
+    body.len(),
+    body
+  )
+}
+fn stdout_of(assert: assert_cmd::assert::Assert) -> String {
+  String::from_utf8_lossy(&assert.get_output().stdout).to_string()
 }
 fn test_cleanup(name: &str) {
   let home_str = get_home();
@@ -68,6 +165,7 @@ fn tempesta_init() {
     format!("{}/.config/tempesta/test-tempesta-{}.toml", &home_str, name);
   let test_bookmark_dir_path =
     format!("{}/.test-bookmark-store-{}/", &home_str, name);
+  test_cleanup(name);
 
   // Create parent directories if they don't exist
   if let Some(parent) = PathBuf::from(&test_config_file_path).parent() {
@@ -97,13 +195,31 @@ fn tempesta_init() {
     "Config file was not created [{}]",
     &test_config_file_path
   );
-  let config_string = fs::read_to_string(&test_config_file_path).expect(
-    &format!("Canot read config file {}", &test_config_file_path),
-  );
+  let config_string = fs::read_to_string(&test_config_file_path)
+    .unwrap_or_else(|_| {
+      panic!("Canot read config file {}", &test_config_file_path)
+    });
   let config: Config = toml::from_str(&config_string)
     .expect("Cannot parse config toml into Config");
   assert_eq!(&config.dir, &test_bookmark_dir_path);
   // TODO: check also other parameter
+  // Running init again keeps the settings it does not ask for
+  append_config(
+    &test_config_file_path,
+    "push_mode = \"manual\"\nstore_backend = \"json\"\n",
+  );
+  cargo_bin()
+    .unwrap()
+    .arg("init")
+    .args(["--config", &test_config_file_path])
+    .write_stdin(format!("{}\nno\n", &test_bookmark_dir_path))
+    .assert()
+    .success();
+  let config: Config =
+    toml::from_str(&fs::read_to_string(&test_config_file_path).unwrap())
+      .unwrap();
+  assert_eq!(config.push_mode, Some(PushMode::Manual));
+  assert_eq!(config.store_backend, Some(StoreBackend::Json));
   test_cleanup(name);
 }
 
@@ -134,7 +250,8 @@ fn tempesta_add() {
     ])
     .assert()
     .success()
-    .stdout(output_add);
+    .stdout(output_add)
+    .stderr("");
   // add (again but this time overwrite)
   let output_add_overwrite = format!(
     concat!(
@@ -159,6 +276,298 @@ fn tempesta_add() {
     .assert()
     .success()
     .stdout(output_add_overwrite);
+  // A bookmark path cannot name a directory
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "dir/",
+      "https://dir.test.local",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .failure();
+  assert!(fs::metadata(format!("{}dir", &test_bookmark_dir_path)).is_err());
+  // A broken bookmark file does not prevent adding others
+  fs::write(format!("{}broken.toml", &test_bookmark_dir_path), "url = [")
+    .unwrap();
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "other",
+      "https://other.test.local",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_add_auto_path() {
+  let name = "add-auto-path";
+  let (test_config_file_path, _test_bookmark_dir_path) = test_setup(name);
+  for expected_path in ["docs.rs/serde", "docs.rs/serde-2"] {
+    let output = stdout_of(
+      cargo_bin()
+        .unwrap()
+        .args([
+          "add",
+          "https://www.docs.rs/Serde/latest/?q=1",
+          "rust",
+          "--config",
+          &test_config_file_path,
+        ])
+        .assert()
+        .success(),
+    );
+    assert!(output.ends_with(&format!(
+      "Bookmark added successfully as {}\n",
+      expected_path
+    )));
+  }
+  append_config(
+    &test_config_file_path,
+    "path_template = \"inbox/{title}\"\n",
+  );
+  let output = stdout_of(
+    cargo_bin()
+      .unwrap()
+      .args([
+        "add",
+        "mailto:Team@Test.Local",
+        "--config",
+        &test_config_file_path,
+      ])
+      .assert()
+      .success(),
+  );
+  assert!(output.ends_with(
+    "Bookmark added successfully as inbox/mailto-team-test-local\n"
+  ));
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_add_fetch() {
+  let name = "add-fetch";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  let html = concat!(
+    "<html><head><title>Fallback title</title>",
+    "<meta property=\"og:title\" content=\"Rust &amp; CLIs\">",
+    "<meta name=\"description\" content=\"Writing CLIs in Rust\">",
+    "<meta name=\"keywords\" content=\"Rust, Command Line, rust\">",
+    "</head><body></body></html>",
+  );
+  let base_url = spawn_http_server(vec![(
+    "/article",
+    http_response("200 OK", "Content-Type: text/html\r\n", html),
+  )]);
+  let output_add = format!(
+    concat!(
+      "Title: Rust & CLIs\n",
+      "Suggested tags: command-line\n",
+      "Bookmark file stored at {}articles/rust.toml\n",
+      "Bookmark added successfully as articles/rust\n",
+    ),
+    &test_bookmark_dir_path
+  );
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "articles/rust",
+      &format!("{}/article", &base_url),
+      "rust",
+      "--fetch",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success()
+    .stdout(output_add);
+  let bookmark = fs::read_to_string(format!(
+    "{}articles/rust.toml",
+    &test_bookmark_dir_path
+  ))
+  .unwrap();
+  assert!(bookmark.contains("title = \"Rust & CLIs\""));
+  assert!(bookmark.contains("description = \"Writing CLIs in Rust\""));
+  // Pages that cannot be fetched are stored without metadata
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "articles/missing",
+      &format!("{}/missing", &base_url),
+      "--fetch",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_batch() {
+  let name = "batch";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup_git(name);
+  let run = |args: &[&str], stdin: &str| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &test_config_file_path])
+      .write_stdin(stdin)
+      .assert()
+  };
+  let commits = || {
+    let output = std::process::Command::new("git")
+      .args(["rev-list", "--count", "HEAD"])
+      .current_dir(&test_bookmark_dir_path)
+      .output()
+      .unwrap();
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+  };
+  let before = commits();
+  let batch = concat!(
+    "# comments and blank lines are skipped\n",
+    "\n",
+    "add one https://test.local/one a b\n",
+    "add 'dir/two' \"https://test.local/two\"\n",
+    "{\"command\": \"add\", \"path\": \"three\", \"url\": \"https://test.local/three\", \"tags\": [\"c\"]}\n",
+    "update one https://test.local/uno a\n",
+    "move dir/two two\n",
+    "remove three\n",
+  );
+  let output = stdout_of(run(&["batch"], batch).success());
+  assert!(output.ends_with("Batch applied: 6 changes\n"));
+  run(&["list"], "").stdout(concat!(
+    "one :: https://test.local/uno\n",
+    "two :: https://test.local/two\n"
+  ));
+  assert_eq!(
+    commits(),
+    (before.parse::<usize>().unwrap() + 1).to_string()
+  );
+  // A single invalid line aborts the whole batch
+  let batch_file = format!("{}batch.txt", &test_bookmark_dir_path);
+  fs::write(
+    &batch_file,
+    "add four https://test.local/four\nremove missing\nadd bad\nadd dir/ https://test.local/dir\n",
+  )
+  .unwrap();
+  run(&["batch", "--file", &batch_file], "")
+    .failure()
+    .stderr(concat!(
+      "line 2: bookmark missing does not exist\n",
+      "line 3: usage: add <path> <url> [tags...]\n",
+      "line 4: Invalid path dir/: a bookmark path cannot end with '/'\n",
+      "Batch aborted: nothing was written.\n"
+    ));
+  assert!(
+    fs::metadata(format!("{}four.toml", &test_bookmark_dir_path)).is_err()
+  );
+  // Bookmarks that do not load abort the batch before anything is written
+  let broken_file = format!("{}broken.toml", &test_bookmark_dir_path);
+  fs::write(&broken_file, "url = [").unwrap();
+  let output = run(
+    &["batch"],
+    "add four https://test.local/four\nmove broken fixed\n",
+  )
+  .failure();
+  let stderr = String::from_utf8_lossy(&output.get_output().stderr).to_string();
+  assert!(stderr.starts_with("line 2: bookmark broken cannot be loaded: "));
+  assert!(
+    fs::metadata(format!("{}four.toml", &test_bookmark_dir_path)).is_err()
+  );
+  fs::remove_file(&broken_file).unwrap();
+  // A destination ending with `/` keeps the name, as with `tempesta move`
+  let batch = "move two archive/\nupdate archive/two https://test.local/2\n";
+  run(&["batch"], batch).success();
+  run(&["list"], "").stdout(concat!(
+    "archive/two :: https://test.local/2\n",
+    "one :: https://test.local/uno\n"
+  ));
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_check() {
+  let name = "check";
+  let (test_config_file_path, _test_bookmark_dir_path) = test_setup(name);
+  let base_url = spawn_http_server(vec![
+    ("/ok", http_response("200 OK", "", "ok")),
+    (
+      "/moved",
+      http_response("301 Moved Permanently", "Location: /ok\r\n", ""),
+    ),
+    (
+      "/temporary",
+      http_response("302 Found", "Location: /ok\r\n", ""),
+    ),
+  ]);
+  for (path, route) in [
+    ("check/ok", "/ok"),
+    ("check/moved", "/moved"),
+    ("check/temporary", "/temporary"),
+    ("check/missing", "/missing"),
+  ] {
+    cargo_bin()
+      .unwrap()
+      .args([
+        "add",
+        path,
+        &format!("{}{}", &base_url, route),
+        "--config",
+        &test_config_file_path,
+      ])
+      .assert()
+      .success();
+  }
+  let output_check = format!(
+    concat!(
+      "check/missing :: {0}/missing :: 404\n",
+      "check/moved :: {0}/moved :: 301 -> {0}/ok :: 200\n",
+      "check/ok :: {0}/ok :: 200\n",
+      "check/temporary :: {0}/temporary :: 302 -> {0}/ok :: 200\n",
+      "Checked 4 bookmarks: 1 broken\n",
+    ),
+    &base_url
+  );
+  cargo_bin()
+    .unwrap()
+    .args(["check", "check/", "--config", &test_config_file_path])
+    .assert()
+    .failure()
+    .stdout(output_check);
+  // Only permanent redirects are fixed
+  cargo_bin()
+    .unwrap()
+    .args([
+      "check",
+      "check/*o*",
+      "--fix-redirects",
+      "--concurrency",
+      "1",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  for (path, url) in [
+    ("check/moved", format!("{}/ok\n", &base_url)),
+    ("check/temporary", format!("{}/temporary\n", &base_url)),
+  ] {
+    cargo_bin()
+      .unwrap()
+      .args(["get", path, "--config", &test_config_file_path])
+      .assert()
+      .success()
+      .stdout(url);
+  }
   test_cleanup(name);
 }
 
@@ -172,6 +581,68 @@ fn tempesta_config() {
   // TODO
 }
 
+#[test]
+fn tempesta_dedupe() {
+  let name = "dedupe";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  for (path, url, tag) in [
+    (
+      "jira/board",
+      "https://Jira.test.local:443/board/?b=2&a=1",
+      "jira",
+    ),
+    (
+      "team/board",
+      "https://jira.test.local/board?a=1&utm_source=x&b=2#:~:text=board",
+      "team",
+    ),
+    ("other", "https://jira.test.local/other", "other"),
+    // Routes of a single page app are different pages
+    ("app/a", "https://app.test.local/#/a", "app"),
+    ("app/b", "https://app.test.local/#/b", "app"),
+  ] {
+    cargo_bin()
+      .unwrap()
+      .args([
+        "add",
+        path,
+        url,
+        tag,
+        "--raw",
+        "--config",
+        &test_config_file_path,
+      ])
+      .assert()
+      .success();
+  }
+  let output_dedupe_list = concat!(
+    "Duplicates of https://jira.test.local/board?a=1&b=2:\n",
+    "  1) jira/board :: https://Jira.test.local:443/board/?b=2&a=1\n",
+    "  2) team/board :: https://jira.test.local/board?a=1&utm_source=x&b=2#:~:text=board\n",
+  );
+  cargo_bin()
+    .unwrap()
+    .args(["dedupe", "--list", "--config", &test_config_file_path])
+    .assert()
+    .success()
+    .stdout(output_dedupe_list);
+  cargo_bin()
+    .unwrap()
+    .args(["dedupe", "--config", &test_config_file_path])
+    .write_stdin("2\n")
+    .assert()
+    .success();
+  assert!(
+    fs::metadata(format!("{}jira/board.toml", &test_bookmark_dir_path))
+      .is_err()
+  );
+  let bookmark =
+    fs::read_to_string(format!("{}team/board.toml", &test_bookmark_dir_path))
+      .unwrap();
+  assert!(bookmark.contains("tags = [\"team\", \"jira\"]"));
+  test_cleanup(name);
+}
+
 #[test]
 fn tempesta_list() {
   let name = "list";
@@ -217,6 +688,138 @@ fn tempesta_list() {
   test_cleanup(name);
 }
 
+#[test]
+fn tempesta_log() {
+  let name = "log";
+  let (test_config_file_path, _) = test_setup_git(name);
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &test_config_file_path])
+      .assert()
+      .success()
+  };
+  run(&[
+    "add",
+    "test",
+    "https://test.local/old",
+    "one",
+    "two",
+    "--raw",
+  ]);
+  run(&[
+    "update",
+    "test",
+    "https://test.local/new",
+    "two",
+    "three",
+    "--raw",
+  ]);
+  run(&["move", "test", "moved/test"]);
+  let output = stdout_of(run(&["log", "moved/test"]));
+  let lines: Vec<&str> = output.lines().collect();
+  assert!(
+    lines[0].ends_with("Tempesta Test: Move bookmark from test to moved/test")
+  );
+  assert_eq!(lines[1], "  moved test -> moved/test");
+  assert!(lines[2].ends_with("Tempesta Test: Update bookmark test"));
+  assert_eq!(
+    lines[3],
+    "  url https://test.local/old -> https://test.local/new"
+  );
+  assert_eq!(lines[4], "  tags +three, -one");
+  assert!(lines[5].ends_with("Tempesta Test: Add bookmark test"));
+  assert_eq!(lines[6], "  added test :: https://test.local/old");
+  assert_eq!(lines[7], "  tags one, two");
+  assert_eq!(lines.len(), 8);
+  let output = stdout_of(run(&["log", "moved/test", "--json"]));
+  let entries: serde_json::Value = serde_json::from_str(&output).unwrap();
+  assert_eq!(entries[0]["change"], "moved");
+  assert_eq!(entries[0]["previous_path"], "test");
+  assert_eq!(entries[1]["previous_url"], "https://test.local/old");
+  assert_eq!(entries[1]["tags_added"], serde_json::json!(["three"]));
+  assert_eq!(entries[2]["change"], "added");
+  // Store-wide changelog
+  let output = stdout_of(run(&["log", "--limit", "2"]));
+  let lines: Vec<&str> = output.lines().collect();
+  assert_eq!(lines[1], "  moved test -> moved/test");
+  assert_eq!(lines[3], "  modified test");
+  assert_eq!(lines.len(), 4);
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_merge_driver() {
+  let name = "merge-driver";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup_git(name);
+  let gitattributes =
+    fs::read_to_string(format!("{}.gitattributes", &test_bookmark_dir_path))
+      .unwrap();
+  assert_eq!(
+    gitattributes,
+    "*.toml merge=tempesta\nbookmarks.json merge=tempesta\n"
+  );
+  let driver = std::process::Command::new("git")
+    .args(["config", "merge.tempesta.driver"])
+    .current_dir(&test_bookmark_dir_path)
+    .output()
+    .unwrap();
+  assert_eq!(
+    String::from_utf8_lossy(&driver.stdout),
+    "tempesta merge-driver %O %A %B\n"
+  );
+  let write = |file: &str, content: &str| {
+    let path = format!("{}{}", &test_bookmark_dir_path, file);
+    fs::write(&path, content).unwrap();
+    path
+  };
+  let merge = |base: &str, current: &str, other: &str| {
+    cargo_bin()
+      .unwrap()
+      .args(["merge-driver", base, current, other])
+      .args(["--config", &test_config_file_path])
+      .assert()
+  };
+  let base = write(
+    "base",
+    "url = \"https://test.local\"\ntags = [\"a\", \"b\"]\n",
+  );
+  let current = write(
+    "current",
+    "url = \"https://test.local\"\ntags = [\"a\", \"b\", \"c\"]\nupdated_at = \"2025-01-01T00:00:00Z\"\n",
+  );
+  let other = write(
+    "other",
+    "url = \"https://test.local/new\"\ntags = [\"a\", \"d\"]\nupdated_at = \"2025-02-01T00:00:00Z\"\n",
+  );
+  merge(&base, &current, &other).success();
+  assert_eq!(
+    fs::read_to_string(&current).unwrap(),
+    concat!(
+      "url = \"https://test.local/new\"\n",
+      "tags = [\"a\", \"c\", \"d\"]\n",
+      "updated_at = \"2025-02-01T00:00:00Z\"\n"
+    )
+  );
+  // Only URLs changed on both sides conflict
+  let current =
+    write("current", "url = \"https://test.local/one\"\ntags = []\n");
+  merge(&base, &current, &other).code(1);
+  assert_eq!(
+    fs::read_to_string(&current).unwrap(),
+    concat!(
+      "<<<<<<< current\n",
+      "url = \"https://test.local/one\"\n",
+      "=======\n",
+      "url = \"https://test.local/new\"\n",
+      ">>>>>>> other\n",
+      "tags = [\"d\"]\n",
+      "updated_at = \"2025-02-01T00:00:00Z\"\n"
+    )
+  );
+  test_cleanup(name);
+}
+
 #[test]
 fn tempesta_move() {
   let name = "move";
@@ -272,33 +875,1563 @@ fn tempesta_move() {
 
 #[test]
 fn tempesta_open() {
-  // TODO
+  let name = "open";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  append_config(&test_config_file_path, "opener = \"touch\"\n");
+  let marker_path = format!("{}opened-marker", &test_bookmark_dir_path);
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "local/marker",
+      &marker_path,
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  cargo_bin()
+    .unwrap()
+    .args(["open", "local/marker", "--config", &test_config_file_path])
+    .assert()
+    .success();
+  assert!(
+    wait_for_file(&marker_path),
+    "Opener was not run for {}",
+    &marker_path
+  );
+  // Web URLs go to the browser profile set for the directory or on the
+  // command line
+  append_config(
+    &test_config_file_path,
+    &format!(
+      concat!(
+        "[browsers]\n",
+        "work = \"mkdir -p {}browser-work/{{url}}\"\n",
+        "personal = \"mkdir -p {}browser-personal/{{url}}\"\n",
+        "[directory_browsers]\n",
+        "work = \"work\"\n",
+      ),
+      &test_bookmark_dir_path, &test_bookmark_dir_path
+    ),
+  );
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "work/ci",
+      "https://ci.test.local",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  cargo_bin()
+    .unwrap()
+    .args(["open", "work/ci", "--config", &test_config_file_path])
+    .assert()
+    .success();
+  assert!(wait_for_file(&format!(
+    "{}browser-work/https:/ci.test.local",
+    &test_bookmark_dir_path
+  )));
+  cargo_bin()
+    .unwrap()
+    .args([
+      "open",
+      "work/ci",
+      "--browser",
+      "personal",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  assert!(wait_for_file(&format!(
+    "{}browser-personal/https:/ci.test.local",
+    &test_bookmark_dir_path
+  )));
+  cargo_bin()
+    .unwrap()
+    .args([
+      "open",
+      "work/ci",
+      "--browser",
+      "unknown",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .failure();
+  // Schemes outside of allowed_schemes are rejected
+  let config = fs::read_to_string(&test_config_file_path).unwrap();
+  fs::write(
+    &test_config_file_path,
+    format!("allowed_schemes = [\"https\"]\n{}", config),
+  )
+  .unwrap();
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "mail/team",
+      "mailto:team@test.local",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .failure();
+  test_cleanup(name);
 }
 
 #[test]
-fn tempesta_remove() {
-  let name = "remove";
-  let (test_config_file_path, _test_bookmakr_dir_path) = test_setup(name); // Fixed unused variable
+fn tempesta_open_many() {
+  let name = "open-many";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  append_config(
+    &test_config_file_path,
+    &format!(
+      concat!(
+        "browser = \"mkdir -p {}opened/{{url}}\"\n",
+        "open_confirm_threshold = 2\n",
+      ),
+      &test_bookmark_dir_path
+    ),
+  );
+  for (path, url, tag) in [
+    ("oncall/grafana", "https://grafana.test.local", "oncall"),
+    ("oncall/pager", "https://pager.test.local", "oncall"),
+    ("projects/a/ci", "https://a-ci.test.local", "ci"),
+    ("projects/b/ci", "https://b-ci.test.local", "oncall"),
+  ] {
+    cargo_bin()
+      .unwrap()
+      .args(["add", path, url, tag, "--config", &test_config_file_path])
+      .assert()
+      .success();
+  }
+  let opened = |host: &str| {
+    wait_for_file(&format!(
+      "{}opened/https:/{}",
+      &test_bookmark_dir_path, host
+    ))
+  };
+  // Directory
+  cargo_bin()
+    .unwrap()
+    .args(["open", "oncall/", "--config", &test_config_file_path])
+    .assert()
+    .success();
+  assert!(opened("grafana.test.local"));
+  assert!(opened("pager.test.local"));
+  // Glob
   cargo_bin()
     .unwrap()
     .args([
-      "add",
-      "move/test",
-      "https://test.local",
-      "test",
+      "open",
+      "projects/*/ci",
+      "--delay",
+      "10ms",
       "--config",
       &test_config_file_path,
     ])
     .assert()
     .success();
-  // remove (removing the last entry in the bookmark-store-test removes it completely)
-  let output_remove = "Bookmark removed successfully as move/test\n";
+  assert!(opened("a-ci.test.local"));
+  assert!(opened("b-ci.test.local"));
+  // Glob classes
+  for (glob, hosts) in [
+    (
+      "projects/[a-c]/ci",
+      ["a-ci.test.local", "b-ci.test.local"].as_slice(),
+    ),
+    ("projects/[!a]/ci", ["b-ci.test.local"].as_slice()),
+  ] {
+    fs::remove_dir_all(format!("{}opened", &test_bookmark_dir_path)).unwrap();
+    cargo_bin()
+      .unwrap()
+      .args(["open", glob, "--delay", "10ms"])
+      .args(["--config", &test_config_file_path])
+      .assert()
+      .success();
+    for host in hosts {
+      assert!(opened(host), "{} did not open {}", glob, host);
+    }
+    if hosts.len() == 1 {
+      assert!(!fs::exists(format!(
+        "{}opened/https:/a-ci.test.local",
+        &test_bookmark_dir_path
+      ))
+      .unwrap());
+    }
+  }
+  // Tag, above the confirmation threshold
   cargo_bin()
     .unwrap()
-    .args(["remove", "move/test", "--config", &test_config_file_path])
+    .args([
+      "open",
+      "--tag",
+      "oncall",
+      "--config",
+      &test_config_file_path,
+    ])
+    .write_stdin("n\n")
     .assert()
     .success()
-    .stdout(output_remove);
+    .stdout("Open 3 bookmarks? (y/N): Operation cancelled.\n");
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_open_quoted_browser() {
+  let name = "open-quoted-browser";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  // Quoted arguments keep their spaces
+  append_config(
+    &test_config_file_path,
+    &format!(
+      "browser = \"mkdir -p '{}opened dir/{{url}}'\"\n",
+      &test_bookmark_dir_path
+    ),
+  );
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "docs",
+      "https://docs.test.local",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  cargo_bin()
+    .unwrap()
+    .args(["open", "docs", "--config", &test_config_file_path])
+    .assert()
+    .success();
+  assert!(wait_for_file(&format!(
+    "{}opened dir/https:/docs.test.local",
+    &test_bookmark_dir_path
+  )));
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_queue() {
+  let name = "queue";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  append_config(
+    &test_config_file_path,
+    &format!(
+      "browser = \"mkdir -p {}opened/{{url}}\"\n",
+      &test_bookmark_dir_path
+    ),
+  );
+  let output_queue_add = format!(
+    concat!(
+      "Bookmark file stored at {}inbox/a-test-local-first.toml\n",
+      "Bookmark queued successfully as inbox/a-test-local-first\n",
+    ),
+    &test_bookmark_dir_path
+  );
+  cargo_bin()
+    .unwrap()
+    .args([
+      "queue",
+      "add",
+      "https://a.test.local/first",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success()
+    .stdout(output_queue_add);
+  cargo_bin()
+    .unwrap()
+    .args([
+      "queue",
+      "add",
+      "https://b.test.local/second",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  cargo_bin()
+    .unwrap()
+    .args(["queue", "list", "--config", &test_config_file_path])
+    .assert()
+    .success()
+    .stdout(concat!(
+      "inbox/a-test-local-first :: https://a.test.local/first\n",
+      "inbox/b-test-local-second :: https://b.test.local/second\n",
+    ));
+  let output_queue_next = format!(
+    concat!(
+      "Bookmark file stored at {}inbox/a-test-local-first.toml\n",
+      "Opened inbox/a-test-local-first and marked it as read\n",
+    ),
+    &test_bookmark_dir_path
+  );
+  cargo_bin()
+    .unwrap()
+    .args(["queue", "next", "--config", &test_config_file_path])
+    .assert()
+    .success()
+    .stdout(output_queue_next);
+  assert!(wait_for_file(&format!(
+    "{}opened/https:/a.test.local/first",
+    &test_bookmark_dir_path
+  )));
+  cargo_bin()
+    .unwrap()
+    .args(["queue", "list", "--config", &test_config_file_path])
+    .assert()
+    .success()
+    .stdout("inbox/b-test-local-second :: https://b.test.local/second\n");
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_remove() {
+  let name = "remove";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "move/test",
+      "https://test.local",
+      "test",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success();
+  // remove (removing the last entry in the bookmark-store-test removes it completely)
+  let output_remove = "Bookmark removed successfully as move/test\n";
+  cargo_bin()
+    .unwrap()
+    .args(["remove", "move/test", "--config", &test_config_file_path])
+    .assert()
+    .success()
+    .stdout(output_remove);
+  // Absolute paths and `..` are refused before anything is deleted
+  let kept_dir_path = format!("{}kept", &test_bookmark_dir_path);
+  fs::create_dir_all(&kept_dir_path).unwrap();
+  for path in [kept_dir_path.as_str(), "../kept"] {
+    cargo_bin()
+      .unwrap()
+      .args(["remove", path, "--config", &test_config_file_path])
+      .write_stdin("y\n")
+      .assert()
+      .failure();
+  }
+  assert!(fs::metadata(&kept_dir_path).is_ok());
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_restore() {
+  let name = "restore";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup_git(name);
+  let bookmark_file_path = format!("{}test.toml", &test_bookmark_dir_path);
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &test_config_file_path])
+      .assert()
+      .success()
+  };
+  run(&["add", "test", "https://test.local/old", "--raw"]);
+  run(&["update", "test", "https://test.local/new", "--raw"]);
+  run(&["remove", "test"]);
+  // Without --at the version before the last change comes back
+  let output = stdout_of(run(&["restore", "test"]));
+  assert!(output.starts_with("Bookmark test restored from "));
+  let content = fs::read_to_string(&bookmark_file_path).unwrap();
+  assert!(content.contains("https://test.local/new"));
+  let output = stdout_of(run(&["restore", "test", "--at", "HEAD~3"]));
+  assert!(output.starts_with("Bookmark test restored from "));
+  let content = fs::read_to_string(&bookmark_file_path).unwrap();
+  assert!(content.contains("https://test.local/old"));
+  // undo reverts the restore, then the previous one
+  let short = output.trim_end().rsplit(' ').next().unwrap();
+  run(&["undo"])
+    .stdout(format!("Undid \"Restore bookmark test from {}\"\n", short));
+  let content = fs::read_to_string(&bookmark_file_path).unwrap();
+  assert!(content.contains("https://test.local/new"));
+  run(&["undo"]);
+  assert!(fs::metadata(&bookmark_file_path).is_err());
+  run(&["undo"]).stdout("Undid \"Remove bookmark test\"\n");
+  assert!(fs::metadata(&bookmark_file_path).is_ok());
+  test_cleanup(name);
+}
+
+/// Creates a bare repository and makes it the remote of the stores.
+fn setup_git_remote(name: &str, stores: &[(&str, &str)]) -> String {
+  let remote_path =
+    format!("{}/.test-bookmark-remote-{}.git", get_home(), name);
+  let _ = fs::remove_dir_all(&remote_path);
+  let git = |dir: &str, args: &[&str]| {
+    let status = std::process::Command::new("git")
+      .args(args)
+      .current_dir(dir)
+      .output()
+      .expect("Cannot run git")
+      .status;
+    assert!(status.success(), "git {:?} failed", args);
+  };
+  fs::create_dir_all(&remote_path).unwrap();
+  git(&remote_path, &["init", "--quiet", "--bare"]);
+  for (config_file_path, bookmark_dir_path) in stores {
+    git(
+      bookmark_dir_path,
+      &["remote", "add", "origin", &remote_path],
+    );
+    append_config(config_file_path, &format!("remote = {:?}\n", remote_path));
+  }
+  remote_path
+}
+
+#[test]
+fn tempesta_sync() {
+  let (config_a, store_a) = test_setup_git("sync-a");
+  let (config_b, store_b) = test_setup_git("sync-b");
+  // Stores initialized on two machines have unrelated histories, while two
+  // stores initialized in the same second share the same first commit
+  let amended = std::process::Command::new("git")
+    .args(["commit", "--quiet", "--amend", "--no-edit"])
+    .args(["--date", "2001-01-01T00:00:00"])
+    .current_dir(&store_b)
+    .env("GIT_COMMITTER_DATE", "2001-01-01T00:00:00")
+    .env("GIT_COMMITTER_NAME", "Tempesta Test")
+    .env("GIT_COMMITTER_EMAIL", "test@tempesta.local")
+    .status()
+    .unwrap();
+  assert!(amended.success());
+  let remote_path =
+    setup_git_remote("sync", &[(&config_a, &store_a), (&config_b, &store_b)]);
+  let run = |config: &str, args: &[&str], stdin: &str| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", config])
+      .write_stdin(stdin)
+      .assert()
+  };
+  let read = |store: &str, path: &str| {
+    fs::read_to_string(format!("{}{}.toml", store, path)).unwrap()
+  };
+  run(&config_a, &["add", "shared", "https://test.local", "a"], "").success();
+  let output = stdout_of(run(&config_b, &["sync"], "").success());
+  assert!(output.contains("  added shared\n"));
+  assert!(output.ends_with("Synced: 1 added, 0 changed, 0 removed, 0 moved\n"));
+  // a gets the merge of the two histories
+  run(&config_a, &["sync"], "").success();
+  // Concurrent tag changes are merged
+  run(
+    &config_a,
+    &["update", "shared", "https://test.local", "a", "x"],
+    "",
+  )
+  .success();
+  run(
+    &config_b,
+    &["update", "shared", "https://test.local", "a", "y"],
+    "",
+  )
+  .success();
+  run(&config_b, &["sync"], "").success();
+  assert!(read(&store_b, "shared").contains(r#"tags = ["a", "y", "x"]"#));
+  run(&config_a, &["sync"], "").success();
+  assert!(read(&store_a, "shared").contains(r#"tags = ["a", "y", "x"]"#));
+  // Divergent URLs are asked for, aborting leaves the store untouched
+  run(
+    &config_a,
+    &["update", "shared", "https://test.local/a", "a"],
+    "",
+  )
+  .success();
+  run(
+    &config_b,
+    &["update", "shared", "https://test.local/b", "a"],
+    "",
+  )
+  .success();
+  run(&config_b, &["sync"], "a\n").failure();
+  assert!(read(&store_b, "shared").contains("https://test.local/b"));
+  assert!(fs::metadata(format!("{}.git/MERGE_HEAD", store_b)).is_err());
+  let output = stdout_of(run(&config_b, &["sync"], "2\n").success());
+  assert!(output.contains("  2) https://test.local/a (remote)\n"));
+  assert!(read(&store_b, "shared").contains("https://test.local/a"));
+  // Local commits are rebased on the remote ones
+  let config = fs::read_to_string(&config_a).unwrap();
+  fs::write(
+    &config_a,
+    config.replace("rebase_on_pull = false", "rebase_on_pull = true"),
+  )
+  .unwrap();
+  run(&config_a, &["add", "local", "https://test.local/local"], "").success();
+  run(&config_a, &["sync"], "").success();
+  assert!(fs::metadata(format!("{}.git/rebase-merge", store_a)).is_err());
+  assert!(read(&store_a, "local").contains("https://test.local/local"));
+  let output = stdout_of(run(&config_b, &["sync"], "").success());
+  assert!(output.contains("  added local\n"));
+  test_cleanup("sync-a");
+  test_cleanup("sync-b");
+  fs::remove_dir_all(remote_path).unwrap();
+}
+
+#[test]
+fn tempesta_sync_single_file() {
+  let (config_a, store_a) = test_setup_git("sync-file-a");
+  let (config_b, store_b) = test_setup_git("sync-file-b");
+  let amended = std::process::Command::new("git")
+    .args(["commit", "--quiet", "--amend", "--no-edit"])
+    .args(["--date", "2001-01-01T00:00:00"])
+    .current_dir(&store_b)
+    .env("GIT_COMMITTER_DATE", "2001-01-01T00:00:00")
+    .env("GIT_COMMITTER_NAME", "Tempesta Test")
+    .env("GIT_COMMITTER_EMAIL", "test@tempesta.local")
+    .status()
+    .unwrap();
+  assert!(amended.success());
+  append_config(&config_a, "store_backend = \"toml\"\n");
+  append_config(&config_b, "store_backend = \"toml\"\n");
+  let remote_path = setup_git_remote(
+    "sync-file",
+    &[(&config_a, &store_a), (&config_b, &store_b)],
+  );
+  let run = |config: &str, args: &[&str], stdin: &str| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", config])
+      .write_stdin(stdin)
+      .assert()
+  };
+  let read = |store: &str| {
+    fs::read_to_string(format!("{}bookmarks.toml", store)).unwrap()
+  };
+  run(&config_a, &["add", "shared", "https://test.local", "a"], "").success();
+  let output = stdout_of(run(&config_b, &["sync"], "").success());
+  assert!(output.contains("  added shared\n"));
+  run(&config_a, &["sync"], "").success();
+  // Bookmarks of the same file are merged one by one
+  run(
+    &config_a,
+    &["update", "shared", "https://test.local", "a", "x"],
+    "",
+  )
+  .success();
+  run(
+    &config_a,
+    &["add", "only-a", "https://test.local/only-a"],
+    "",
+  )
+  .success();
+  run(
+    &config_b,
+    &["update", "shared", "https://test.local", "a", "y"],
+    "",
+  )
+  .success();
+  run(
+    &config_b,
+    &["add", "only-b", "https://test.local/only-b"],
+    "",
+  )
+  .success();
+  run(&config_a, &["sync"], "").success();
+  let output = stdout_of(run(&config_b, &["sync"], "").success());
+  assert!(output.contains("  added only-a\n"));
+  assert!(output.contains("  modified shared\n"));
+  let merged = read(&store_b);
+  assert!(merged.contains(r#"tags = ["a", "y", "x"]"#));
+  assert!(merged.contains("https://test.local/only-a"));
+  assert!(merged.contains("https://test.local/only-b"));
+  // Divergent URLs are asked for
+  run(
+    &config_a,
+    &["update", "shared", "https://test.local/a", "a"],
+    "",
+  )
+  .success();
+  run(&config_a, &["sync"], "").success();
+  run(
+    &config_b,
+    &["update", "shared", "https://test.local/b", "a"],
+    "",
+  )
+  .success();
+  let output = stdout_of(run(&config_b, &["sync"], "2\n").success());
+  assert!(output.contains("  2) https://test.local/a (remote)\n"));
+  assert!(read(&store_b).contains("https://test.local/a"));
+  assert!(!read(&store_b).contains("https://test.local/b"));
+  // A database cannot be merged
+  let config = fs::read_to_string(&config_b).unwrap();
+  fs::write(
+    &config_b,
+    config.replace("store_backend = \"toml\"", "store_backend = \"sqlite\""),
+  )
+  .unwrap();
+  run(&config_b, &["sync"], "").failure().stderr(
+    "The sqlite store backend cannot be synced: git cannot merge the \
+    database. Migrate to another backend to sync.\n",
+  );
+  test_cleanup("sync-file-a");
+  test_cleanup("sync-file-b");
+  fs::remove_dir_all(remote_path).unwrap();
+}
+
+#[test]
+fn tempesta_push_mode() {
+  let (config, store) = test_setup_git("push-mode");
+  let remote_path = setup_git_remote("push-mode", &[(&config, &store)]);
+  append_config(&config, "push_mode = \"manual\"\n");
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &config])
+      .assert()
+  };
+  let unpushed = || {
+    let output = stdout_of(run(&["status"]).success());
+    let remote = output
+      .lines()
+      .find_map(|line| line.strip_prefix("Remote:"))
+      .unwrap();
+    let (_, counts) = remote.split_once('(').unwrap();
+    counts.split_once(' ').unwrap().0.to_string()
+  };
+  run(&["sync"]).success();
+  assert_eq!(unpushed(), "0");
+  // Manual mode only pushes on sync
+  run(&["add", "manual", "https://test.local/manual"]).success();
+  assert_eq!(unpushed(), "1");
+  run(&["sync"]).success();
+  assert_eq!(unpushed(), "0");
+  // Deferred mode commits even when the remote cannot be reached
+  let config_content = fs::read_to_string(&config).unwrap();
+  fs::write(
+    &config,
+    config_content
+      .replace("push_mode = \"manual\"", "push_mode = \"deferred\""),
+  )
+  .unwrap();
+  let git = |args: &[&str]| {
+    std::process::Command::new("git")
+      .args(args)
+      .current_dir(&store)
+      .output()
+      .unwrap();
+  };
+  let offline_path = format!("{}-offline", remote_path);
+  git(&["remote", "set-url", "origin", &offline_path]);
+  run(&["add", "offline", "https://test.local/offline"]).success();
+  run(&["add", "plane", "https://test.local/plane"]).success();
+  assert_eq!(unpushed(), "2");
+  git(&["remote", "set-url", "origin", &remote_path]);
+  run(&["sync"]).success();
+  assert_eq!(unpushed(), "0");
+  test_cleanup("push-mode");
+  fs::remove_dir_all(remote_path).unwrap();
+}
+
+#[test]
+fn tempesta_commit_message() {
+  let (config, store) = test_setup_git("commit-message");
+  let editor_path = format!("{}/.test-editor-commit-message.sh", get_home());
+  fs::write(
+    &editor_path,
+    "#!/bin/sh\necho 'title = \"Std\"' >> \"$1\"\n",
+  )
+  .unwrap();
+  fs::set_permissions(&editor_path, fs::Permissions::from_mode(0o755)).unwrap();
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &config])
+      .env("EDITOR", &editor_path)
+      .assert()
+  };
+  let last_commit = || {
+    let output = std::process::Command::new("git")
+      .args(["log", "-1", "--format=%s|%an|%ae|%b"])
+      .current_dir(&store)
+      .output()
+      .unwrap();
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+  };
+  run(&["add", "docs/rust", "https://doc.rust-lang.org/std"]).success();
+  run(&["edit", "docs/rust"]).success();
+  assert!(last_commit().starts_with("Edit bookmark docs/rust|Tempesta Test|"));
+  append_config(
+    &config,
+    concat!(
+      "commit_template = \"{action} {path} ({host}) by {user}\"\n",
+      "conventional_commits = true\n",
+      "git_author_name = \"Store Bot\"\n",
+      "git_author_email = \"bot@store.local\"\n",
+    ),
+  );
+  run(&["add", "docs/cargo", "https://doc.rust-lang.org/cargo"]).success();
+  assert_eq!(
+    last_commit(),
+    "feat: add docs/cargo (doc.rust-lang.org) by Store Bot\
+     |Store Bot|bot@store.local|Created-by: tempesta"
+  );
+  run(&["remove", "docs/rust"]).success();
+  assert!(last_commit()
+    .starts_with("chore: remove docs/rust (doc.rust-lang.org) by Store Bot|"));
+  // The body of the undo commit is kept
+  run(&["undo"]).success();
+  assert!(last_commit().starts_with("revert: undo  () by Store Bot|"));
+  assert!(last_commit().contains("Reverts: "));
+  assert!(fs::metadata(format!("{}docs/rust.toml", store)).is_ok());
+  test_cleanup("commit-message");
+  fs::remove_file(editor_path).unwrap();
+}
+
+#[test]
+fn tempesta_verify() {
+  let (config, store) = test_setup_git("verify");
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &config])
+      .assert()
+  };
+  let key_path = |name: &str| format!("{}/.test-verify-{}", get_home(), name);
+  for name in ["allowed", "other"] {
+    let _ = fs::remove_file(key_path(name));
+    std::process::Command::new("ssh-keygen")
+      .args(["-q", "-t", "ed25519", "-N", "", "-C", name, "-f"])
+      .arg(key_path(name))
+      .status()
+      .unwrap();
+  }
+  let public_key =
+    fs::read_to_string(format!("{}.pub", key_path("allowed"))).unwrap();
+  let allowed_signers = key_path("signers");
+  fs::write(
+    &allowed_signers,
+    format!("# Store maintainers\nallowed@test.local {}", public_key),
+  )
+  .unwrap();
+  // The list cannot come from the store being verified
+  fs::copy(&allowed_signers, format!("{}.allowed_signers", store)).unwrap();
+  append_config(
+    &config,
+    &format!("allowed_signers = \"{}.allowed_signers\"\n", store),
+  );
+  run(&["verify"]).failure().stderr(format!(
+    "The allowed signers must be outside the store: {}.allowed_signers\n",
+    store
+  ));
+  fs::remove_file(format!("{}.allowed_signers", store)).unwrap();
+  let base_config = fs::read_to_string(&config)
+    .unwrap()
+    .replace(&format!("{}.allowed_signers", store), &allowed_signers);
+  fs::write(&config, &base_config).unwrap();
+  let sign_with = |key: &str| {
+    fs::write(
+      &config,
+      format!(
+        "{}sign_commits = true\nsigning_format = \"ssh\"\nsigning_key = {:?}\n",
+        base_config,
+        key_path(key)
+      ),
+    )
+    .unwrap();
+  };
+  sign_with("allowed");
+  run(&["add", "runbook", "https://test.local/runbook"]).success();
+  let output = stdout_of(run(&["verify"]).success());
+  assert_eq!(output, "Verified 1 commits\n");
+  // A URL swap signed by another key is reported
+  sign_with("other");
+  run(&["update", "runbook", "https://phishing.local/runbook"]).success();
+  let output = stdout_of(run(&["verify"]).failure());
+  assert!(output.contains(
+    " Update bookmark runbook: signed by a key that is not allowed (SHA256:"
+  ));
+  fs::write(&config, &base_config).unwrap();
+  run(&["update", "runbook", "https://test.local/runbook"]).success();
+  let output = stdout_of(run(&["verify"]).failure());
+  assert!(output.contains(" Update bookmark runbook: not signed\n"));
+  // Only the given range is verified
+  run(&["verify", "HEAD~2"]).success();
+  // A range without bookmark changes verifies nothing and fails
+  run(&["verify", "HEAD~3"])
+    .failure()
+    .stderr("None of the 1 commits changes *.toml: nothing was verified\n");
+  test_cleanup("verify");
+  for name in ["allowed", "other"] {
+    fs::remove_file(key_path(name)).unwrap();
+    fs::remove_file(format!("{}.pub", key_path(name))).unwrap();
+  }
+  fs::remove_file(allowed_signers).unwrap();
+}
+
+#[cfg(feature = "native-git")]
+#[test]
+fn tempesta_native_git() {
+  let (config, store) = test_setup_git("native-git");
+  let remote_path = setup_git_remote("native-git", &[(&config, &store)]);
+  append_config(&config, "git_backend = \"native\"\n");
+  let last_remote_commit = || {
+    let output = std::process::Command::new("git")
+      .args(["log", "-1", "--all", "--format=%s|%an|%b"])
+      .current_dir(&remote_path)
+      .output()
+      .unwrap();
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+  };
+  // Commits and pushes without the git binary
+  cargo_bin_git()
+    .args([
+      "add",
+      "native",
+      "https://test.local/native",
+      "--config",
+      &config,
+    ])
+    .env("PATH", "/nonexistent")
+    .assert()
+    .success();
+  assert_eq!(
+    last_remote_commit(),
+    "Add bookmark native|Tempesta Test|Created-by: tempesta"
+  );
+  // Deferred pushes do not need the git binary either
+  append_config(&config, "push_mode = \"deferred\"\n");
+  cargo_bin_git()
+    .args([
+      "add",
+      "deferred",
+      "https://test.local/deferred",
+      "--config",
+      &config,
+    ])
+    .env("PATH", "/nonexistent")
+    .assert()
+    .success();
+  assert_eq!(
+    last_remote_commit(),
+    "Add bookmark deferred|Tempesta Test|Created-by: tempesta"
+  );
+  let native_config = fs::read_to_string(&config).unwrap();
+  fs::write(
+    &config,
+    native_config
+      .replace("git_backend = \"native\"", "git_backend = \"cli\"")
+      .replace("push_mode = \"deferred\"\n", ""),
+  )
+  .unwrap();
+  cargo_bin_git()
+    .args(["add", "cli", "https://test.local/cli", "--config", &config])
+    .assert()
+    .success();
+  assert_eq!(
+    last_remote_commit(),
+    "Add bookmark cli|Tempesta Test|Created-by: tempesta"
+  );
+  test_cleanup("native-git");
+  fs::remove_dir_all(remote_path).unwrap();
+}
+
+#[test]
+fn tempesta_status() {
+  let (config, store) = test_setup_git("status");
+  let remote_path = setup_git_remote("status", &[(&config, &store)]);
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &config])
+      .assert()
+  };
+  run(&["add", "valid", "https://test.local/valid"]).success();
+  run(&["add", "edited", "https://test.local/edited"]).success();
+  fs::write(
+    format!("{}edited.toml", store),
+    "url = \"https://x.local\"\n",
+  )
+  .unwrap();
+  fs::write(format!("{}broken.toml", store), "url = \n").unwrap();
+  let output = stdout_of(run(&["status"]).success());
+  assert!(output.contains("Bookmarks:                1\n"));
+  assert!(output.contains("Git:                      enabled\n"));
+  assert!(output.contains("Remote:                   origin/"));
+  assert!(output.contains("(0 ahead, 0 behind)\n"));
+  assert!(output.contains("Uncommitted changes:\n"));
+  assert!(output.contains("  M edited.toml\n"));
+  assert!(output.contains("  ?? broken.toml\n"));
+  assert!(output.contains("Invalid bookmark files:\n  broken: "));
+  assert!(output.contains("  edited: missing field `tags`\n"));
+  let output = stdout_of(run(&["status", "--json"]).success());
+  let status: serde_json::Value = serde_json::from_str(&output).unwrap();
+  assert_eq!(status["bookmarks"], 1);
+  assert_eq!(status["git"], true);
+  assert_eq!(status["ahead"], 0);
+  assert_eq!(status["uncommitted"][0]["status"], "M");
+  assert_eq!(status["errors"].as_array().unwrap().len(), 2);
+  test_cleanup("status");
+  fs::remove_dir_all(remote_path).unwrap();
+}
+
+#[test]
+fn tempesta_migrate_store() {
+  let (config, store) = test_setup_git("migrate-store");
+  let run = |args: &[&str]| {
+    cargo_bin_git()
+      .args(args)
+      .args(["--config", &config])
+      .assert()
+  };
+  run(&["add", "dir/one", "https://test.local/one"]).success();
+  run(&["add", "two", "https://test.local/two", "tag"]).success();
+  let output = stdout_of(run(&["migrate-store", "--to", "json"]).success());
+  assert_eq!(
+    output,
+    "Migrated 2 bookmarks from the directory to the json store
+"
+  );
+  assert!(!Path::new(&format!("{}two.toml", store)).exists());
+  assert!(Path::new(&format!("{}bookmarks.json", store)).is_file());
+  run(&["get", "dir/one"])
+    .success()
+    .stdout("https://test.local/one\n");
+  run(&["add", "three", "https://test.local/three"]).success();
+  run(&["move", "three", "dir/three"]).success();
+  run(&["log", "two"]).failure().stderr(
+    "The json store backend has no history per bookmark: \
+     migrate to the directory backend.\n",
+  );
+  let backends: &[&str] = if cfg!(feature = "sqlite") {
+    &["sqlite", "toml", "directory"]
+  } else {
+    &["toml", "directory"]
+  };
+  for backend in backends {
+    run(&["migrate-store", "--to", backend]).success();
+  }
+  assert!(!Path::new(&format!("{}bookmarks.json", store)).exists());
+  run(&["list"]).success().stdout(
+    "dir/one :: https://test.local/one\n\
+     dir/three :: https://test.local/three\n\
+     two :: https://test.local/two\n",
+  );
+  run(&["remove", "two", "--permanent"]).success();
+  run(&["get", "two"]).failure();
+  let output = std::process::Command::new("git")
+    .args(["log", "-1", "--format=%s"])
+    .current_dir(&store)
+    .output()
+    .unwrap();
+  assert_eq!(
+    String::from_utf8_lossy(&output.stdout),
+    "Remove bookmark two\n"
+  );
+  let output = std::process::Command::new("git")
+    .args(["status", "--porcelain"])
+    .current_dir(&store)
+    .output()
+    .unwrap();
+  assert!(output.stdout.is_empty());
+  test_cleanup("migrate-store");
+}
+
+/// Starts `tempesta serve` on a free port and returns it with its base URL.
+#[allow(deprecated)]
+fn start_server(config: &str, args: &[&str]) -> (std::process::Child, String) {
+  let port = TcpListener::bind("127.0.0.1:0")
+    .unwrap()
+    .local_addr()
+    .unwrap()
+    .port();
+  let server =
+    std::process::Command::new(assert_cmd::cargo::cargo_bin("tempesta"))
+      .args(["serve", "--port", &port.to_string(), "--config", config])
+      .args(args)
+      .env("GIT_AUTHOR_NAME", "Tempesta Test")
+      .env("GIT_AUTHOR_EMAIL", "test@tempesta.local")
+      .env("GIT_COMMITTER_NAME", "Tempesta Test")
+      .env("GIT_COMMITTER_EMAIL", "test@tempesta.local")
+      .stdout(std::process::Stdio::null())
+      .spawn()
+      .unwrap();
+  for _ in 0..50 {
+    if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+      break;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+  }
+  (server, format!("http://127.0.0.1:{}", port))
+}
+
+/// Status and body of a request, whatever the status.
+fn http(request: ureq::Request, body: Option<&str>) -> (u16, String) {
+  let response = match body {
+    Some(body) => request
+      .set("Content-Type", "application/json")
+      .send_string(body),
+    None => request.call(),
+  };
+  let response = match response {
+    Ok(response) => response,
+    Err(ureq::Error::Status(_, response)) => response,
+    Err(e) => panic!("{}", e),
+  };
+  let status = response.status();
+  let location = response.header("Location").map(str::to_string);
+  let body = response.into_string().unwrap();
+  (status, location.unwrap_or(body))
+}
+
+#[test]
+fn tempesta_serve() {
+  let (config, store) = test_setup_git("serve");
+  cargo_bin_git()
+    .args(["add", "dev/rust", "https://rust-lang.org", "lang"])
+    .args(["--config", &config])
+    .assert()
+    .success();
+  let (mut server, base) = start_server(&config, &[]);
+  let agent = ureq::AgentBuilder::new().redirects(0).build();
+  let (status, body) = http(
+    agent.post(&format!("{}/bookmarks", base)),
+    Some(
+      r#"{"path": "dev/go", "url": "https://go.dev/?utm_source=x", "tags": ["lang", "google"], "keyword": "golang"}"#,
+    ),
+  );
+  assert_eq!(status, 201);
+  let added: serde_json::Value = serde_json::from_str(&body).unwrap();
+  assert_eq!(added["url"], "https://go.dev/");
+  assert_eq!(added["keyword"], "golang");
+  let (status, _) = http(
+    agent.post(&format!("{}/bookmarks", base)),
+    Some(r#"{"path": "dev/go", "url": "https://go.dev"}"#),
+  );
+  assert_eq!(status, 409);
+  // A keyword resolves a single bookmark
+  let (status, _) = http(
+    agent.post(&format!("{}/bookmarks", base)),
+    Some(
+      r#"{"path": "dev/golang", "url": "https://go.dev", "keyword": "GoLang"}"#,
+    ),
+  );
+  assert_eq!(status, 409);
+  let (status, _) = http(
+    agent.request("PATCH", &format!("{}/bookmarks/dev/rust", base)),
+    Some(r#"{"keyword": "golang"}"#),
+  );
+  assert_eq!(status, 409);
+  // An invalid field refuses the whole change
+  let (status, _) = http(
+    agent.request("PATCH", &format!("{}/bookmarks/dev/rust", base)),
+    Some(r#"{"keyword": "rs", "url": "javascript:alert(1)"}"#),
+  );
+  assert_eq!(status, 400);
+  let (_, body) =
+    http(agent.get(&format!("{}/bookmarks/dev/rust", base)), None);
+  let current: serde_json::Value = serde_json::from_str(&body).unwrap();
+  assert!(current["keyword"].is_null());
+  let (status, body) = http(
+    agent.get(&format!("{}/bookmarks?tag=lang&tag=google", base)),
+    None,
+  );
+  assert_eq!(status, 200);
+  let found: serde_json::Value = serde_json::from_str(&body).unwrap();
+  assert_eq!(found.as_array().unwrap().len(), 1);
+  assert_eq!(found[0]["path"], "dev/go");
+  let (status, body) = http(
+    agent.request("PATCH", &format!("{}/bookmarks/dev/rust", base)),
+    Some(r#"{"tags": ["lang", "mozilla"], "keyword": "rs"}"#),
+  );
+  assert_eq!(status, 200);
+  let updated: serde_json::Value = serde_json::from_str(&body).unwrap();
+  assert_eq!(updated["url"], "https://rust-lang.org");
+  assert_eq!(updated["tags"][1], "mozilla");
+  assert_eq!(updated["keyword"], "rs");
+  assert_eq!(
+    http(agent.get(&format!("{}/go/rs", base)), None),
+    (302, "https://rust-lang.org".to_string())
+  );
+  assert_eq!(
+    http(agent.get(&format!("{}/go/dev/rust", base)), None),
+    (302, "https://rust-lang.org".to_string())
+  );
+  assert_eq!(
+    http(agent.get(&format!("{}/go/missing", base)), None).0,
+    404
+  );
+  let (status, body) = http(agent.get(&format!("{}/?q=google", base)), None);
+  assert_eq!(status, 200);
+  assert!(body.contains("<summary>dev</summary>"));
+  assert!(body.contains("<a href=\"https://go.dev/\""));
+  assert!(!body.contains("rust-lang.org"));
+  let (status, _) =
+    http(agent.delete(&format!("{}/bookmarks/dev/go", base)), None);
+  assert_eq!(status, 200);
+  assert_eq!(
+    http(agent.get(&format!("{}/bookmarks/dev/go", base)), None).0,
+    404
+  );
+  server.kill().unwrap();
+  server.wait().unwrap();
+  let output = std::process::Command::new("git")
+    .args(["log", "--format=%s"])
+    .current_dir(&store)
+    .output()
+    .unwrap();
+  // The update of the tags and the keyword is a single commit
+  assert!(String::from_utf8_lossy(&output.stdout).starts_with(concat!(
+    "Remove bookmark dev/go\n",
+    "Update bookmark dev/rust\n",
+    "Add bookmark dev/go\n"
+  )));
+  test_cleanup("serve");
+}
+
+/// Status code of a request written as is, to send any header.
+fn raw_http(base: &str, request: &str) -> u16 {
+  let address = base.trim_start_matches("http://");
+  let mut stream = std::net::TcpStream::connect(address).unwrap();
+  stream.write_all(request.as_bytes()).unwrap();
+  let mut response = String::new();
+  let _ = stream.read_to_string(&mut response);
+  response.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn tempesta_serve_checks() {
+  let name = "serve-checks";
+  let (config, store) = test_setup(name);
+  let (mut server, base) = start_server(&config, &[]);
+  let host = base.trim_start_matches("http://");
+  let post = |headers: &str, body: &str| {
+    raw_http(
+      &base,
+      &format!(
+        "POST /bookmarks HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}",
+        headers,
+        body.len(),
+        body
+      ),
+    )
+  };
+  let body = r#"{"path": "dev/rust", "url": "https://rust-lang.org"}"#;
+  let json = "Content-Type: application/json\r\n";
+  // Other sites cannot reach the API, even through their own domain
+  assert_eq!(post(&format!("Host: evil.test\r\n{}", json), body), 403);
+  assert_eq!(post(json, body), 403);
+  assert_eq!(
+    post(
+      &format!("Host: {}\r\nOrigin: http://evil.test\r\n{}", host, json),
+      body
+    ),
+    403
+  );
+  // Changes need a JSON body, of a limited size
+  assert_eq!(
+    post(
+      &format!("Host: {}\r\nContent-Type: text/plain\r\n", host),
+      body
+    ),
+    415
+  );
+  assert_eq!(
+    raw_http(
+      &base,
+      &format!(
+        "POST /bookmarks HTTP/1.1\r\nHost: {}\r\n{}Content-Length: {}\r\n\r\n",
+        host,
+        json,
+        2 * 1024 * 1024
+      ),
+    ),
+    413
+  );
+  // Paths cannot leave the store
+  let agent = ureq::AgentBuilder::new().redirects(0).build();
+  let (status, _) = http(
+    agent.post(&format!("{}/bookmarks", base)),
+    Some(r#"{"path": "../escape", "url": "https://test.local"}"#),
+  );
+  assert_eq!(status, 400);
+  assert!(fs::metadata(format!("{}../escape.toml", store)).is_err());
+  let (status, _) =
+    http(agent.post(&format!("{}/bookmarks", base)), Some(body));
+  assert_eq!(status, 201);
+  // Request and header lines are bounded
+  let long = "a".repeat(8 * 1024);
+  assert_eq!(raw_http(&base, &format!("GET /{}", long)[..8 * 1024]), 414);
+  assert_eq!(
+    raw_http(
+      &base,
+      &format!("GET / HTTP/1.1\r\nX-Long: {}", long)[..16 + 8 * 1024]
+    ),
+    431
+  );
+  assert_eq!(
+    raw_http(
+      &base,
+      &format!("GET / HTTP/1.1\r\n{}", "X-Header: a\r\n".repeat(100))
+    ),
+    431
+  );
+  // Bookmarked URLs that are not allowed are neither linked nor redirected
+  fs::write(
+    format!("{}unsafe.toml", store),
+    "url = \"javascript:alert(1)\"\ntags = []\n",
+  )
+  .unwrap();
+  fs::write(
+    format!("{}split.toml", store),
+    "url = \"https://test.local/\\r\\nSet-Cookie: a=1\"\ntags = []\n",
+  )
+  .unwrap();
+  let (status, page) = http(agent.get(&format!("{}/", base)), None);
+  assert_eq!(status, 200);
+  assert!(page.contains("unsafe"));
+  assert!(!page.contains("href=\"javascript:"));
+  assert!(!page.contains("href=\"https://test.local/"));
+  let (status, _) = http(agent.get(&format!("{}/go/unsafe", base)), None);
+  assert_eq!(status, 400);
+  let (status, _) = http(agent.get(&format!("{}/go/split", base)), None);
+  assert_eq!(status, 400);
+  // Config changes apply without restarting the server
+  append_config(&config, "clean_urls = false\n");
+  let (status, _) = http(
+    agent.post(&format!("{}/bookmarks", base)),
+    Some(r#"{"path": "dev/go", "url": "https://go.dev/?utm_source=x"}"#),
+  );
+  assert_eq!(status, 201);
+  assert!(fs::read_to_string(format!("{}dev/go.toml", store))
+    .unwrap()
+    .contains("utm_source=x"));
+  server.kill().unwrap();
+  server.wait().unwrap();
+  // The changes of the API are journaled like the ones of the CLI
+  cargo_bin()
+    .unwrap()
+    .args(["undo", "--config", &config])
+    .assert()
+    .success();
+  assert!(fs::metadata(format!("{}dev/go.toml", store)).is_err());
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_go_links() {
+  let name = "go-links";
+  let (config, _) = test_setup(name);
+  let run = |args: &[&str]| {
+    cargo_bin()
+      .unwrap()
+      .args(args)
+      .args(["--config", &config])
+      .assert()
+      .success()
+  };
+  run(&["add", "docs/rust", "https://doc.rust-lang.org/"]);
+  run(&[
+    "add",
+    "dev/github",
+    "https://github.com/{*}",
+    "--keyword",
+    "gh",
+  ]);
+  run(&[
+    "add",
+    "dev/issue",
+    "https://github.com/{1}/{2}/issues/{3}?x=1",
+  ]);
+  let (mut server, base) = start_server(&config, &["--go-links"]);
+  let agent = ureq::AgentBuilder::new().redirects(0).build();
+  let go = |link: &str| http(agent.get(&format!("{}/{}", base, link)), None);
+  assert_eq!(
+    go("docs/rust"),
+    (302, "https://doc.rust-lang.org/".to_string())
+  );
+  assert_eq!(
+    go("docs/rust/std/vec"),
+    (302, "https://doc.rust-lang.org/std/vec".to_string())
+  );
+  assert_eq!(
+    go("GH/rust-lang/rust"),
+    (302, "https://github.com/rust-lang/rust".to_string())
+  );
+  assert_eq!(
+    go("go/dev/issue/rust-lang/rust/1"),
+    (
+      302,
+      "https://github.com/rust-lang/rust/issues/1?x=1".to_string()
+    )
+  );
+  assert_eq!(
+    go("gh/a%20b"),
+    (302, "https://github.com/a%20b".to_string())
+  );
+  let (status, body) = go("dcs/rst");
+  assert_eq!(status, 404);
+  assert!(body.contains("<a href=\"/docs/rust\">docs/rust</a>"));
+  assert!(!body.contains("dev/github"));
+  let (status, body) = go("github");
+  assert_eq!(status, 404);
+  assert!(body.contains("<a href=\"/dev/github\">"));
+  cargo_bin()
+    .unwrap()
+    .args([
+      "add",
+      "dev/gitlab",
+      "https://gitlab.com/",
+      "--keyword",
+      "gh",
+    ])
+    .args(["--config", &config])
+    .assert()
+    .failure()
+    .stderr("The keyword gh is already used by dev/github\n");
+  assert_eq!(go("bookmarks").0, 200);
+  server.kill().unwrap();
+  server.wait().unwrap();
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_trash() {
+  let name = "trash";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  let run = |args: &[&str]| {
+    cargo_bin()
+      .unwrap()
+      .args(args)
+      .args(["--config", &test_config_file_path])
+      .assert()
+      .success()
+  };
+  run(&["add", "dir/one", "https://test.local/one"]);
+  run(&["add", "dir/two", "https://test.local/two"]);
+  run(&["add", "three", "https://test.local/three"]);
+  run(&["remove", "dir/one"]);
+  run(&["remove", "three", "--permanent"]);
+  run(&["list"]).stdout("dir/two :: https://test.local/two\n");
+  let output = stdout_of(run(&["trash", "list"]));
+  assert!(output.starts_with("dir/one :: https://test.local/one :: deleted 20"));
+  assert_eq!(output.lines().count(), 1);
+  let output = stdout_of(run(&["trash", "restore", "dir/one"]));
+  assert!(output.ends_with("Bookmark restored successfully as dir/one\n"));
+  let content =
+    fs::read_to_string(format!("{}dir/one.toml", &test_bookmark_dir_path))
+      .unwrap();
+  assert!(!content.contains("deleted_at"));
+  cargo_bin()
+    .unwrap()
+    .args(["remove", "dir", "--config", &test_config_file_path])
+    .write_stdin("y\n")
+    .assert()
+    .success();
+  assert_eq!(stdout_of(run(&["trash", "list"])).lines().count(), 2);
+  run(&["trash", "empty", "--older-than", "1d"])
+    .stdout("Removed 0 bookmarks from the trash\n");
+  run(&["trash", "empty"]).stdout("Removed 2 bookmarks from the trash\n");
+  run(&["trash", "list"]).stdout("The trash is empty.\n");
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_undo() {
+  let name = "undo";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  let bookmark_file_path = format!("{}dir/test.toml", &test_bookmark_dir_path);
+  let run = |args: &[&str]| {
+    cargo_bin()
+      .unwrap()
+      .args(args)
+      .args(["--config", &test_config_file_path])
+      .assert()
+      .success()
+  };
+  // Like a store created before the journal: the first change is recorded
+  fs::remove_file(format!(
+    "{}.tempesta/snapshot.toml",
+    &test_bookmark_dir_path
+  ))
+  .unwrap();
+  run(&["add", "dir/test", "https://test.local/old"]);
+  run(&["update", "dir/test", "https://test.local/new"]);
+  // Removing a directory asks for confirmation and defaults to no
+  cargo_bin()
+    .unwrap()
+    .args(["remove", "dir", "--config", &test_config_file_path])
+    .write_stdin("\n")
+    .assert()
+    .success()
+    .stdout(concat!(
+      "Bookmark not found as a file, but 'dir' is a directory. ",
+      "Do you want to delete it and all its bookmarks? [y/N] ",
+      "Operation canceled.\n"
+    ));
+  cargo_bin()
+    .unwrap()
+    .args(["remove", "dir", "--config", &test_config_file_path])
+    .write_stdin("y\n")
+    .assert()
+    .success();
+  assert!(fs::metadata(&bookmark_file_path).is_err());
+  run(&["undo"]).stdout("Undid \"Removed directory dir and all bookmarks\"\n");
+  let content = fs::read_to_string(&bookmark_file_path).unwrap();
+  assert!(content.contains("https://test.local/new"));
+  run(&["undo"]).stdout("Undid \"Update bookmark dir/test\"\n");
+  let content = fs::read_to_string(&bookmark_file_path).unwrap();
+  assert!(content.contains("https://test.local/old"));
+  run(&["undo"]).stdout("Undid \"Add bookmark dir/test\"\n");
+  assert!(fs::metadata(&bookmark_file_path).is_err());
+  run(&["undo"]).stdout("Nothing to undo.\n");
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_review() {
+  let name = "review";
+  let (test_config_file_path, test_bookmark_dir_path) = test_setup(name);
+  fs::create_dir_all(format!("{}team", &test_bookmark_dir_path)).unwrap();
+  for (path, content) in [
+    (
+      "team/expired",
+      "url = \"https://expired.test.local\"\ntags = []\nexpires_at = \"2001-01-01\"\n",
+    ),
+    (
+      "team/old",
+      "url = \"https://old.test.local\"\ntags = []\ncreated_at = \"2001-01-01T10:00:00Z\"\n",
+    ),
+    (
+      "team/fresh",
+      "url = \"https://fresh.test.local\"\ntags = []\nexpires_at = \"2999-01-01\"\n",
+    ),
+  ] {
+    fs::write(format!("{}{}.toml", &test_bookmark_dir_path, path), content)
+      .unwrap();
+  }
+  let output_review_list = concat!(
+    "team/expired :: https://expired.test.local (expired)\n",
+    "team/old :: https://old.test.local (not visited nor updated in 30 days)\n",
+  );
+  cargo_bin()
+    .unwrap()
+    .args([
+      "review",
+      "--list",
+      "--days",
+      "30",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success()
+    .stdout(output_review_list);
+  // Update the expired bookmark, after a mistyped URL, and delete the old one
+  cargo_bin()
+    .unwrap()
+    .args(["review", "--days", "30", "--config", &test_config_file_path])
+    .write_stdin("u\nnot a url\nhttps://renewed.test.local\nd\n")
+    .assert()
+    .success()
+    .stderr(
+      "Invalid URL. Please use a proper format (e.g., https://example.com).\n",
+    );
+  assert!(fs::read_to_string(format!(
+    "{}team/expired.toml",
+    &test_bookmark_dir_path
+  ))
+  .unwrap()
+  .contains("https://renewed.test.local"));
+  assert!(
+    fs::metadata(format!("{}team/old.toml", &test_bookmark_dir_path)).is_err()
+  );
+  cargo_bin()
+    .unwrap()
+    .args([
+      "review",
+      "--list",
+      "--days",
+      "30",
+      "--config",
+      &test_config_file_path,
+    ])
+    .assert()
+    .success()
+    .stdout("Nothing to review.\n");
+  test_cleanup(name);
+}
+
+#[test]
+fn tempesta_update_clean_url() {
+  let name = "update-clean-url";
+  let (test_config_file_path, _test_bookmark_dir_path) = test_setup(name);
+  append_config(
+    &test_config_file_path,
+    concat!(
+      "tracking_params = [\"utm_*\", \"ref\"]\n",
+      "[[host_rewrites]]\n",
+      "pattern = '^(\\w+)\\.m\\.wiki\\.test$'\n",
+      "replace = '$1.wiki.test'\n",
+      "[[url_rules]]\n",
+      "pattern = '/amp$'\n",
+      "replace = ''\n",
+    ),
+  );
+  let bookmark_path = "wiki/rust";
+  for (args, expected_url) in [
+    (
+      vec![
+        "add",
+        bookmark_path,
+        "https://en.m.wiki.test/Rust?utm_source=x&q=1",
+      ],
+      "https://en.wiki.test/Rust?q=1",
+    ),
+    (
+      vec![
+        "update",
+        bookmark_path,
+        "https://news.test/story/amp?ref=feed",
+      ],
+      "https://news.test/story",
+    ),
+    (
+      vec![
+        "update",
+        bookmark_path,
+        "https://www-site-test.cdn.ampproject.org/c/s/site.test/page",
+      ],
+      "https://site.test/page",
+    ),
+    (
+      vec![
+        "update",
+        bookmark_path,
+        "https://news.test/?ref=feed",
+        "--raw",
+      ],
+      "https://news.test/?ref=feed",
+    ),
+  ] {
+    cargo_bin()
+      .unwrap()
+      .args(args)
+      .args(["--config", &test_config_file_path])
+      .assert()
+      .success();
+    cargo_bin()
+      .unwrap()
+      .args(["get", bookmark_path, "--config", &test_config_file_path])
+      .assert()
+      .success()
+      .stdout(format!("{}\n", expected_url));
+  }
   test_cleanup(name);
 }
 